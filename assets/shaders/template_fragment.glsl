@@ -0,0 +1,8 @@
+#version 330 core
+
+/* INPUTS */
+/* OUTPUTS */
+
+void main() {
+/* FRAGMENT_CODE */
+}
@@ -1,12 +1,17 @@
 use eframe::egui::{self, ImageButton};
 use eframe::epaint::{TextureHandle, Vec2};
+use resources::asset_watcher::AssetWatcher;
 use resources::container::ContainerRef;
 use resources::texture::Texture;
 use structures::scene::Scene;
+use ui::console_view::{ConsoleDock, ConsoleView};
+use ui::key_bindings::Action;
 use ui::scene_view::SceneView;
+use ui::shader_editor_view::ShaderEditorView;
 use ui::ui_traits::*;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 use egui::mutex::Mutex;
 
 pub mod util;
@@ -16,6 +21,21 @@ pub mod structures;
 pub mod nodes;
 
 fn main() {
+    let (flags, files): (Vec<_>, Vec<_>) = std::env::args_os().skip(1).partition(|arg| arg.to_string_lossy().starts_with("--"));
+    for flag in flags {
+        match flag.to_string_lossy().as_ref() {
+            "--no-mesh-cache" => resources::mesh_cache::set_enabled(false),
+            "--repair-meshes" => resources::mesh_validate::set_repair_on_import(true),
+            flag => util::log::warning(util::log::Subsystem::General, format!("unknown option {}", flag)),
+        }
+    }
+    // The scene file given on the command line brings the assets of its project with it
+    if let Some(scene_file) = files.first() {
+        resources::asset_paths::set_scene_file(Some(std::path::Path::new(scene_file)));
+    }
+    for root in resources::asset_paths::get_asset_roots() {
+        util::log::debug(util::log::Subsystem::Resource, format!("asset root: {}", root));
+    }
     let options = eframe::NativeOptions {
         multisampling: 0,
         renderer: eframe::Renderer::Glow,
@@ -32,10 +52,13 @@ fn main() {
 struct MyApp {
     shared_state: Rc<ui::shared_state::SharedState>,
     main_scene: SceneView,
-    left_panel_texture: ContainerRef<Texture>,
-    left_panel_texture_size: Vec2,
-    right_panel_texture: ContainerRef<Texture>,
-    right_panel_texture_size: Vec2,
+    console_view: ConsoleView,
+    shader_editor_view: ShaderEditorView,
+    scene: Arc<Mutex<Scene>>,
+    asset_watcher: AssetWatcher,
+    /* The panel buttons fall back to text when their texture couldn't be loaded */
+    left_panel_texture: Option<(ContainerRef<Texture>, Vec2)>,
+    right_panel_texture: Option<(ContainerRef<Texture>, Vec2)>,
 }
 
 impl MyApp {
@@ -46,38 +69,102 @@ impl MyApp {
             .as_ref()
             .expect("You need to run eframe with the glow backend");
         let ui = &cc.egui_ctx;
-        let scene = Arc::new(Mutex::new(Scene::default(gl).unwrap()));
+        let scene = match Scene::default(gl) {
+            Ok(scene) => scene,
+            Err(err) => {
+                util::log::error("Could not load the default scene, starting with an empty one", &err);
+                Scene::new(resources::container::Container::new())
+            }
+        };
+        let scene = Arc::new(Mutex::new(scene));
         let shared_state = Rc::new(ui::shared_state::SharedState::new(ui));
 
-        let left_panel_texture = shared_state
-            .get_container()
-            .get_texture("left_rectangle.png")
-            .unwrap();
-        let mut left_panel_texture_size = left_panel_texture
-            .lock()
-            .unwrap()
-            .get_handle()
-            .size_vec2();
-        left_panel_texture_size = 16.0 * left_panel_texture_size / left_panel_texture_size.y;
+        let left_panel_texture = Self::panel_texture(&shared_state, "left_rectangle.png");
+        let right_panel_texture = Self::panel_texture(&shared_state, "right_rectangle.png");
 
-        let right_panel_texture = shared_state
-            .get_container()
-            .get_texture("right_rectangle.png")
-            .unwrap();
-        let mut right_panel_texture_size = right_panel_texture
-            .lock()
-            .unwrap()
-            .get_handle()
-            .size_vec2();
-        right_panel_texture_size = 16.0 * right_panel_texture_size / right_panel_texture_size.y;
+        let mut console_view = ConsoleView::new();
+        // Point out straight away anything that went wrong while loading
+        if console_view.get_error_count() > 0 {
+            console_view.toggle();
+        }
 
         Self {
             shared_state: shared_state.clone(),
-            main_scene: SceneView::new(scene, shared_state.clone()),
+            main_scene: SceneView::new(scene.clone(), shared_state.clone()),
+            console_view,
+            shader_editor_view: ShaderEditorView::new(scene.clone(), shared_state.clone()),
+            scene,
+            asset_watcher: AssetWatcher::new(Duration::from_secs(1)),
             left_panel_texture,
-            left_panel_texture_size,
             right_panel_texture,
-            right_panel_texture_size,
+        }
+    }
+
+    /* The texture and the size it is drawn at in the top panel */
+    fn panel_texture(shared_state: &ui::shared_state::SharedState, name: &str) -> Option<(ContainerRef<Texture>, Vec2)> {
+        let texture = shared_state.get_container().get_texture(name)?;
+        let size = texture.lock().unwrap().get_handle().size_vec2();
+        Some((texture, 16.0 * size / size.y))
+    }
+
+    fn panel_button(ui: &mut egui::Ui, texture: &Option<(ContainerRef<Texture>, Vec2)>, text: &str, tint: egui::Color32) -> bool {
+        match texture {
+            Some((texture, size)) => ui.add(ImageButton::new(texture.lock().unwrap().get_handle(), *size).tint(tint)).clicked(),
+            None => ui.add(egui::Button::new(egui::RichText::new(text).color(tint))).clicked(),
+        }
+    }
+}
+
+impl MyApp {
+    /* Hands the asset files that changed to whoever uses them */
+    fn reload_assets(&mut self) {
+        if !self.asset_watcher.is_due() {
+            return;
+        }
+        let mut files = self.scene.lock().get_asset_files();
+        files.extend(self.shared_state.get_container().get_asset_files());
+        for file in &files {
+            self.asset_watcher.watch(file);
+        }
+        let changed = self.asset_watcher.poll();
+        if changed.is_empty() {
+            return;
+        }
+        for file in &changed {
+            match self.shared_state.get_container().reload_texture_file(file) {
+                Ok(true) => util::log::info(util::log::Subsystem::Resource, format!("Reloaded {}", file)),
+                Ok(false) => (),
+                Err(err) => util::log::error(&format!("Could not reload {}, keeping the old image", file), &err),
+            }
+        }
+        self.scene.lock().queue_asset_changes(changed);
+    }
+
+    fn panel_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let (toggle_left, toggle_right, toggle_console) = {
+            let input = ctx.input();
+            let key_bindings = self.shared_state.get_key_bindings();
+            (
+                key_bindings.is_pressed(&input, Action::ToggleLeftPanel),
+                key_bindings.is_pressed(&input, Action::ToggleRightPanel),
+                key_bindings.is_pressed(&input, Action::ToggleConsole),
+            )
+        };
+        if toggle_console {
+            self.console_view.toggle();
+        }
+        if toggle_left {
+            if let Some(state) = self.main_scene.left_panel_state() {
+                *state = !*state;
+            }
+        }
+        if toggle_right {
+            if let Some(state) = self.main_scene.right_panel_state() {
+                *state = !*state;
+            }
         }
     }
 }
@@ -108,25 +195,43 @@ impl eframe::App for MyApp {
                 false => ui.style().visuals.weak_text_color(),
             };
             ui.horizontal(|ui| {
-                if ui.add(ImageButton::new(self.left_panel_texture.lock().unwrap().get_handle(), self.left_panel_texture_size).tint(left_tint)).clicked() {
+                if Self::panel_button(ui, &self.left_panel_texture, "Left panel", left_tint) {
                     if let Some(state) = self.main_scene.left_panel_state() {
                         *state = !state.clone();
                     }
                 }
-                if ui.add(ImageButton::new(self.right_panel_texture.lock().unwrap().get_handle(), self.right_panel_texture_size).tint(right_tint)).clicked() {
+                if Self::panel_button(ui, &self.right_panel_texture, "Right panel", right_tint) {
                     if let Some(state) = self.main_scene.right_panel_state() {
                         *state = !state.clone();
                     }
                 }
+                let error_count = self.console_view.get_error_count();
+                let console_text = match error_count {
+                    0 => egui::RichText::new("Console"),
+                    _ => egui::RichText::new(format!("Console ({} errors)", error_count)).color(ui.style().visuals.error_fg_color),
+                };
+                let console_open = self.console_view.get_dock() != ConsoleDock::Hidden;
+                if ui.selectable_label(console_open, console_text).clicked() {
+                    self.console_view.toggle();
+                }
+                if ui.selectable_label(self.shader_editor_view.is_open(), "Shader editor").clicked() {
+                    self.shader_editor_view.toggle();
+                }
             });
         });
+        self.console_view.show(ctx);
+        self.shader_editor_view.show(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
             self.main_scene.render(ui);
         });
+        self.panel_shortcuts(ctx);
+        self.reload_assets();
         ctx.request_repaint();
     }
 
-    fn on_exit(&mut self, _gl: Option<&glow::Context>) {
-        //TODO: tear down the scene.
+    fn on_exit(&mut self, gl: Option<&glow::Context>) {
+        if let Some(gl) = gl {
+            self.scene.lock().destroy(gl);
+        }
     }
 }
@@ -0,0 +1,209 @@
+/* where assets are looked for, in order */
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::util::error::Error;
+
+use super::embedded::get_embedded;
+
+/* Overrides the user asset directory, may hold several paths like PATH */
+pub const ASSET_PATH_VAR: &str = "RUSTY_FRACTAL_ASSETS";
+const APP_DIR_NAME: &str = "rusty_fractal";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AssetRoot {
+    Directory(PathBuf),
+    Embedded,
+}
+
+impl Display for AssetRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Directory(dir) => write!(f, "{}", dir.display()),
+            Self::Embedded => write!(f, "built-in assets"),
+        }
+    }
+}
+
+/* Where an asset was found */
+pub enum AssetSource {
+    File(PathBuf),
+    Embedded(&'static [u8]),
+}
+
+/*
+ * @brief
+ * The ordered asset roots, the first one holding a file wins:
+ * the project directory next to the scene file, the directories in RUSTY_FRACTAL_ASSETS,
+ * ~/.rusty_fractal, the XDG data dirs and finally the assets built into the binary.
+ */
+pub struct AssetPaths {
+    project_dir: Option<PathBuf>,
+    roots: Vec<AssetRoot>,
+}
+
+impl AssetPaths {
+    /* The roots taken from the environment, without a project directory */
+    pub fn from_environment() -> Self {
+        let mut roots = Vec::new();
+        if let Some(paths) = std::env::var_os(ASSET_PATH_VAR) {
+            roots.extend(std::env::split_paths(&paths).filter(|path| !path.as_os_str().is_empty()).map(AssetRoot::Directory));
+        }
+        if let Some(dir) = user_asset_dir() {
+            roots.push(AssetRoot::Directory(dir));
+        }
+        if let Some(dir) = dirs::data_dir() {
+            roots.push(AssetRoot::Directory(dir.join(APP_DIR_NAME)));
+        }
+        let data_dirs = std::env::var_os("XDG_DATA_DIRS").unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+        roots.extend(std::env::split_paths(&data_dirs)
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| AssetRoot::Directory(path.join(APP_DIR_NAME))));
+        roots.push(AssetRoot::Embedded);
+        Self::new(roots)
+    }
+
+    pub fn new(roots: Vec<AssetRoot>) -> Self {
+        let mut result = Self { project_dir: None, roots: Vec::new() };
+        for root in roots {
+            // The same directory can show up twice, e.g. when XDG_DATA_HOME is also in the variable
+            if !result.roots.contains(&root) {
+                result.roots.push(root);
+            }
+        }
+        result
+    }
+
+    /* Assets of the project are kept in an assets directory next to the scene file */
+    pub fn set_scene_file(&mut self, scene_file: Option<&Path>) {
+        self.project_dir = scene_file.map(|file| match file.is_dir() {
+            true => file.to_path_buf(),
+            false => file.parent().unwrap_or(Path::new(".")).join("assets"),
+        });
+    }
+
+    pub fn get_roots(&self) -> Vec<AssetRoot> {
+        let mut result: Vec<AssetRoot> = self.project_dir.iter().cloned().map(AssetRoot::Directory).collect();
+        result.extend(self.roots.iter().cloned());
+        result
+    }
+
+    /* relative uses / as separator, e.g. "shaders/template_vertex.glsl" */
+    pub fn find(&self, relative: &str) -> Result<AssetSource, Error> {
+        let roots = self.get_roots();
+        for root in &roots {
+            match root {
+                AssetRoot::Directory(dir) => {
+                    let path = join_relative(dir, relative);
+                    if path.is_file() {
+                        return Ok(AssetSource::File(path));
+                    }
+                }
+                AssetRoot::Embedded => {
+                    if let Some(data) = get_embedded(relative) {
+                        return Ok(AssetSource::Embedded(data));
+                    }
+                }
+            }
+        }
+        let searched = roots.iter().map(|root| match root {
+            AssetRoot::Directory(dir) => join_relative(dir, relative).display().to_string(),
+            AssetRoot::Embedded => root.to_string(),
+        }).collect();
+        Err(Error::asset_not_found(relative, searched))
+    }
+}
+
+/* Where files written by the app go, ~/.rusty_fractal */
+pub fn user_asset_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|dir| dir.join(".rusty_fractal"))
+}
+
+fn join_relative(dir: &Path, relative: &str) -> PathBuf {
+    let mut result = dir.to_path_buf();
+    result.extend(relative.split('/').filter(|part| !part.is_empty()));
+    result
+}
+
+fn global_paths() -> &'static Mutex<AssetPaths> {
+    static PATHS: OnceLock<Mutex<AssetPaths>> = OnceLock::new();
+    PATHS.get_or_init(|| Mutex::new(AssetPaths::from_environment()))
+}
+
+fn with_global_paths<T>(f: impl FnOnce(&mut AssetPaths) -> T) -> T {
+    let mut paths = match global_paths().lock() {
+        Ok(paths) => paths,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    f(&mut paths)
+}
+
+pub fn find_asset(relative: &str) -> Result<AssetSource, Error> {
+    with_global_paths(|paths| paths.find(relative))
+}
+
+pub fn get_asset_roots() -> Vec<AssetRoot> {
+    with_global_paths(|paths| paths.get_roots())
+}
+
+pub fn set_scene_file(scene_file: Option<&Path>) {
+    with_global_paths(|paths| paths.set_scene_file(scene_file));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::temp_dir;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let dir = temp_dir(&format!("asset_paths_{}", name));
+        std::fs::create_dir_all(dir.join("shaders")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn first_root_wins() {
+        let first = temp_root("first");
+        let second = temp_root("second");
+        std::fs::write(first.join("shaders/only_first.glsl"), "first").unwrap();
+        std::fs::write(second.join("shaders/template_vertex.glsl"), "second").unwrap();
+
+        let paths = AssetPaths::new(vec![
+            AssetRoot::Directory(first.clone()),
+            AssetRoot::Directory(second.clone()),
+            AssetRoot::Embedded,
+        ]);
+        match paths.find("shaders/template_vertex.glsl") {
+            Ok(AssetSource::File(path)) => assert_eq!(path, second.join("shaders").join("template_vertex.glsl")),
+            _ => panic!("expected the file of the second root"),
+        }
+        assert!(matches!(paths.find("shaders/only_first.glsl"), Ok(AssetSource::File(_))));
+        assert!(matches!(paths.find("shaders/template_fragment.glsl"), Ok(AssetSource::Embedded(_))));
+
+        std::fs::remove_dir_all(first).unwrap();
+        std::fs::remove_dir_all(second).unwrap();
+    }
+
+    #[test]
+    fn missing_reports_every_root() {
+        let mut paths = AssetPaths::new(vec![
+            AssetRoot::Directory(PathBuf::from("/nowhere/a")),
+            AssetRoot::Directory(PathBuf::from("/nowhere/a")),
+            AssetRoot::Embedded,
+        ]);
+        paths.set_scene_file(Some(Path::new("/nowhere/project/scene.txt")));
+        let roots = paths.get_roots();
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots[0], AssetRoot::Directory(PathBuf::from("/nowhere/project/assets")));
+
+        let message = match paths.find("meshes/missing.ply") {
+            Err(err) => err.to_string(),
+            Ok(_) => panic!("the mesh doesn't exist"),
+        };
+        assert!(message.contains(&join_relative(Path::new("/nowhere/project/assets"), "meshes/missing.ply").display().to_string()));
+        assert!(message.contains(&join_relative(Path::new("/nowhere/a"), "meshes/missing.ply").display().to_string()));
+        assert!(message.contains("built-in assets"));
+    }
+}
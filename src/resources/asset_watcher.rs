@@ -0,0 +1,128 @@
+/* notices changed asset files by polling their modification times */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::asset_paths::{find_asset, AssetPaths, AssetSource};
+
+/* What a watched asset resolved to last time, None for built-in or missing assets */
+type FileState = Option<(PathBuf, Option<SystemTime>)>;
+
+/*
+ * @brief
+ * Polls the files behind a set of assets. The asset is resolved through the search roots again
+ * every time, so a file appearing in front of the built-in copy counts as a change too.
+ */
+pub struct AssetWatcher {
+    files: HashMap<String, FileState>,
+    interval: Duration,
+    last_poll: Option<Instant>,
+    /* Resolved through instead of the global asset roots when set */
+    paths: Option<AssetPaths>,
+}
+
+impl AssetWatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            files: HashMap::new(),
+            interval,
+            last_poll: None,
+            paths: None,
+        }
+    }
+
+    /* Watches the assets in paths rather than the global asset roots */
+    pub fn with_paths(interval: Duration, paths: AssetPaths) -> Self {
+        Self {
+            paths: Some(paths),
+            ..Self::new(interval)
+        }
+    }
+
+    /* relative is the path in the asset roots, e.g. "meshes/gargoyle.ply" */
+    pub fn watch(&mut self, relative: &str) {
+        if !self.files.contains_key(relative) {
+            let state = self.get_state(relative);
+            self.files.insert(String::from(relative), state);
+        }
+    }
+
+    /* Whether the next poll will look at the files */
+    pub fn is_due(&self) -> bool {
+        self.last_poll.is_none_or(|last_poll| last_poll.elapsed() >= self.interval)
+    }
+
+    /* The assets that changed since the last poll, checked at most once per interval */
+    pub fn poll(&mut self) -> Vec<String> {
+        if !self.is_due() {
+            return Vec::new();
+        }
+        self.last_poll = Some(Instant::now());
+        self.poll_now()
+    }
+
+    pub fn poll_now(&mut self) -> Vec<String> {
+        let mut result = Vec::new();
+        let new_states: Vec<(String, FileState)> = self.files
+            .keys()
+            .map(|relative| (relative.clone(), self.get_state(relative)))
+            .collect();
+        for (relative, new_state) in new_states {
+            if self.files.insert(relative.clone(), new_state.clone()) != Some(new_state) {
+                result.push(relative);
+            }
+        }
+        result.sort();
+        result
+    }
+
+    fn get_state(&self, relative: &str) -> FileState {
+        let source = match &self.paths {
+            Some(paths) => paths.find(relative),
+            None => find_asset(relative),
+        };
+        match source {
+            Ok(AssetSource::File(path)) => {
+                let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+                Some((path, modified))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::resources::asset_paths::AssetRoot;
+    use crate::util::test_util::temp_dir;
+
+    #[test]
+    fn detects_changes() {
+        let project = temp_dir("asset_watcher");
+        std::fs::create_dir_all(project.join("shaders")).unwrap();
+        let mut paths = AssetPaths::new(vec![AssetRoot::Embedded]);
+        paths.set_scene_file(Some(&project));
+
+        let mut watcher = AssetWatcher::with_paths(Duration::from_secs(60), paths);
+        watcher.watch("shaders/watched.glsl");
+        watcher.watch("shaders/template_vertex.glsl");
+        assert!(watcher.poll().is_empty());
+
+        let path = project.join("shaders").join("watched.glsl");
+        std::fs::write(&path, "void main() {}").unwrap();
+        // Too soon for another poll
+        assert!(watcher.poll().is_empty());
+        assert_eq!(watcher.poll_now(), vec![String::from("shaders/watched.glsl")]);
+        assert!(watcher.poll_now().is_empty());
+
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        assert_eq!(watcher.poll_now(), vec![String::from("shaders/watched.glsl")]);
+
+        std::fs::remove_dir_all(project).unwrap();
+    }
+}
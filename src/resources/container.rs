@@ -10,13 +10,16 @@ use crate::resources::mesh::Mesh;
 use crate::resources::material::Material;
 use crate::resources::object::Object;
 
+use crate::structures::bounds::{Aabb, BoundingSphere};
 use crate::structures::camera::Camera;
 use crate::util::error::{Error, ErrorKind};
 use crate::util::ref_dict::*;
+use crate::util::log;
 
 use super::mesh::mesh_data::cube_with_normals;
-use super::parser::parse_mesh;
-use super::resource_file::{load_system_texture, mesh_dir};
+use super::mesh_loader::LoadedMesh;
+use super::polygon_mesh::PolygonSource;
+use super::resource_file::{load_system_image, load_system_texture};
 use super::texture::Texture;
 
 pub type ContainerRef<T> = Arc<Mutex<T>>;
@@ -28,6 +31,8 @@ pub struct Container {
     materials: HashMap<String, ContainerRef<Material>>,
     objects: HashMap<String, ContainerRef<Object>>,
     textures: HashMap<String, ContainerRef<Texture>>,
+    /* Asset path of the file each mesh was loaded from, by mesh name */
+    mesh_files: HashMap<String, String>,
 }
 
 impl Container {
@@ -37,29 +42,29 @@ impl Container {
             materials: HashMap::new(),
             objects: HashMap::new(),
             textures: HashMap::new(),
+            mesh_files: HashMap::new(),
         }
     }
 
     pub fn default(gl: &glow::Context) -> Result<Self, Error> {
         let mut result = Self::new();
-        result.add_material(Material::create_solid_color(Vec3::new(0.5, 0.5, 1.0), "default_material")).unwrap();
-        let gargoyle_mesh = parse_mesh(mesh_dir("gargoyle.ply").unwrap(), gl, "gargoyle_mesh")?;
-        let monkey_mesh = parse_mesh(mesh_dir("monkey.ply").unwrap(), gl, "monkey_mesh")?;
+        result.add_material(Material::create_solid_color(Vec3::new(0.5, 0.5, 1.0), "default_material"))?;
         let cube_mesh = Mesh::create_with_shader(cube_with_normals(), gl, super::mesh::VertexShader::default_simple_with_normal(), "cube_mesh")?;
-        result.add_mesh(gargoyle_mesh)?;
         result.add_mesh(cube_mesh)?;
-        result.add_mesh(monkey_mesh)?;
-        result.create_object("gargoyle", "default_material", "gargoyle_mesh", gl).unwrap();
-        result.create_object("cube", "default_material", "cube_mesh", gl).unwrap();
-        result.create_object("monkey", "default_material", "monkey_mesh", gl).unwrap();
-        return Ok(result);
+        result.create_object("cube", "default_material", "cube_mesh", gl)?;
+        // Meshes from files are loaded in the background, see Scene::load_object
+        Ok(result)
     }
 
-    pub fn system_default(gl: &egui::Context) -> Result<Self, Error> {
+    /* Textures that fail to load are logged and left out, the ui falls back to text */
+    pub fn system_default(gl: &egui::Context) -> Self {
         let mut result = Self::new();
-        result.load_system_texture("right_rectangle.png", gl)?;
-        result.load_system_texture("left_rectangle.png", gl)?;
-        Ok(result)
+        for name in ["right_rectangle.png", "left_rectangle.png"] {
+            if let Err(err) = result.load_system_texture(name, gl) {
+                log::error(&format!("Could not load the system texture {}", name), &err);
+            }
+        }
+        result
     }
 
     pub fn create_object(
@@ -94,6 +99,35 @@ impl Container {
         Ok(())
     }
 
+    pub fn add_object(&mut self, object: ContainerRef<Object>) -> Result<(), Error> {
+        let name = String::from(object.lock().unwrap().get_name());
+        if self.objects.contains_key(&name) {
+            return Err(Error::object_already_exists(&name));
+        }
+        self.objects.insert(name, object);
+        Ok(())
+    }
+
+    pub fn rename_object(&mut self, old_name: &str, new_name: &str) -> Result<(), Error> {
+        if self.objects.contains_key(new_name) {
+            return Err(Error::object_already_exists(new_name));
+        }
+        let object = match self.objects.remove(old_name) {
+            Some(object) => object,
+            None => return Err(Error::object_does_not_exist(old_name)),
+        };
+        object.lock().unwrap().set_name(new_name);
+        self.objects.insert(String::from(new_name), object);
+        Ok(())
+    }
+
+    pub fn remove_object(&mut self, name: &str) -> Result<ContainerRef<Object>, Error> {
+        match self.objects.remove(name) {
+            Some(object) => Ok(object),
+            None => Err(Error::object_does_not_exist(name)),
+        }
+    }
+
     pub fn add_mesh(&mut self, mesh: Mesh) -> Result<(), Error> {
         if self.meshes.contains_key(&String::from(mesh.get_name())) {
             return Err(Error::mesh_already_exists(mesh.get_name()));
@@ -103,6 +137,71 @@ impl Container {
         }
     }
 
+    /* Adds a mesh loaded from file_name in the meshes asset directory, it is reloaded when the file changes */
+    pub fn add_mesh_from_file(&mut self, mesh: Mesh, file_name: &str) -> Result<(), Error> {
+        let mesh_name = String::from(mesh.get_name());
+        self.add_mesh(mesh)?;
+        self.mesh_files.insert(mesh_name, format!("meshes/{}", file_name));
+        Ok(())
+    }
+
+    /* Files behind the meshes and textures, as paths in the asset roots */
+    pub fn get_asset_files(&self) -> Vec<String> {
+        let mut result: Vec<String> = self.mesh_files.values().cloned().collect();
+        result.extend(self.textures.keys().map(|name| format!("system_images/{}", name)));
+        result
+    }
+
+    pub fn has_mesh_file(&self, asset_file: &str) -> bool {
+        self.mesh_files.values().any(|file| file == asset_file)
+    }
+
+    /*
+     * Swaps in the mesh loaded again from asset_file, returning it so the objects using it can be rebuilt.
+     * Nothing changes if uploading fails, None if no mesh came from the file.
+     */
+    pub fn replace_mesh_file(&mut self, asset_file: &str, loaded: LoadedMesh, gl: &glow::Context) -> Result<Option<ContainerRef<Mesh>>, Error> {
+        let mesh_name = match self.mesh_files.iter().find(|(_, file)| file.as_str() == asset_file) {
+            Some((mesh_name, _)) => mesh_name.clone(),
+            None => return Ok(None),
+        };
+        let new_mesh = Mesh::from_data_with_lods(loaded.data, loaded.lods, gl, &mesh_name)?;
+        let mesh = match self.meshes.get(&mesh_name) {
+            Some(mesh) => mesh.clone(),
+            None => return Err(Error::mesh_does_not_exist(&mesh_name)),
+        };
+        let old_mesh = std::mem::replace(&mut *mesh.lock().unwrap(), new_mesh);
+        old_mesh.destroy(gl);
+        Ok(Some(mesh))
+    }
+
+    /*
+     * The faces to subdivide mesh_name from. Meshes from files are read again so their quads
+     * stay whole for Catmull-Clark, others are subdivided from their triangles.
+     */
+    pub fn get_polygon_source(&self, mesh_name: &str) -> Result<PolygonSource, Error> {
+        match (self.mesh_files.get(mesh_name), self.meshes.get(mesh_name)) {
+            (Some(asset_file), _) => Ok(PolygonSource::File(String::from(asset_file.trim_start_matches("meshes/")))),
+            (None, Some(mesh)) => Ok(PolygonSource::Mesh(Box::new(mesh.lock().unwrap().to_polygon_mesh()))),
+            (None, None) => Err(Error::mesh_does_not_exist(mesh_name)),
+        }
+    }
+
+    /* Loads the image behind a system texture again, false if asset_file isn't one */
+    pub fn reload_texture_file(&self, asset_file: &str) -> Result<bool, Error> {
+        let name = match asset_file.strip_prefix("system_images/") {
+            Some(name) => name,
+            None => return Ok(false),
+        };
+        let texture = match self.textures.get(name) {
+            Some(texture) => texture,
+            None => return Ok(false),
+        };
+        let image = load_system_image(name)?;
+        texture.lock().unwrap().set_image(image);
+        Ok(true)
+    }
+
     pub fn add_material(&mut self, material: Material) -> Result<(), Error> {
         if self.materials.contains_key(&String::from(material.get_name())) {
             return Err(Error::material_already_exists(material.get_name()));
@@ -141,6 +240,11 @@ impl Container {
         }
         return None;
     }
+    /* Bounds of a mesh in its own space */
+    pub fn get_mesh_bounds(&self, name: &str) -> Option<(Aabb, BoundingSphere)> {
+        let mesh = self.meshes.get(name)?.lock().unwrap();
+        Some((*mesh.get_bounds(), *mesh.get_bounding_sphere()))
+    }
     pub fn get_object(&self, name: &str) -> Option<ContainerRef<Object>> {
         if let Some(object) = self.objects.get(name) {
             return Some(object.clone());
@@ -154,6 +258,14 @@ impl Container {
         return None;
     }
 
+    pub fn get_meshes(&self) -> hash_map::Values<'_, String, ContainerRef<Mesh>> {
+        self.meshes.values()
+    }
+
+    pub fn get_objects(&self) -> hash_map::Values<'_, String, ContainerRef<Object>> {
+        self.objects.values()
+    }
+
     pub fn get_object_names(&self) -> hash_map::Keys<String, ContainerRef<Object>> {
         self.objects.keys()
     }
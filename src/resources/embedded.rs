@@ -0,0 +1,33 @@
+/* assets compiled into the binary so the app runs without an asset directory */
+
+macro_rules! embed {
+    ($path:literal) => {
+        ($path, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/", $path)) as &[u8])
+    };
+}
+
+/* Paths are relative to the asset directory, files there take precedence over these */
+const EMBEDDED: [(&str, &[u8]); 5] = [
+    embed!("shaders/template_vertex.glsl"),
+    embed!("shaders/template_fragment.glsl"),
+    embed!("system_images/left_rectangle.png"),
+    embed!("system_images/right_rectangle.png"),
+    embed!("meshes/icosphere.ply"),
+];
+
+/* path uses / as separator, e.g. "shaders/template_vertex.glsl" */
+pub fn get_embedded(path: &str) -> Option<&'static [u8]> {
+    EMBEDDED.iter().find(|(name, _)| *name == path).map(|(_, data)| *data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        assert!(get_embedded("shaders/template_fragment.glsl").is_some());
+        assert!(get_embedded("meshes/icosphere.ply").is_some_and(|data| data.starts_with(b"ply\n")));
+        assert!(get_embedded("meshes/missing.ply").is_none());
+    }
+}
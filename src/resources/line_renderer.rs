@@ -0,0 +1,101 @@
+/* draws flat colored lines over the scene */
+
+use glam::{Mat4, Vec3};
+use glow::*;
+
+use crate::util::error::Error;
+
+use super::program;
+use super::shader_gen;
+
+/*
+ * @brief
+ * A small program and a buffer that is refilled for every draw.
+ * Only meant for a handful of lines like boxes around objects.
+ */
+pub struct LineRenderer {
+    program: NativeProgram,
+    vertex_shader: NativeShader,
+    fragment_shader: NativeShader,
+    vao: NativeVertexArray,
+    vbo: NativeBuffer,
+}
+
+impl LineRenderer {
+    pub fn create(gl: &glow::Context) -> Result<Self, Error> {
+        let (program, vertex_shader, fragment_shader) = program::link_program(
+            &shader_gen::gen_line_vertex_shader(),
+            &shader_gen::gen_line_fragment_shader(),
+            gl)?;
+        unsafe {
+            let vao = match gl.create_vertex_array() {
+                Ok(vao) => vao,
+                Err(err) => {
+                    program::delete_program(gl, Some(program), Some(vertex_shader), Some(fragment_shader));
+                    return Err(Error::invalid_buffer(err));
+                }
+            };
+            let vbo = match gl.create_buffer() {
+                Ok(vbo) => vbo,
+                Err(err) => {
+                    gl.delete_vertex_array(vao);
+                    program::delete_program(gl, Some(program), Some(vertex_shader), Some(fragment_shader));
+                    return Err(Error::invalid_buffer(err));
+                }
+            };
+            Ok(Self { program, vertex_shader, fragment_shader, vao, vbo })
+        }
+    }
+
+    /* lines holds the two ends of every line, in the space camera_matrix transforms from */
+    pub fn render(&self, gl: &glow::Context, camera_matrix: &Mat4, lines: &[Vec3], color: [f32; 4]) {
+        if lines.is_empty() {
+            return;
+        }
+        let data: Vec<f32> = lines.iter().flat_map(|point| point.to_array()).collect();
+        unsafe {
+            gl.use_program(Some(self.program));
+            let location = gl.get_uniform_location(self.program, "camera_matrix");
+            gl.uniform_matrix_4_f32_slice(location.as_ref(), false, &camera_matrix.to_cols_array());
+            let location = gl.get_uniform_location(self.program, "line_color");
+            gl.uniform_4_f32_slice(location.as_ref(), &color);
+
+            gl.bind_vertex_array(Some(self.vao));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+            let bytes = std::slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * std::mem::size_of::<f32>());
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, bytes, glow::STREAM_DRAW);
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 3 * std::mem::size_of::<f32>() as i32, 0);
+            gl.draw_arrays(glow::LINES, 0, lines.len() as i32);
+            gl.disable_vertex_attrib_array(0);
+            gl.bind_vertex_array(None);
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_buffer(self.vbo);
+            gl.delete_vertex_array(self.vao);
+        }
+        program::delete_program(gl, Some(self.program), Some(self.vertex_shader), Some(self.fragment_shader));
+    }
+}
+
+/* The 12 edges of the box between min and max, as pairs of points for LineRenderer::render */
+pub fn box_lines(min: Vec3, max: Vec3) -> Vec<Vec3> {
+    let corner = |i: usize| Vec3::new(
+        if i & 1 == 0 { min.x } else { max.x },
+        if i & 2 == 0 { min.y } else { max.y },
+        if i & 4 == 0 { min.z } else { max.z });
+    let mut result = Vec::with_capacity(24);
+    for i in 0..8 {
+        // Each edge once, from the corner with the bit cleared
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                result.push(corner(i));
+                result.push(corner(i | bit));
+            }
+        }
+    }
+    result
+}
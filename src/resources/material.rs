@@ -1,10 +1,13 @@
 use glam::Vec3;
+use glow::HasContext;
 
 use super::shader_attribute::ShaderAttribute;
 
 pub struct Material {
     material_type: MaterialType,
     name: String,
+    /* Bumped whenever a change needs the shaders using this material to be regenerated */
+    revision: u64,
 }
 
 impl Material {
@@ -12,6 +15,7 @@ impl Material {
         Self {
             material_type,
             name: String::from(name),
+            revision: 0,
         }
     }
     pub fn create_solid_color(color: Vec3, name: &str) -> Self {
@@ -20,28 +24,148 @@ impl Material {
     pub fn get_material_type(&self) -> &MaterialType {
         &self.material_type
     }
+    pub fn get_mut_material_type(&mut self) -> &mut MaterialType {
+        &mut self.material_type
+    }
     pub fn get_name(&self) -> &str { &self.name }
+
+    pub fn get_revision(&self) -> u64 {
+        self.revision
+    }
+    /* Call after changing something that is compiled into the shader rather than passed as a uniform */
+    pub fn invalidate_shader(&mut self) {
+        self.revision += 1;
+    }
+
+    pub fn get_parameters(&self) -> MaterialParameters {
+        match &self.material_type {
+            MaterialType::SolidColor(solid_material) => MaterialParameters::SolidColor(solid_material.get_parameters()),
+        }
+    }
+    /* Sets every parameter at once, regenerating the shader if needed */
+    pub fn set_parameters(&mut self, parameters: MaterialParameters) {
+        let structure_changed = match (&mut self.material_type, parameters) {
+            (MaterialType::SolidColor(solid_material), MaterialParameters::SolidColor(parameters)) => {
+                let shaded = solid_material.is_shaded();
+                solid_material.set_parameters(parameters);
+                shaded != parameters.shaded
+            }
+        };
+        if structure_changed {
+            self.invalidate_shader();
+        }
+    }
+
+    pub fn apply_uniforms(&self, gl: &glow::Context, program: &glow::NativeProgram) {
+        match &self.material_type {
+            MaterialType::SolidColor(solid_material) => solid_material.apply_uniforms(gl, program),
+        }
+    }
 }
 
 pub enum MaterialType {
     SolidColor(SolidColorMaterial),
 }
 
+/* Copy of the editable values of a material, used to undo edits */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MaterialParameters {
+    SolidColor(SolidColorParameters),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SolidColorParameters {
+    pub color: Vec3,
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub shaded: bool,
+}
+
 pub struct SolidColorMaterial {
     color: Vec3,
+    ambient: f32,
+    diffuse: f32,
+    shaded: bool,
     color_out: ShaderAttribute,
+    color_uniform: ShaderAttribute,
+    ambient_uniform: ShaderAttribute,
+    diffuse_uniform: ShaderAttribute,
 }
 impl SolidColorMaterial {
     pub fn create(color: Vec3) -> Self {
         Self {
             color,
+            ambient: 0.5,
+            diffuse: 0.4,
+            shaded: true,
             color_out: ShaderAttribute::output_float4(String::from("color_out")),
+            color_uniform: ShaderAttribute::uniform_float3(String::from("material_color")),
+            ambient_uniform: ShaderAttribute::uniform_float(String::from("material_ambient")),
+            diffuse_uniform: ShaderAttribute::uniform_float(String::from("material_diffuse")),
         }
     }
     pub fn get_color_out(&self) -> &ShaderAttribute {
         &self.color_out
     }
+    pub fn get_color_uniform(&self) -> &ShaderAttribute {
+        &self.color_uniform
+    }
+    pub fn get_ambient_uniform(&self) -> &ShaderAttribute {
+        &self.ambient_uniform
+    }
+    pub fn get_diffuse_uniform(&self) -> &ShaderAttribute {
+        &self.diffuse_uniform
+    }
+
     pub fn get_color(&self) -> Vec3 {
         self.color
     }
+    pub fn set_color(&mut self, color: Vec3) {
+        self.color = color;
+    }
+    pub fn get_ambient(&self) -> f32 {
+        self.ambient
+    }
+    pub fn set_ambient(&mut self, ambient: f32) {
+        self.ambient = ambient;
+    }
+    pub fn get_diffuse(&self) -> f32 {
+        self.diffuse
+    }
+    pub fn set_diffuse(&mut self, diffuse: f32) {
+        self.diffuse = diffuse;
+    }
+    /* Whether lighting is compiled into the shader. Needs Material::invalidate_shader after changing. */
+    pub fn is_shaded(&self) -> bool {
+        self.shaded
+    }
+    pub fn set_shaded(&mut self, shaded: bool) {
+        self.shaded = shaded;
+    }
+
+    pub fn get_parameters(&self) -> SolidColorParameters {
+        SolidColorParameters {
+            color: self.color,
+            ambient: self.ambient,
+            diffuse: self.diffuse,
+            shaded: self.shaded,
+        }
+    }
+    pub fn set_parameters(&mut self, parameters: SolidColorParameters) {
+        self.color = parameters.color;
+        self.ambient = parameters.ambient;
+        self.diffuse = parameters.diffuse;
+        self.shaded = parameters.shaded;
+    }
+
+    pub fn apply_uniforms(&self, gl: &glow::Context, program: &glow::NativeProgram) {
+        unsafe {
+            let location = gl.get_uniform_location(*program, self.color_uniform.name());
+            gl.uniform_3_f32_slice(location.as_ref(), &self.color.to_array());
+            let location = gl.get_uniform_location(*program, self.ambient_uniform.name());
+            gl.uniform_1_f32(location.as_ref(), self.ambient);
+            let location = gl.get_uniform_location(*program, self.diffuse_uniform.name());
+            gl.uniform_1_f32(location.as_ref(), self.diffuse);
+        }
+    }
 }
@@ -1,8 +1,60 @@
 use glow::*;
-use crate::{structures::camera::Camera, util::error::Error};
+use glam::{DVec3, Vec3};
 
+use std::sync::OnceLock;
+
+use crate::{structures::{bounds::{Aabb, BoundingSphere}, bvh::Bvh, ray::Ray}, util::error::Error};
+
+use super::mesh_simplify::select_lod;
+use super::polygon_mesh::PolygonMesh;
 use super::shader_attribute::{ShaderAttribute, ShaderAttributePair};
 
+/* How the attributes of the vertexes are arranged in the vertex buffer once uploaded */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VertexLayout {
+    /* All attributes of a vertex next to each other */
+    Interleaved,
+    /* The attribute of every vertex, then the next attribute of every vertex */
+    Separate,
+}
+
+/* Type of the index buffer, the smallest one that can address every vertex is used */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexType {
+    U8,
+    U16,
+    U32,
+}
+
+impl IndexType {
+    /*
+     * U8 is only picked with allow_u8. Many drivers have no native u8 indexes
+     * and convert them on every draw, so small meshes get U16 by default.
+     */
+    pub fn smallest_for(vertex_count: usize, allow_u8: bool) -> Self {
+        match vertex_count {
+            count if allow_u8 && count <= u8::MAX as usize + 1 => Self::U8,
+            count if count <= u16::MAX as usize + 1 => Self::U16,
+            _ => Self::U32,
+        }
+    }
+    pub fn get_data_type(&self) -> u32 {
+        match self {
+            Self::U8 => glow::UNSIGNED_BYTE,
+            Self::U16 => glow::UNSIGNED_SHORT,
+            Self::U32 => glow::UNSIGNED_INT,
+        }
+    }
+    /* indexes as stored in the buffer, they have to fit the type */
+    pub fn pack(&self, indexes: &[u32]) -> Vec<u8> {
+        match self {
+            Self::U8 => indexes.iter().map(|index| *index as u8).collect(),
+            Self::U16 => indexes.iter().flat_map(|index| (*index as u16).to_ne_bytes()).collect(),
+            Self::U32 => indexes.iter().flat_map(|index| index.to_ne_bytes()).collect(),
+        }
+    }
+}
+
 pub enum VertexShader {
     Simple(SimpleVertexShader),
 }
@@ -16,6 +68,14 @@ impl VertexShader {
         ];
         Self::Simple(SimpleVertexShader::from_vertex_map(properties))
     }
+    /* float3 position, float3 normal and float2 texture coordinate, 8 floats per vertex */
+    pub fn default_simple_with_normal_uv() -> Self {
+        let properties = vec![
+            ShaderAttributePair::float3(1, String::from("normal")),
+            ShaderAttributePair::float2(2, String::from("uv")),
+        ];
+        Self::Simple(SimpleVertexShader::from_vertex_map(properties))
+    }
     pub fn simple(properties: Vec<ShaderAttributePair>) -> Self {
         Self::Simple(SimpleVertexShader::from_vertex_map(properties))
     }
@@ -24,9 +84,32 @@ impl VertexShader {
             Self::Simple(simple_shader) => return &simple_shader.vertex_out,
         }
     }
-    pub fn apply_attributes(&self, gl: &glow::Context) {
+    /* Points the attributes at a buffer made by pack_vertexes from vertex_count vertexes */
+    pub fn apply_attributes(&self, gl: &glow::Context, vertex_count: usize) -> Result<(), Error> {
+        match self {
+            Self::Simple(simple_shader) => simple_shader.apply_attributes(gl, vertex_count),
+        }
+    }
+    /* The vertexes converted to the formats and layout of the attributes, ready to upload */
+    pub fn pack_vertexes(&self, vertexes: &[f32]) -> Vec<u8> {
+        match self {
+            Self::Simple(simple_shader) => simple_shader.pack_vertexes(vertexes),
+        }
+    }
+    pub fn get_layout(&self) -> VertexLayout {
         match self {
-            Self::Simple(simple_shader) => simple_shader.apply_attributes(gl),
+            Self::Simple(simple_shader) => simple_shader.get_layout(),
+        }
+    }
+    pub fn set_layout(&mut self, layout: VertexLayout) {
+        match self {
+            Self::Simple(simple_shader) => simple_shader.set_layout(layout),
+        }
+    }
+    /* Number of floats used by a single vertex */
+    pub fn get_vertex_size(&self) -> usize {
+        match self {
+            Self::Simple(simple_shader) => simple_shader.get_vertex_size(),
         }
     }
     pub fn get_normal(&self) -> Option<&ShaderAttributePair> {
@@ -36,6 +119,18 @@ impl VertexShader {
             }
         }
     }
+    /* Where a property starts within a vertex, in floats */
+    pub fn get_property_offset(&self, name: &str) -> Option<usize> {
+        match self {
+            Self::Simple(simple_shader) => simple_shader.get_property_offset(name),
+        }
+    }
+    /* Attribute locations the vertexes fill, these have to be enabled while drawing */
+    pub fn get_attribute_locations(&self) -> Vec<u32> {
+        match self {
+            Self::Simple(simple_shader) => simple_shader.get_attributes().filter_map(ShaderAttribute::index).collect(),
+        }
+    }
 }
 
 pub struct SimpleVertexShader {
@@ -43,6 +138,7 @@ pub struct SimpleVertexShader {
     vertex_out: ShaderAttribute,
     vertex_properties: Vec<ShaderAttributePair>,
     camera_matrix: ShaderAttribute,
+    layout: VertexLayout,
 }
 impl SimpleVertexShader {
     pub fn new(vertex_in: ShaderAttribute, vertex_out: ShaderAttribute, properties: Vec<ShaderAttributePair>) -> Self {
@@ -51,6 +147,7 @@ impl SimpleVertexShader {
             vertex_out,
             vertex_properties: properties,
             camera_matrix: ShaderAttribute::uniform_mat4(String::from("camera_matrix")),
+            layout: VertexLayout::Interleaved,
         }
     }
     pub fn default() -> Self {
@@ -59,6 +156,7 @@ impl SimpleVertexShader {
             vertex_out: ShaderAttribute::output_float4(String::from("gl_Position")),
             vertex_properties: Vec::new(),
             camera_matrix: ShaderAttribute::uniform_mat4(String::from("camera_matrix")),
+            layout: VertexLayout::Interleaved,
         }
     }
 
@@ -68,6 +166,7 @@ impl SimpleVertexShader {
             vertex_out: ShaderAttribute::output_float4(String::from("gl_Position")),
             vertex_properties: properties,
             camera_matrix: ShaderAttribute::uniform_mat4(String::from("camera_matrix")),
+            layout: VertexLayout::Interleaved,
         }
     }
     pub fn get_vertex_in(&self) -> &ShaderAttribute {
@@ -90,21 +189,88 @@ impl SimpleVertexShader {
         }
         None
     }
+    pub fn get_property_offset(&self, name: &str) -> Option<usize> {
+        let mut offset = self.get_vertex_in().size() as usize;
+        for property in &self.vertex_properties {
+            if property.get_name() == name {
+                return Some(offset);
+            }
+            offset += property.get_attribute_in().size() as usize;
+        }
+        None
+    }
+    pub fn get_vertex_size(&self) -> usize {
+        let mut size = self.get_vertex_in().size();
+        for property in &self.vertex_properties {
+            size += property.get_attribute_in().size();
+        }
+        size as usize
+    }
     pub fn get_camera_matrix(&self) -> &ShaderAttribute {
         &self.camera_matrix
     }
-    pub fn apply_attributes(&self, gl: &glow::Context) {
-        let mut stride = self.get_vertex_in().get_stride();
-        for property in &self.vertex_properties {
-            stride += property.get_attribute_in().get_stride();
+    pub fn get_layout(&self) -> VertexLayout {
+        self.layout
+    }
+    pub fn set_layout(&mut self, layout: VertexLayout) {
+        self.layout = layout;
+    }
+    fn get_attributes(&self) -> impl Iterator<Item = &ShaderAttribute> {
+        std::iter::once(self.get_vertex_in())
+            .chain(self.vertex_properties.iter().map(ShaderAttributePair::get_attribute_in))
+    }
+    /* Bytes of a vertex in the vertex buffer */
+    pub fn get_vertex_stride(&self) -> i32 {
+        self.get_attributes().map(ShaderAttribute::get_stride).sum()
+    }
+    pub fn pack_vertexes(&self, vertexes: &[f32]) -> Vec<u8> {
+        let vertex_size = self.get_vertex_size();
+        let vertex_count = vertexes.len() / vertex_size;
+        let mut result = Vec::with_capacity(vertex_count * self.get_vertex_stride() as usize);
+        let write = |result: &mut Vec<u8>, attribute: &ShaderAttribute, values: &[f32]| {
+            let start = result.len();
+            for value in values {
+                attribute.get_format().write(*value, result);
+            }
+            result.resize(start + attribute.get_stride() as usize, 0);
+        };
+        match self.layout {
+            VertexLayout::Interleaved => {
+                for vertex in vertexes.chunks_exact(vertex_size) {
+                    let mut offset = 0;
+                    for attribute in self.get_attributes() {
+                        write(&mut result, attribute, &vertex[offset..offset + attribute.size() as usize]);
+                        offset += attribute.size() as usize;
+                    }
+                }
+            }
+            VertexLayout::Separate => {
+                let mut offset = 0;
+                for attribute in self.get_attributes() {
+                    for vertex in vertexes.chunks_exact(vertex_size) {
+                        write(&mut result, attribute, &vertex[offset..offset + attribute.size() as usize]);
+                    }
+                    offset += attribute.size() as usize;
+                }
+            }
         }
+        result
+    }
+    pub fn apply_attributes(&self, gl: &glow::Context, vertex_count: usize) -> Result<(), Error> {
         let mut offset = 0;
-        self.get_vertex_in().apply_attrib_with_stride_offset(gl, stride, offset);
-        offset += self.get_vertex_in().get_stride();
-        for property in &self.vertex_properties {
-            property.get_attribute_in().apply_attrib_with_stride_offset(gl, stride, offset);
-            offset += property.get_attribute_in().get_stride();
+        for attribute in self.get_attributes() {
+            match self.layout {
+                VertexLayout::Interleaved => {
+                    attribute.apply_attrib_with_stride_offset(gl, self.get_vertex_stride(), offset)?;
+                    offset += attribute.get_stride();
+                }
+                VertexLayout::Separate => {
+                    attribute.apply_attrib_with_stride_offset(gl, attribute.get_stride(), offset)?;
+                    offset += attribute.get_stride() * vertex_count as i32;
+                }
+            }
         }
+        Ok(())
     }
 }
 
@@ -211,14 +377,32 @@ pub mod mesh_data {
 }
 
 
+/* The vertex data of a mesh before it is uploaded, it can be built on any thread */
+pub struct MeshData {
+    pub vertexes: Vec<f32>,
+    pub indexes: Option<Vec<u32>>,
+    pub shader: VertexShader,
+}
+
 pub struct Mesh {
    vertexes: Vec<f32>,
    vertex_indexes: Option<Vec<u32>>,
-   vbo: NativeBuffer,
-   vao: NativeVertexArray,
+   /* None only for meshes the tests make without gl */
+   vbo: Option<NativeBuffer>,
+   vao: Option<NativeVertexArray>,
    index_buffer: Option<NativeBuffer>,
+   index_type: IndexType,
    shader: VertexShader,
    name: String,
+   /* In the mesh's own space, objects transform them with their world matrix */
+   bounds: Aabb,
+   bounding_sphere: BoundingSphere,
+   /* Over the triangles, built the first time the mesh is hit tested */
+   bvh: OnceLock<Bvh>,
+   /* Simplified versions drawn when the mesh is small on screen, finest first */
+   lods: Vec<Mesh>,
+   /* Of the mesh and then every level, for select_lod. Empty without levels */
+   lod_triangle_counts: Vec<usize>,
 }
 
 impl Mesh {
@@ -234,50 +418,209 @@ impl Mesh {
                 Err(err) => return Err(err),
             };
         }
+        let (bounds, bounding_sphere) = Self::compute_bounds(&vertexes, &shader);
         Ok(Self {
+            bounds,
+            bounding_sphere,
+            bvh: OnceLock::new(),
+            lods: Vec::new(),
+            lod_triangle_counts: Vec::new(),
             vertexes,
             vertex_indexes: None,
-            vbo,
-            vao,
+            vbo: Some(vbo),
+            vao: Some(vao),
             index_buffer: None,
+            index_type: IndexType::U32,
             shader,
             name: String::from(name),
         })
     }
 
+    /* Uploads data, has to be called where gl is current */
+    pub fn from_data(data: MeshData, gl: &glow::Context, name: &str) -> Result<Self, Error> {
+        match data.indexes {
+            Some(indexes) => Self::create_indexed(data.vertexes, indexes, gl, data.shader, name),
+            None => Self::create_with_shader(data.vertexes, gl, data.shader, name),
+        }
+    }
+
+    /* from_data with levels of detail from build_lod_chain, they need the same vertex layout as data */
+    pub fn from_data_with_lods(data: MeshData, lods: Vec<MeshData>, gl: &glow::Context, name: &str) -> Result<Self, Error> {
+        let mut mesh = Self::from_data(data, gl, name)?;
+        for (level, lod) in lods.into_iter().enumerate() {
+            match Self::from_data(lod, gl, &format!("{}_lod{}", name, level + 1)) {
+                Ok(lod) => mesh.lods.push(lod),
+                Err(err) => {
+                    mesh.destroy(gl);
+                    return Err(err);
+                }
+            }
+        }
+        if !mesh.lods.is_empty() {
+            mesh.lod_triangle_counts = std::iter::once(&mesh)
+                .chain(&mesh.lods)
+                .map(Mesh::get_triangle_count)
+                .collect();
+        }
+        Ok(mesh)
+    }
+
     pub fn create_indexed(vertexes: Vec<f32>, indexes: Vec<u32>, gl: &glow::Context, shader: VertexShader, name: &str) -> Result<Self, Error> {
+        Self::create_indexed_allow_u8(vertexes, indexes, gl, shader, name, false)
+    }
+
+    /* create_indexed, but with allow_u8 meshes of up to 256 vertexes get u8 indexes. See IndexType::smallest_for */
+    pub fn create_indexed_allow_u8(vertexes: Vec<f32>, indexes: Vec<u32>, gl: &glow::Context, shader: VertexShader, name: &str, allow_u8: bool) -> Result<Self, Error> {
         let (vao, vbo, vertex_index_buffer);
+        let index_type = IndexType::smallest_for(vertexes.len() / shader.get_vertex_size(), allow_u8);
         unsafe {
             (vao, vbo) = match Mesh::create_vertex_buffer(vertexes.clone(), &shader, gl) {
                 Ok(pair) => pair,
                 Err(err) => return Err(err),
             };
-            vertex_index_buffer = match Mesh::create_index_buffer(indexes.clone(), gl) {
-                Ok(pair) => pair,
-                Err(err) => return Err(err),
-            };
+            vertex_index_buffer = Mesh::create_index_buffer(&indexes, index_type, gl)?;
         }
+        let (bounds, bounding_sphere) = Self::compute_bounds(&vertexes, &shader);
         Ok(Self {
+            bounds,
+            bounding_sphere,
+            bvh: OnceLock::new(),
+            lods: Vec::new(),
+            lod_triangle_counts: Vec::new(),
             vertexes,
             vertex_indexes: Some(indexes),
-            vbo,
-            vao,
+            vbo: Some(vbo),
+            vao: Some(vao),
             index_buffer: Some(vertex_index_buffer),
+            index_type,
             shader,
             name: String::from(name),
         })
     }
 
-    fn get_vertex_array(&self) -> NativeVertexArray {
+    /* A mesh that is never drawn, for tests of what holds meshes */
+    #[cfg(test)]
+    pub fn from_data_without_gl(data: MeshData, name: &str) -> Self {
+        let (bounds, bounding_sphere) = Self::compute_bounds(&data.vertexes, &data.shader);
+        Self {
+            bounds,
+            bounding_sphere,
+            bvh: OnceLock::new(),
+            lods: Vec::new(),
+            lod_triangle_counts: Vec::new(),
+            vertexes: data.vertexes,
+            vertex_indexes: data.indexes,
+            vbo: None,
+            vao: None,
+            index_buffer: None,
+            index_type: IndexType::U32,
+            shader: data.shader,
+            name: String::from(name),
+        }
+    }
+
+    /* Positions and triangles of the mesh, for operations like subdivide */
+    pub fn to_polygon_mesh(&self) -> PolygonMesh {
+        let vertex_count = self.vertexes.len() / self.shader.get_vertex_size();
+        let positions: Vec<Vec3> = (0..vertex_count).map(|index| self.get_position(index).as_vec3()).collect();
+        let faces = (0..self.get_triangle_count())
+            .map(|triangle| self.get_triangle(triangle).iter().map(|corner| *corner as u32).collect())
+            .collect();
+        PolygonMesh::from_positions(&positions, faces)
+    }
+
+    fn compute_bounds(vertexes: &[f32], shader: &VertexShader) -> (Aabb, BoundingSphere) {
+        let positions = vertexes
+            .chunks_exact(shader.get_vertex_size())
+            .map(|vertex| Vec3::from_slice(&vertex[..3]).as_dvec3());
+        (Aabb::from_points(positions.clone()), BoundingSphere::from_points(positions))
+    }
+
+    pub fn get_lod_count(&self) -> usize {
+        self.lods.len()
+    }
+
+    /* The mesh or level of detail to draw when the mesh is screen_size pixels tall */
+    pub fn get_lod(&self, screen_size: f64) -> &Mesh {
+        if self.lods.is_empty() {
+            return self;
+        }
+        match select_lod(&self.lod_triangle_counts, screen_size) {
+            0 => self,
+            level => &self.lods[level - 1],
+        }
+    }
+
+    pub fn get_bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+    pub fn get_bounding_sphere(&self) -> &BoundingSphere {
+        &self.bounding_sphere
+    }
+
+    fn get_vertex_array(&self) -> Option<NativeVertexArray> {
         self.vao
     }
 
-    fn _get_vertex_buffer(&self) -> NativeBuffer {
+    fn _get_vertex_buffer(&self) -> Option<NativeBuffer> {
         self.vbo
     }
 
     fn get_vertex_count(&self) -> i32 {
-        (self.vertexes.len() / 3) as i32
+        (self.vertexes.len() / self.shader.get_vertex_size()) as i32
+    }
+
+    pub fn get_position(&self, index: usize) -> DVec3 {
+        let start = index * self.shader.get_vertex_size();
+        Vec3::from_slice(&self.vertexes[start..start + 3]).as_dvec3()
+    }
+
+    pub fn get_triangle_count(&self) -> usize {
+        match &self.vertex_indexes {
+            Some(indexes) => indexes.len() / 3,
+            None => self.get_vertex_count() as usize / 3,
+        }
+    }
+
+    /* Indexes of the vertexes making up a triangle */
+    pub fn get_triangle(&self, index: usize) -> [usize; 3] {
+        match &self.vertex_indexes {
+            Some(indexes) => [
+                indexes[index * 3] as usize,
+                indexes[index * 3 + 1] as usize,
+                indexes[index * 3 + 2] as usize,
+            ],
+            None => [index * 3, index * 3 + 1, index * 3 + 2],
+        }
+    }
+
+    pub fn get_bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| {
+            let bounds: Vec<Aabb> = (0..self.get_triangle_count())
+                .map(|triangle| Aabb::from_points(self.get_triangle(triangle).map(|index| self.get_position(index))))
+                .collect();
+            Bvh::build(&bounds)
+        })
+    }
+
+    fn intersect_triangle(&self, ray: &Ray, triangle: usize) -> Option<f64> {
+        let [a, b, c] = self.get_triangle(triangle);
+        ray.intersect_triangle(self.get_position(a), self.get_position(b), self.get_position(c))
+    }
+
+    /* (triangle, distance) of the closest triangle hit before max_distance by a ray given in the mesh's space */
+    pub fn closest_hit(&self, ray: &Ray, max_distance: f64) -> Option<(usize, f64)> {
+        self.get_bvh().closest_hit(ray, max_distance, |triangle| self.intersect_triangle(ray, triangle))
+    }
+
+    /* Whether any triangle is hit before max_distance, cheaper than closest_hit for visibility tests */
+    pub fn any_hit(&self, ray: &Ray, max_distance: f64) -> bool {
+        self.get_bvh().any_hit(ray, max_distance, |triangle| self.intersect_triangle(ray, triangle)).is_some()
+    }
+
+    /* Distance to the closest triangle hit by a ray given in the mesh's space */
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f64> {
+        self.closest_hit(ray, f64::INFINITY).map(|(_, distance)| distance)
     }
 
     pub fn get_name(&self) -> &str { &self.name }
@@ -292,44 +635,48 @@ impl Mesh {
         &self.shader
     }
 
-    pub fn render(&self, gl: &glow::Context, camera: &Camera, program: &NativeProgram) {
+    pub fn get_index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    pub fn render(&self, gl: &glow::Context, camera_matrix: &glam::Mat4, program: &NativeProgram) -> Result<(), Error> {
+        let locations = self.shader.get_attribute_locations();
         unsafe {
             let location = gl.get_uniform_location(*program, "camera_matrix");
-            gl.uniform_matrix_4_f32_slice(location.as_ref(), false, &camera.get_transformation_matrix().to_cols_array());
+            gl.uniform_matrix_4_f32_slice(location.as_ref(), false, &camera_matrix.to_cols_array());
+
+            gl.bind_vertex_array(self.get_vertex_array());
+            for location in &locations {
+                gl.enable_vertex_attrib_array(*location);
+            }
+            gl.bind_buffer(glow::ARRAY_BUFFER, self.vbo);
+            let result = self.shader.apply_attributes(gl, self.get_vertex_count() as usize);
+            if result.is_err() {
+                gl.bind_vertex_array(None);
+                return result;
+            }
 
             if let Some(index_buffer) = self.index_buffer {
-                gl.bind_vertex_array(Some(self.get_vertex_array()));
-                gl.enable_vertex_attrib_array(0);
-                gl.enable_vertex_attrib_array(1);
-                gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-                self.shader.apply_attributes(gl);
-                
                 gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(index_buffer));
-
                 gl.draw_elements(
                     glow::TRIANGLES,
-                    self.vertex_indexes.as_ref().unwrap().len() as i32,
-                    glow::UNSIGNED_INT, 0);
-                gl.disable_vertex_attrib_array(0);
-                gl.disable_vertex_attrib_array(1);
+                    self.vertex_indexes.as_ref().map_or(0, |indexes| indexes.len()) as i32,
+                    self.index_type.get_data_type(), 0);
             } else {
-                gl.bind_vertex_array(Some(self.get_vertex_array()));
-                gl.enable_vertex_attrib_array(0);
-                gl.enable_vertex_attrib_array(1);
-                gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-                self.shader.apply_attributes(gl);
                 gl.draw_arrays(glow::TRIANGLES, 0, self.get_vertex_count());
-                gl.disable_vertex_attrib_array(0);
-                gl.disable_vertex_attrib_array(1);
+            }
+            for location in &locations {
+                gl.disable_vertex_attrib_array(*location);
             }
 
             gl.bind_vertex_array(None);
         }
+        Ok(())
     }
 
     unsafe fn create_vertex_buffer(
         vertexes: Vec<f32>,
-        _shader: &VertexShader,
+        shader: &VertexShader,
         gl: &glow::Context
     ) -> Result<(NativeVertexArray, NativeBuffer), Error> {
         let vao = match gl.create_vertex_array() {
@@ -345,14 +692,9 @@ impl Mesh {
 
         gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
 
-        let vertexes_u8: &[u8] = core::slice::from_raw_parts(
-            vertexes.as_ptr() as *const u8,
-            vertexes.len() * core::mem::size_of::<f32>(),
-        );
-
         gl.buffer_data_u8_slice(
             glow::ARRAY_BUFFER,
-            vertexes_u8,
+            &shader.pack_vertexes(&vertexes),
             glow::STATIC_DRAW);
 
         //shader.apply_attributes(gl);
@@ -365,26 +707,108 @@ impl Mesh {
         Ok((vao, vbo))
     }
 
-    unsafe fn create_index_buffer(vertex_indexes: Vec<u32>, gl: &glow::Context) -> Result<NativeBuffer, Error> {
+    unsafe fn create_index_buffer(vertex_indexes: &[u32], index_type: IndexType, gl: &glow::Context) -> Result<NativeBuffer, Error> {
         let vbo = match gl.create_buffer() {
             Ok(buffer) => buffer,
             Err(err) => return Err(Error::invalid_buffer(err)),
         };
         gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(vbo));
 
-        let vertex_indexes_u8: &[u8] = core::slice::from_raw_parts(
-            vertex_indexes.as_ptr() as *const u8,
-            vertex_indexes.len() * core::mem::size_of::<u32>());
-
-        gl.buffer_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, vertex_indexes_u8, glow::STATIC_DRAW);
+        gl.buffer_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, &index_type.pack(vertex_indexes), glow::STATIC_DRAW);
         Ok(vbo)
     }
 
-    pub fn destroy(&self, gl: glow::Context) {
+    pub fn destroy(&self, gl: &glow::Context) {
         unsafe {
-            gl.delete_buffer(self.vbo);
-            gl.delete_vertex_array(self.vao);
+            if let Some(vbo) = self.vbo {
+                gl.delete_buffer(vbo);
+            }
+            if let Some(index_buffer) = self.index_buffer {
+                gl.delete_buffer(index_buffer);
+            }
+            if let Some(vao) = self.vao {
+                gl.delete_vertex_array(vao);
+            }
+        }
+        for lod in &self.lods {
+            lod.destroy(gl);
         }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::shader_attribute::AttributeFormat;
+
+    /* float3 position, a byte color and an int id, 8 floats on the cpu and 20 bytes in the buffer */
+    fn colored_shader() -> VertexShader {
+        VertexShader::simple(vec![
+            ShaderAttributePair::color_u8(1, String::from("color")),
+            ShaderAttributePair::int(2, String::from("id")),
+        ])
+    }
+
+    #[test]
+    fn attribute_formats() {
+        let color = ShaderAttributePair::color_u16(1, String::from("color"));
+        assert_eq!(color.get_attribute_in().get_stride(), 8);
+        assert_eq!(color.get_attribute_in().data_type(), glow::UNSIGNED_SHORT);
+        assert_eq!(color.get_attribute_in().get_cpp_type(), "vec4");
+        let id = ShaderAttributePair::uint(2, String::from("id"));
+        assert_eq!(id.get_attribute_in().get_cpp_type(), "uint");
+        assert_eq!(id.get_attribute_out().as_vertex_shader_out(), "flat out uint id;\n");
+        // Padded so the attribute after it stays aligned
+        assert_eq!(ShaderAttribute::input(1, String::from("rgb"), 3, AttributeFormat::NormalizedU8).get_stride(), 4);
+        for id in 0..5 {
+            assert_eq!(AttributeFormat::from_id(id).map(|format| format.get_id()), Some(id));
+        }
+        assert_eq!(AttributeFormat::from_id(5), None);
+    }
+
+    #[test]
+    fn pack_layouts() {
+        let vertexes = [
+            1.0, 2.0, 3.0, 1.0, 0.5, 0.0, 2.0, 7.0,
+            4.0, 5.0, 6.0, 0.0, 0.0, 1.0, 1.0, 9.0,
+        ];
+        let mut shader = colored_shader();
+        assert_eq!(shader.get_vertex_size(), 8);
+        assert_eq!(shader.get_attribute_locations(), vec![0, 1, 2]);
+
+        let interleaved = shader.pack_vertexes(&vertexes);
+        assert_eq!(interleaved.len(), 2 * 20);
+        assert_eq!(interleaved[..4], 1.0f32.to_ne_bytes());
+        // Out of range values are clamped
+        assert_eq!(interleaved[12..16], [255, 128, 0, 255]);
+        assert_eq!(interleaved[16..20], 7i32.to_ne_bytes());
+        assert_eq!(interleaved[20..24], 4.0f32.to_ne_bytes());
+
+        shader.set_layout(VertexLayout::Separate);
+        let separate = shader.pack_vertexes(&vertexes);
+        assert_eq!(separate.len(), interleaved.len());
+        assert_eq!(separate[12..16], 4.0f32.to_ne_bytes());
+        assert_eq!(separate[24..28], [255, 128, 0, 255]);
+        assert_eq!(separate[28..32], [0, 0, 255, 255]);
+        assert_eq!(separate[36..40], 9i32.to_ne_bytes());
+
+        // The plain float layouts upload the floats as they are
+        let floats = [0.5, 1.5, 2.5, 0.0, 1.0, 0.0];
+        let packed = VertexShader::default_simple_with_normal().pack_vertexes(&floats);
+        assert_eq!(packed, floats.iter().flat_map(|value| value.to_ne_bytes()).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn index_types() {
+        assert_eq!(IndexType::smallest_for(3, false), IndexType::U16);
+        assert_eq!(IndexType::smallest_for(3, true), IndexType::U8);
+        assert_eq!(IndexType::smallest_for(256, true), IndexType::U8);
+        assert_eq!(IndexType::smallest_for(257, true), IndexType::U16);
+        assert_eq!(IndexType::smallest_for(65536, false), IndexType::U16);
+        assert_eq!(IndexType::smallest_for(65537, false), IndexType::U32);
+        assert_eq!(IndexType::U8.pack(&[0, 1, 255]), vec![0, 1, 255]);
+        assert_eq!(IndexType::U16.pack(&[258]), 258u16.to_ne_bytes().to_vec());
+        assert_eq!(IndexType::U32.pack(&[70000]).len(), 4);
+    }
+}
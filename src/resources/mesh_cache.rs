@@ -0,0 +1,446 @@
+/* parsed meshes and their levels of detail kept on disk in the layout Mesh::create_indexed takes, so big plys are parsed and simplified once */
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+
+use crate::util::error::Error;
+use crate::util::log::{self, Subsystem};
+
+use super::asset_paths::AssetSource;
+use super::mesh::{MeshData, SimpleVertexShader, VertexLayout, VertexShader};
+use super::mesh_loader::{LoadProgress, LoadedMesh};
+use super::mesh_normals::ensure_normals;
+use super::mesh_simplify::build_lod_chain;
+use super::mesh_validate::{check_import, is_repair_on_import};
+use super::parser::{parse_mesh_data, parse_polygon_mesh, RawParser};
+use super::polygon_mesh::PolygonMesh;
+use super::resource_file::{load_file_raw_vec, mesh_cache_dir, mesh_dir};
+use super::shader_attribute::{AttributeFormat, ShaderAttribute, ShaderAttributePair};
+
+const MAGIC: &[u8; 8] = b"RFMESH\0\0";
+/* Bump whenever the entry layout or what the parser or simplifier produce changes, older entries are parsed again */
+pub const CACHE_VERSION: u32 = 3;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/* Turned off with --no-mesh-cache, meshes are then always parsed from their source */
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/* What an entry was made from, it is only used while all of it still matches the source file */
+#[derive(PartialEq, Eq, Debug)]
+struct SourceKey {
+    path: String,
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+    /* Whether the mesh was repaired on import, the entry holds the repaired one then */
+    repaired: bool,
+}
+
+impl SourceKey {
+    fn from_file(source: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(source).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            path: source.to_string_lossy().into_owned(),
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            repaired: is_repair_on_import(),
+        })
+    }
+}
+
+/*
+ * @brief
+ * One file per source mesh in dir. An entry starts with the version and the SourceKey,
+ * followed by the imported mesh and then its levels of detail, each as a vertex layout and
+ * the vertex and index buffers as they are uploaded. The mesh is checked again on every
+ * load so its warnings are still logged.
+ */
+pub struct MeshCache {
+    dir: PathBuf,
+}
+
+impl MeshCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /* The entry for a source file, named after the file with a hash of the whole path */
+    pub fn get_entry_path(&self, source: &Path) -> PathBuf {
+        let stem = source.file_stem().map_or(String::from("mesh"), |stem| stem.to_string_lossy().into_owned());
+        self.dir.join(format!("{}_{:016x}.meshcache", stem, path_hash(&source.to_string_lossy())))
+    }
+
+    /* The cached data of source and its levels of detail, None when there is no entry or it is stale or broken */
+    pub fn read(&self, source: &Path) -> Option<(MeshData, Vec<MeshData>)> {
+        let key = SourceKey::from_file(source)?;
+        let entry = self.get_entry_path(source);
+        let mut data = load_file_raw_vec(entry.clone()).ok()?;
+        match read_entry(&mut data, &key) {
+            Ok(result) => result,
+            Err(err) => {
+                log::warning(Subsystem::Resource, format!("ignoring mesh cache {}: {}", entry.display(), err));
+                None
+            }
+        }
+    }
+
+    pub fn write(&self, source: &Path, data: &MeshData, lods: &[MeshData]) -> Result<(), Error> {
+        let key = match SourceKey::from_file(source) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let entry = self.get_entry_path(source);
+        let dir_name = || String::from(self.dir.to_str().unwrap_or("__UNKNOWN_DIR__"));
+        if let Err(err) = std::fs::create_dir_all(&self.dir) {
+            return Err(Error::loading_file(err, dir_name()));
+        }
+        // Written next to the entry first so a reader never sees half of it
+        let temporary = entry.with_extension("tmp");
+        if let Err(err) = std::fs::write(&temporary, write_entry(&key, data, lods)) {
+            return Err(Error::loading_file(err, dir_name()));
+        }
+        if let Err(err) = std::fs::rename(&temporary, &entry) {
+            let _ = std::fs::remove_file(&temporary);
+            return Err(Error::loading_file(err, dir_name()));
+        }
+        Ok(())
+    }
+
+    /* Reads source through the cache, parsing and simplifying it and filling the cache on a miss */
+    pub fn load(&self, source: &Path, mesh_name: &str, progress: &LoadProgress) -> Result<LoadedMesh, Error> {
+        if let Some((data, lods)) = self.read(source) {
+            log::debug(Subsystem::Resource, format!("mesh {} loaded from cache", source.display()));
+            progress.set_fraction(1.0);
+            return Ok(LoadedMesh { data: import(mesh_name, data)?, lods });
+        }
+        let data = import(mesh_name, parse_mesh_data(load_file_raw_vec(source.to_path_buf())?, progress)?)?;
+        let lods = build_lod_chain(&data);
+        if let Err(err) = self.write(source, &data, &lods) {
+            log::warning(Subsystem::Resource, format!("could not cache mesh {}: {}", source.display(), err));
+        }
+        Ok(LoadedMesh { data, lods })
+    }
+}
+
+/* Checks a parsed mesh and gives it normals when it has none */
+fn import(mesh_name: &str, parsed: MeshData) -> Result<MeshData, Error> {
+    Ok(ensure_normals(check_import(mesh_name, parsed)?))
+}
+
+/*
+ * @brief
+ * Loads a mesh and its levels of detail from the asset roots. Files go through the cache unless
+ * it is disabled, built-in meshes are small and always parsed. Meshes without normals get generated ones.
+ */
+pub fn load_mesh_with_lods(mesh_name: &str, progress: &LoadProgress) -> Result<LoadedMesh, Error> {
+    let parsed = match mesh_dir(mesh_name)? {
+        AssetSource::File(path) => match (is_enabled(), mesh_cache_dir()) {
+            (true, Ok(dir)) => return MeshCache::new(dir).load(&path, mesh_name, progress),
+            _ => parse_mesh_data(load_file_raw_vec(path)?, progress)?,
+        },
+        AssetSource::Embedded(data) => parse_mesh_data(data.to_vec(), progress)?,
+    };
+    let data = import(mesh_name, parsed)?;
+    Ok(LoadedMesh { lods: build_lod_chain(&data), data })
+}
+
+/* The faces of a mesh file as written, for operations that need more than its triangles. Never cached */
+pub fn load_polygon_mesh(mesh_name: &str) -> Result<PolygonMesh, Error> {
+    let data = match mesh_dir(mesh_name)? {
+        AssetSource::File(path) => load_file_raw_vec(path)?,
+        AssetSource::Embedded(data) => data.to_vec(),
+    };
+    parse_polygon_mesh(data, &LoadProgress::new())
+}
+
+/* FNV-1a, std's hasher isn't guaranteed to stay the same between builds */
+fn path_hash(path: &str) -> u64 {
+    path.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn push_u32(result: &mut Vec<u8>, value: u32) {
+    result.extend_from_slice(&value.to_ne_bytes());
+}
+fn push_u64(result: &mut Vec<u8>, value: u64) {
+    result.extend_from_slice(&value.to_ne_bytes());
+}
+fn push_str(result: &mut Vec<u8>, value: &str) {
+    push_u32(result, value.len() as u32);
+    result.extend_from_slice(value.as_bytes());
+}
+
+fn write_entry(key: &SourceKey, data: &MeshData, lods: &[MeshData]) -> Vec<u8> {
+    let index_count = data.indexes.as_ref().map_or(0, Vec::len);
+    let mut result = Vec::with_capacity(256 + data.vertexes.len() * 4 + index_count * 4);
+    result.extend_from_slice(MAGIC);
+    push_u32(&mut result, CACHE_VERSION);
+    push_str(&mut result, &key.path);
+    push_u64(&mut result, key.size);
+    push_u64(&mut result, key.modified_secs);
+    push_u32(&mut result, key.modified_nanos);
+    result.push(key.repaired as u8);
+
+    push_mesh(&mut result, data);
+    push_u32(&mut result, lods.len() as u32);
+    for level in lods {
+        push_mesh(&mut result, level);
+    }
+    result
+}
+
+fn push_mesh(result: &mut Vec<u8>, data: &MeshData) {
+    let index_count = data.indexes.as_ref().map_or(0, Vec::len);
+    let VertexShader::Simple(shader) = &data.shader;
+    let vertex_in = shader.get_vertex_in();
+    push_u32(result, vertex_in.index().unwrap_or(0));
+    push_u32(result, vertex_in.size() as u32);
+    push_u32(result, shader.get_vertex_properties().len() as u32);
+    for property in shader.get_vertex_properties() {
+        push_u32(result, property.get_attribute_in().index().unwrap_or(0));
+        push_u32(result, property.get_attribute_in().size() as u32);
+        push_u32(result, property.get_attribute_in().get_format().get_id());
+        push_str(result, property.get_name());
+    }
+    result.push((shader.get_layout() == VertexLayout::Separate) as u8);
+
+    push_u64(result, data.vertexes.len() as u64);
+    for value in &data.vertexes {
+        result.extend_from_slice(&value.to_ne_bytes());
+    }
+    result.push(data.indexes.is_some() as u8);
+    push_u64(result, index_count as u64);
+    for value in data.indexes.iter().flatten() {
+        push_u32(result, *value);
+    }
+}
+
+fn next_u64(parser: &mut RawParser) -> Result<u64, Error> {
+    Ok(parser.next_vec::<u64>(1)?[0])
+}
+/* count values of T, checked against what is left since a broken entry can claim any count */
+fn next_counted<T: Copy>(parser: &mut RawParser, count: u64, what: &str) -> Result<Vec<T>, Error> {
+    let fits = usize::try_from(count).ok()
+        .and_then(|count| count.checked_mul(size_of::<T>()))
+        .is_some_and(|bytes| bytes <= parser.get_remaining_bytes());
+    if !fits {
+        return Err(Error::invalid_mesh_cache(format!("{} {} don't fit in the entry", count, what)));
+    }
+    parser.next_vec(count as usize)
+}
+fn next_string(parser: &mut RawParser) -> Result<String, Error> {
+    let len = parser.next_raw_u32()? as u64;
+    String::from_utf8(next_counted(parser, len, "string bytes")?).map_err(|err| Error::invalid_mesh_cache(err.to_string()))
+}
+
+/* Ok(None) for an entry of another version or source, errors for broken entries */
+fn read_entry(data: &mut Vec<u8>, expected: &SourceKey) -> Result<Option<(MeshData, Vec<MeshData>)>, Error> {
+    let mut parser = RawParser::create_from_vec(data);
+    if parser.next_vec::<u8>(MAGIC.len())? != MAGIC {
+        return Err(Error::invalid_mesh_cache(String::from("not a mesh cache file")));
+    }
+    if parser.next_raw_u32()? != CACHE_VERSION {
+        return Ok(None);
+    }
+    let key = SourceKey {
+        path: next_string(&mut parser)?,
+        size: next_u64(&mut parser)?,
+        modified_secs: next_u64(&mut parser)?,
+        modified_nanos: parser.next_raw_u32()?,
+        repaired: parser.next_raw_u8()? != 0,
+    };
+    if key != *expected {
+        return Ok(None);
+    }
+
+    let data = next_mesh(&mut parser)?;
+    let level_count = parser.next_raw_u32()?;
+    let mut lods = Vec::new();
+    for _ in 0..level_count {
+        lods.push(next_mesh(&mut parser)?);
+    }
+    if parser.get_remaining_bytes() != 0 {
+        return Err(Error::invalid_mesh_cache(String::from("trailing data")));
+    }
+    Ok(Some((data, lods)))
+}
+
+fn next_mesh(parser: &mut RawParser) -> Result<MeshData, Error> {
+    let position_index = parser.next_raw_u32()?;
+    let vertex_in = match parser.next_raw_u32()? {
+        3 => ShaderAttribute::float3(position_index, String::from("position")),
+        4 => ShaderAttribute::float4(position_index, String::from("position")),
+        size => return Err(Error::invalid_mesh_cache(format!("position with {} components", size))),
+    };
+    let property_count = parser.next_raw_u32()?;
+    let mut properties = Vec::new();
+    for _ in 0..property_count {
+        let index = parser.next_raw_u32()?;
+        let size = parser.next_raw_u32()?;
+        let format = parser.next_raw_u32()?;
+        let name = next_string(parser)?;
+        if !(1..=4).contains(&size) {
+            return Err(Error::invalid_mesh_cache(format!("attribute {} with {} components", name, size)));
+        }
+        let format = match AttributeFormat::from_id(format) {
+            Some(format) => format,
+            None => return Err(Error::invalid_mesh_cache(format!("attribute {} with format {}", name, format))),
+        };
+        properties.push(ShaderAttributePair::new(index, name, size as i32, format));
+    }
+    let mut shader = VertexShader::Simple(SimpleVertexShader::new(
+        vertex_in,
+        ShaderAttribute::output_float4(String::from("gl_Position")),
+        properties));
+    if parser.next_raw_u8()? != 0 {
+        shader.set_layout(VertexLayout::Separate);
+    }
+
+    let vertex_count = next_u64(parser)?;
+    if !vertex_count.is_multiple_of(shader.get_vertex_size() as u64) {
+        return Err(Error::invalid_mesh_cache(String::from("vertex data doesn't match the layout")));
+    }
+    let vertexes = next_counted(parser, vertex_count, "vertex values")?;
+    let has_indexes = parser.next_raw_u8()? != 0;
+    let index_count = next_u64(parser)?;
+    let indexes: Vec<u32> = next_counted(parser, index_count, "indexes")?;
+    Ok(MeshData {
+        vertexes,
+        indexes: has_indexes.then_some(indexes),
+        shader,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::util::error::ErrorKind;
+    use crate::util::test_util::{icosphere_ply, temp_dir};
+
+    #[test]
+    fn round_trip() {
+        let dir = temp_dir("mesh_cache_round_trip");
+        let source = dir.join("sphere.ply");
+        std::fs::write(&source, icosphere_ply()).unwrap();
+        let cache = MeshCache::new(dir.join("cache"));
+        assert!(cache.read(&source).is_none());
+
+        let loaded = cache.load(&source, "sphere.ply", &LoadProgress::new()).unwrap();
+        let (cached, lods) = cache.read(&source).unwrap();
+        assert_eq!(cached.vertexes, loaded.data.vertexes);
+        assert_eq!(cached.indexes, loaded.data.indexes);
+        assert_eq!(cached.shader.get_vertex_size(), 6);
+        assert!(cached.shader.get_normal().is_some_and(|normal| normal.get_attribute_in().index() == Some(1)));
+        assert!(lods.is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn attribute_formats() {
+        let dir = temp_dir("mesh_cache_formats");
+        let source = dir.join("colored.ply");
+        std::fs::write(&source, "colored").unwrap();
+        let mut shader = VertexShader::simple(vec![
+            ShaderAttributePair::color_u8(1, String::from("color")),
+            ShaderAttributePair::uint(2, String::from("id")),
+        ]);
+        shader.set_layout(VertexLayout::Separate);
+        let data = MeshData { vertexes: vec![0.0, 1.0, 2.0, 1.0, 0.5, 0.25, 1.0, 3.0], indexes: None, shader };
+        let cache = MeshCache::new(dir.join("cache"));
+        cache.write(&source, &data, &[]).unwrap();
+
+        let (cached, _) = cache.read(&source).unwrap();
+        assert_eq!(cached.vertexes, data.vertexes);
+        assert_eq!(cached.shader.get_layout(), VertexLayout::Separate);
+        assert_eq!(cached.shader.pack_vertexes(&cached.vertexes), data.shader.pack_vertexes(&data.vertexes));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalidated_by_changes() {
+        let dir = temp_dir("mesh_cache_invalidated");
+        let source = dir.join("sphere.ply");
+        std::fs::write(&source, icosphere_ply()).unwrap();
+        let cache = MeshCache::new(dir.join("cache"));
+        let data = cache.load(&source, "sphere.ply", &LoadProgress::new()).unwrap().data;
+
+        // Only the modification time changes
+        let file = File::options().write(true).open(&source).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        assert!(cache.read(&source).is_none());
+
+        // Same size and time as the entry, but another file
+        cache.write(&source, &data, &[]).unwrap();
+        let other = dir.join("other.ply");
+        std::fs::copy(cache.get_entry_path(&source), cache.get_entry_path(&other)).unwrap();
+        std::fs::copy(&source, &other).unwrap();
+        assert!(cache.read(&other).is_none());
+
+        // Broken entries are ignored
+        assert!(cache.read(&source).is_some());
+        let entry = cache.get_entry_path(&source);
+        let length = std::fs::metadata(&entry).unwrap().len();
+        File::options().write(true).open(&entry).unwrap().set_len(length - 3).unwrap();
+        assert!(cache.read(&source).is_none());
+        assert!(cache.load(&source, "sphere.ply", &LoadProgress::new()).is_ok());
+        assert!(cache.read(&source).is_some());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn counts_past_the_entry() {
+        let key = SourceKey { path: String::from("broken.ply"), size: 1, modified_secs: 2, modified_nanos: 3, repaired: false };
+        let data = MeshData { vertexes: vec![0.0; 6], indexes: None, shader: VertexShader::default_simple_with_normal() };
+        let entry = write_entry(&key, &data, &[]);
+        // The vertex count is followed by the vertexes, the index flag, the index count and the level count
+        let offset = entry.len() - (8 + 6 * 4 + 1 + 8 + 4);
+        // The first would wrap around once multiplied by the size of a float
+        for count in [3u64 << 62, 1 << 40, 12] {
+            let mut broken = entry.clone();
+            broken[offset..offset + 8].copy_from_slice(&count.to_ne_bytes());
+            let result = read_entry(&mut broken, &key);
+            assert!(matches!(result.map_err(Error::get_kind), Err(ErrorKind::InvalidMeshCache(_))), "count {}", count);
+        }
+        assert!(read_entry(&mut entry.clone(), &key).unwrap().is_some());
+    }
+
+    #[test]
+    fn levels_of_detail() {
+        let key = |repaired| SourceKey { path: String::from("detailed.ply"), size: 1, modified_secs: 2, modified_nanos: 3, repaired };
+        let shader = || VertexShader::default_simple_with_normal();
+        let data = MeshData { vertexes: vec![1.0; 18], indexes: Some(vec![0, 1, 2, 2, 1, 0]), shader: shader() };
+        let lods = vec![
+            MeshData { vertexes: vec![2.0; 18], indexes: Some(vec![0, 1, 2]), shader: shader() },
+            MeshData { vertexes: vec![3.0; 12], indexes: None, shader: shader() },
+        ];
+        let (cached, cached_lods) = read_entry(&mut write_entry(&key(false), &data, &lods), &key(false)).unwrap().unwrap();
+        assert_eq!(cached.indexes, data.indexes);
+        assert_eq!(cached_lods.len(), 2);
+        for (cached, level) in cached_lods.iter().zip(&lods) {
+            assert_eq!(cached.vertexes, level.vertexes);
+            assert_eq!(cached.indexes, level.indexes);
+            assert_eq!(cached.shader.get_vertex_size(), 6);
+        }
+
+        // Made with repairing turned on, so not what an import without it would give
+        assert!(read_entry(&mut write_entry(&key(true), &data, &lods), &key(false)).unwrap().is_none());
+    }
+}
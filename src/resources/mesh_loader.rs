@@ -0,0 +1,256 @@
+/* loads meshes on worker threads, only the gl upload is left to the render thread */
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+use crate::util::error::Error;
+
+use super::mesh::MeshData;
+use super::mesh_cache::load_mesh_with_lods;
+use super::mesh_simplify::build_lod_chain;
+use super::mesh_subdivide::{subdivide_to_data, SubdivisionOptions};
+use super::polygon_mesh::PolygonSource;
+
+/* Shared between a load and its worker thread */
+#[derive(Clone)]
+pub struct LoadProgress {
+    /* Fraction done in millionths */
+    done: Arc<AtomicU32>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl LoadProgress {
+    pub fn new() -> Self {
+        Self {
+            done: Arc::new(AtomicU32::new(0)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn get_fraction(&self) -> f32 {
+        self.done.load(Ordering::Relaxed) as f32 / 1_000_000.0
+    }
+    pub fn set_fraction(&self, fraction: f32) {
+        self.done.store((fraction.clamp(0.0, 1.0) * 1_000_000.0) as u32, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Default for LoadProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct MeshLoadJob {
+    id: u64,
+    file_name: String,
+    progress: LoadProgress,
+}
+
+impl MeshLoadJob {
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+    pub fn get_file_name(&self) -> &str {
+        &self.file_name
+    }
+    pub fn get_progress(&self) -> f32 {
+        self.progress.get_fraction()
+    }
+}
+
+/* A finished load, the levels of detail are simplified on the loading thread too or come from the mesh cache */
+pub struct LoadedMesh {
+    pub data: MeshData,
+    pub lods: Vec<MeshData>,
+}
+
+/*
+ * @brief
+ * Every load gets its own thread which reads and parses the ply, or subdivides a mesh,
+ * and sends the LoadedMesh back.
+ * Cancelled loads are forgotten straight away, their thread stops at the next check.
+ * A thread that panics sends an error instead so its load still finishes.
+ */
+pub struct MeshLoader {
+    jobs: Vec<MeshLoadJob>,
+    next_id: u64,
+    sender: Sender<(u64, Result<LoadedMesh, Error>)>,
+    receiver: Receiver<(u64, Result<LoadedMesh, Error>)>,
+}
+
+impl MeshLoader {
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            jobs: Vec::new(),
+            next_id: 0,
+            sender,
+            receiver,
+        }
+    }
+
+    /* Starts loading file_name from the meshes in the asset roots through the mesh cache, returns the id of the load */
+    pub fn load(&mut self, file_name: &str) -> u64 {
+        let thread_file_name = String::from(file_name);
+        self.spawn(file_name, move |progress| load_mesh_with_lods(&thread_file_name, progress))
+    }
+
+    /* Starts subdividing the faces from source and building its levels of detail, the load is shown as name */
+    pub fn subdivide(&mut self, name: &str, source: PolygonSource, options: SubdivisionOptions) -> u64 {
+        self.spawn(name, move |progress| {
+            let data = subdivide_to_data(&source.load()?, &options)?;
+            let lods = build_lod_chain(&data);
+            progress.set_fraction(1.0);
+            Ok(LoadedMesh { data, lods })
+        })
+    }
+
+    fn spawn<F>(&mut self, file_name: &str, job: F) -> u64
+    where
+        F: FnOnce(&LoadProgress) -> Result<LoadedMesh, Error> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        let progress = LoadProgress::new();
+        let sender = self.sender.clone();
+        let thread_progress = progress.clone();
+        let thread_file_name = String::from(file_name);
+        std::thread::spawn(move || {
+            // Nothing is shared with the job except the progress, which is only atomics
+            let result = catch_unwind(AssertUnwindSafe(|| job(&thread_progress))).unwrap_or_else(|payload| {
+                let message = match payload.downcast_ref::<&str>() {
+                    Some(message) => message.to_string(),
+                    None => payload.downcast_ref::<String>().cloned().unwrap_or_default(),
+                };
+                Err(Error::load_panicked(&thread_file_name, message))
+            });
+            // The loader may be gone already, nobody wants the mesh then
+            let _ = sender.send((id, result));
+        });
+        self.jobs.push(MeshLoadJob { id, file_name: String::from(file_name), progress });
+        id
+    }
+
+    pub fn cancel(&mut self, id: u64) {
+        if let Some(index) = self.jobs.iter().position(|job| job.id == id) {
+            self.jobs.remove(index).progress.cancel();
+        }
+    }
+
+    /* Loads that haven't finished yet */
+    pub fn get_jobs(&self) -> &Vec<MeshLoadJob> {
+        &self.jobs
+    }
+
+    /* Finished loads, never blocks */
+    pub fn poll(&mut self) -> Vec<(MeshLoadJob, Result<LoadedMesh, Error>)> {
+        let mut result = Vec::new();
+        while let Ok((id, data)) = self.receiver.try_recv() {
+            if let Some(index) = self.jobs.iter().position(|job| job.id == id) {
+                result.push((self.jobs.remove(index), data));
+            }
+        }
+        result
+    }
+}
+
+impl Default for MeshLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::resources::parser::parse_mesh_data;
+    use crate::util::test_util::icosphere_ply;
+
+    fn wait_for(loader: &mut MeshLoader) -> Vec<(MeshLoadJob, Result<LoadedMesh, Error>)> {
+        let start = Instant::now();
+        loop {
+            let finished = loader.poll();
+            if !finished.is_empty() || start.elapsed() > Duration::from_secs(10) {
+                return finished;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn load_embedded() {
+        let mut loader = MeshLoader::new();
+        let id = loader.load("icosphere.ply");
+        assert_eq!(loader.get_jobs().len(), 1);
+
+        let finished = wait_for(&mut loader);
+        assert_eq!(finished.len(), 1);
+        let (job, data) = &finished[0];
+        assert_eq!(job.get_id(), id);
+        assert_eq!(job.get_progress(), 1.0);
+        let data = &data.as_ref().unwrap().data;
+        assert_eq!(data.vertexes.len(), 162 * 6);
+        assert_eq!(data.indexes.as_ref().map(Vec::len), Some(320 * 3));
+        assert!(loader.get_jobs().is_empty());
+    }
+
+    #[test]
+    fn cancel_and_missing() {
+        let mut loader = MeshLoader::new();
+        let cancelled = loader.load("icosphere.ply");
+        loader.cancel(cancelled);
+        assert!(loader.get_jobs().is_empty());
+
+        loader.load("missing_mesh.ply");
+        let finished = wait_for(&mut loader);
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].0.get_file_name(), "missing_mesh.ply");
+        assert!(finished[0].1.is_err());
+    }
+
+    #[test]
+    fn panicking_load_finishes() {
+        let mut loader = MeshLoader::new();
+        loader.spawn("broken.ply", |_| panic!("index out of bounds"));
+        let finished = wait_for(&mut loader);
+        assert_eq!(finished.len(), 1);
+        let err = finished[0].1.as_ref().err().unwrap();
+        assert_eq!(err.to_string(), "Loading broken.ply crashed (index out of bounds)");
+        assert!(loader.get_jobs().is_empty());
+    }
+
+    #[test]
+    fn subdivide_in_the_background() {
+        let mut loader = MeshLoader::new();
+        let options = SubdivisionOptions { levels: 2, ..Default::default() };
+        loader.subdivide("icosphere_mesh", PolygonSource::File(String::from("icosphere.ply")), options);
+        let finished = wait_for(&mut loader);
+        assert_eq!(finished.len(), 1);
+        let loaded = finished[0].1.as_ref().unwrap();
+        assert_eq!(loaded.data.indexes.as_ref().map(Vec::len), Some(320 * 16 * 3));
+
+        // Too big to be worth trying
+        let options = SubdivisionOptions { levels: 10, ..Default::default() };
+        loader.subdivide("icosphere_mesh", PolygonSource::File(String::from("icosphere.ply")), options);
+        assert!(wait_for(&mut loader)[0].1.is_err());
+    }
+
+    #[test]
+    fn cancelled_parse_stops() {
+        let progress = LoadProgress::new();
+        progress.cancel();
+        assert!(parse_mesh_data(icosphere_ply(), &progress).is_err_and(|err| err.is_load_cancelled()));
+    }
+}
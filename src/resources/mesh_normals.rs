@@ -0,0 +1,240 @@
+/* computes normals for meshes that come without them */
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use super::mesh::{MeshData, VertexShader};
+
+/* How much every triangle around a vertex counts towards its smooth normal */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NormalWeighting {
+    Area,
+    /* By the angle of the triangle at the vertex, doesn't depend on how the surface is triangulated */
+    Angle,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NormalMode {
+    /* Every triangle is flat */
+    Face,
+    Smooth(NormalWeighting),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct NormalOptions {
+    pub mode: NormalMode,
+    /* Triangles meeting at a sharper angle than this, in radians, get separate normals */
+    pub crease_angle: f32,
+}
+
+impl Default for NormalOptions {
+    fn default() -> Self {
+        Self {
+            mode: NormalMode::Smooth(NormalWeighting::Angle),
+            crease_angle: 60.0_f32.to_radians(),
+        }
+    }
+}
+
+/*
+ * @brief
+ * Builds indexed data laid out for VertexShader::default_simple_with_normal from the positions of data.
+ * Vertexes at the same position are merged first, then split again wherever the normals differ,
+ * so hard edges get one vertex per side. Attributes other than the position are dropped.
+ */
+pub fn generate_normals(data: &MeshData, options: &NormalOptions) -> MeshData {
+    generate_normals_from(&data.vertexes, data.indexes.as_deref(), data.shader.get_vertex_size(), options)
+}
+
+/* Meshes loaded without normals get smooth ones with the default crease angle */
+pub fn ensure_normals(data: MeshData) -> MeshData {
+    match data.shader.get_normal() {
+        Some(_) => data,
+        None => generate_normals(&data, &NormalOptions::default()),
+    }
+}
+
+/* generate_normals for vertexes with vertex_size floats each, the position being the first 3 */
+pub fn generate_normals_from(vertexes: &[f32], indexes: Option<&[u32]>, vertex_size: usize, options: &NormalOptions) -> MeshData {
+    let (positions, position_ids) = weld_positions(vertexes.chunks_exact(vertex_size).map(Vec3::from_slice));
+    let vertex_count = position_ids.len();
+    let triangles: Vec<[usize; 3]> = match indexes {
+        Some(indexes) => indexes
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize])
+            .filter(|triangle| triangle.iter().all(|index| *index < vertex_count))
+            .map(|triangle| triangle.map(|index| position_ids[index]))
+            .collect(),
+        None => (0..vertex_count / 3)
+            .map(|triangle| [position_ids[triangle * 3], position_ids[triangle * 3 + 1], position_ids[triangle * 3 + 2]])
+            .collect(),
+    };
+
+    let face_normals: Vec<Vec3> = triangles
+        .iter()
+        .map(|[a, b, c]| (positions[*b] - positions[*a]).cross(positions[*c] - positions[*a]))
+        .collect();
+    let unit_normals: Vec<Vec3> = face_normals.iter().map(|normal| normal.normalize_or_zero()).collect();
+
+    let mut incident: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+    for (triangle, corners) in triangles.iter().enumerate() {
+        for position in corners {
+            if !incident[*position].contains(&triangle) {
+                incident[*position].push(triangle);
+            }
+        }
+    }
+
+    let min_cos = options.crease_angle.cos();
+    let mut vertexes = Vec::new();
+    let mut indexes = Vec::with_capacity(triangles.len() * 3);
+    let mut created: HashMap<(usize, [u32; 3]), u32> = HashMap::new();
+    for (triangle, corners) in triangles.iter().enumerate() {
+        for position in corners {
+            let normal = match options.mode {
+                NormalMode::Face => unit_normals[triangle],
+                NormalMode::Smooth(weighting) => {
+                    let mut sum = Vec3::ZERO;
+                    for other in &incident[*position] {
+                        let other_normal = unit_normals[*other];
+                        // Degenerate triangles have no side of the crease, they take all neighbours
+                        let is_flat_enough = unit_normals[triangle] == Vec3::ZERO
+                            || unit_normals[triangle].dot(other_normal) >= min_cos;
+                        if other_normal == Vec3::ZERO || !is_flat_enough {
+                            continue;
+                        }
+                        let weight = match weighting {
+                            NormalWeighting::Area => face_normals[*other].length() * 0.5,
+                            NormalWeighting::Angle => corner_angle(&positions, &triangles[*other], *position),
+                        };
+                        sum += other_normal * weight;
+                    }
+                    sum.normalize_or_zero()
+                }
+            };
+            // Only triangles without any area around them end up here
+            let normal = if normal == Vec3::ZERO { Vec3::Z } else { normal };
+            let key = (*position, position_key(normal));
+            let index = *created.entry(key).or_insert_with(|| {
+                vertexes.extend_from_slice(&positions[*position].to_array());
+                vertexes.extend_from_slice(&normal.to_array());
+                (vertexes.len() / 6 - 1) as u32
+            });
+            indexes.push(index);
+        }
+    }
+    MeshData {
+        vertexes,
+        indexes: Some(indexes),
+        shader: VertexShader::default_simple_with_normal(),
+    }
+}
+
+/* Equal for positions that are the same bit for bit, with -0.0 counted as 0.0 */
+pub fn position_key(position: Vec3) -> [u32; 3] {
+    position.to_array().map(|value| (value + 0.0).to_bits())
+}
+
+/* The distinct positions, and for every vertex the index of its position, see position_key */
+pub fn weld_positions(vertexes: impl IntoIterator<Item = Vec3>) -> (Vec<Vec3>, Vec<usize>) {
+    let mut positions = Vec::new();
+    let mut ids: HashMap<[u32; 3], usize> = HashMap::new();
+    let position_ids = vertexes
+        .into_iter()
+        .map(|position| {
+            *ids.entry(position_key(position)).or_insert_with(|| {
+                positions.push(position);
+                positions.len() - 1
+            })
+        })
+        .collect();
+    (positions, position_ids)
+}
+
+/* Angle of triangle at the corner at position */
+fn corner_angle(positions: &[Vec3], triangle: &[usize; 3], position: usize) -> f32 {
+    let corner = match triangle.iter().position(|index| *index == position) {
+        Some(corner) => corner,
+        None => return 0.0,
+    };
+    let at = positions[triangle[corner]];
+    let next = positions[triangle[(corner + 1) % 3]] - at;
+    let previous = positions[triangle[(corner + 2) % 3]] - at;
+    if next == Vec3::ZERO || previous == Vec3::ZERO {
+        return 0.0;
+    }
+    next.angle_between(previous)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::{cube_corner, icosphere};
+
+    /* A triangle soup cube with float4 positions and every triangle facing outwards */
+    fn cube_data() -> MeshData {
+        let quads = [[0, 1, 3, 2], [4, 5, 7, 6], [0, 1, 5, 4], [2, 3, 7, 6], [0, 2, 6, 4], [1, 3, 7, 5]];
+        let mut vertexes = Vec::new();
+        for quad in quads {
+            for triangle in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                let [a, b, c] = triangle.map(cube_corner);
+                let outwards = (b - a).cross(c - a).dot(a + b + c) > 0.0;
+                let ordered = if outwards { [a, b, c] } else { [a, c, b] };
+                for point in ordered {
+                    vertexes.extend_from_slice(&point.extend(1.0).to_array());
+                }
+            }
+        }
+        MeshData { vertexes, indexes: None, shader: VertexShader::default_simple() }
+    }
+
+    fn normals(data: &MeshData) -> Vec<Vec3> {
+        data.vertexes.chunks_exact(6).map(|vertex| Vec3::from_slice(&vertex[3..])).collect()
+    }
+
+    #[test]
+    fn cube_keeps_hard_edges() {
+        for mode in [NormalMode::Face, NormalMode::Smooth(NormalWeighting::Area), NormalMode::Smooth(NormalWeighting::Angle)] {
+            let result = generate_normals(&cube_data(), &NormalOptions { mode, ..Default::default() });
+            // 8 corners with 3 sides each
+            assert_eq!(result.vertexes.len(), 24 * 6);
+            assert_eq!(result.indexes.as_ref().map(Vec::len), Some(36));
+            for normal in normals(&result) {
+                assert!(normal.abs().max_element() > 0.999, "{:?} isn't an axis", normal);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_past_the_crease_angle() {
+        let options = NormalOptions {
+            mode: NormalMode::Smooth(NormalWeighting::Angle),
+            crease_angle: 100.0_f32.to_radians(),
+        };
+        let result = generate_normals(&cube_data(), &options);
+        assert_eq!(result.vertexes.len(), 8 * 6);
+        for vertex in result.vertexes.chunks_exact(6) {
+            let position = Vec3::from_slice(&vertex[..3]);
+            let normal = Vec3::from_slice(&vertex[3..]);
+            assert!(normal.dot(position.normalize()) > 0.999);
+        }
+    }
+
+    #[test]
+    fn sphere_normals_point_outwards() {
+        let mut sphere = icosphere();
+        // Throw the normals of the file away
+        sphere.vertexes = sphere.vertexes.chunks_exact(6).flat_map(|vertex| vertex[..3].to_vec()).collect();
+        sphere.shader = VertexShader::simple(Vec::new());
+
+        let result = generate_normals(&sphere, &NormalOptions::default());
+        assert_eq!(result.vertexes.len(), 162 * 6);
+        assert!(result.shader.get_normal().is_some());
+        for vertex in result.vertexes.chunks_exact(6) {
+            let position = Vec3::from_slice(&vertex[..3]);
+            let normal = Vec3::from_slice(&vertex[3..]);
+            assert!(normal.dot(position.normalize()) > 0.99);
+        }
+    }
+}
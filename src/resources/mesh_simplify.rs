@@ -0,0 +1,516 @@
+/* quadric error edge collapse decimation, and the chains of simplified meshes used for level of detail */
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use glam::{DMat3, DVec3, Vec3};
+
+use super::mesh::{MeshData, VertexShader};
+use super::mesh_normals::position_key;
+
+/* Boundary edges are held in place by planes this much stronger than the surface's own */
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+/* Collapses turning a triangle further than this (as the cosine between old and new normal) are refused */
+const MIN_NORMAL_COS: f64 = 0.2;
+
+/* Each level of a chain has about this fraction of the triangles of the one before */
+const LOD_REDUCTION: f64 = 0.5;
+/* Meshes with fewer triangles don't get levels, nor are levels made below it */
+pub const LOD_MIN_TRIANGLES: usize = 2000;
+pub const LOD_MAX_LEVELS: usize = 4;
+/* Triangles wanted per pixel of the height the mesh takes up on screen, squared */
+const TRIANGLES_PER_PIXEL: f64 = 0.5;
+
+/* The error of moving a point away from a set of planes, as a symmetric 4x4 matrix */
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+        let [a, b, c] = normal.to_array();
+        let d = -normal.dot(point);
+        Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|value| value * weight))
+    }
+
+    fn add(&self, other: &Quadric) -> Self {
+        let mut result = *self;
+        for (value, other) in result.0.iter_mut().zip(other.0) {
+            *value += other;
+        }
+        result
+    }
+
+    fn evaluate(&self, p: DVec3) -> f64 {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, dd] = self.0;
+        aa * p.x * p.x + 2.0 * ab * p.x * p.y + 2.0 * ac * p.x * p.z + 2.0 * ad * p.x
+            + bb * p.y * p.y + 2.0 * bc * p.y * p.z + 2.0 * bd * p.y
+            + cc * p.z * p.z + 2.0 * cd * p.z
+            + dd
+    }
+
+    /* The point with the least error, None when the planes don't pin one down */
+    fn optimal(&self) -> Option<DVec3> {
+        let [aa, ab, ac, ad, bb, bc, bd, cc, cd, _] = self.0;
+        let matrix = DMat3::from_cols(DVec3::new(aa, ab, ac), DVec3::new(ab, bb, bc), DVec3::new(ac, bc, cc));
+        let determinant = matrix.determinant();
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        Some(matrix.inverse() * -DVec3::new(ad, bd, cd))
+    }
+}
+
+/* A candidate collapse, ordered so the cheapest comes out of the heap first */
+struct Collapse {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    position: DVec3,
+    /* The versions of both vertexes when this was worked out, it is stale once either changed */
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost.total_cmp(&other.cost) == Ordering::Equal
+    }
+}
+impl Eq for Collapse {}
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    positions: Vec<DVec3>,
+    normals: Vec<Vec3>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    triangles: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    /* Triangles around every vertex, dead ones are only dropped now and then */
+    vertex_triangles: Vec<Vec<usize>>,
+    heap: BinaryHeap<Collapse>,
+    triangle_count: usize,
+}
+
+impl Simplifier {
+    fn new(data: &MeshData) -> Self {
+        let vertex_size = data.shader.get_vertex_size();
+        let normal_offset = data.shader.get_property_offset("normal");
+        // Vertexes split only for seams of other attributes are merged. Ones split for hard edges
+        // have different normals and stay apart, so each side of the edge is a boundary and held in place.
+        let mut ids: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+        let mut positions = Vec::new();
+        let mut normal_sums: Vec<Vec3> = Vec::new();
+        let welded: Vec<usize> = data.vertexes.chunks_exact(vertex_size).map(|vertex| {
+            let position = Vec3::from_slice(&vertex[..3]);
+            let normal = normal_offset.map_or(Vec3::ZERO, |offset| Vec3::from_slice(&vertex[offset..offset + 3]));
+            *ids.entry((position_key(position), position_key(normal))).or_insert_with(|| {
+                positions.push(position.as_dvec3());
+                normal_sums.push(normal);
+                positions.len() - 1
+            })
+        }).collect();
+        let triangles: Vec<[usize; 3]> = match &data.indexes {
+            Some(indexes) => indexes
+                .chunks_exact(3)
+                .filter(|triangle| triangle.iter().all(|index| (*index as usize) < welded.len()))
+                .map(|triangle| [0, 1, 2].map(|corner| welded[triangle[corner] as usize]))
+                .collect(),
+            None => (0..welded.len() / 3).map(|triangle| [0, 1, 2].map(|corner| welded[triangle * 3 + corner])).collect(),
+        };
+        let triangles: Vec<[usize; 3]> = triangles
+            .into_iter()
+            .filter(|[a, b, c]| a != b && b != c && a != c)
+            .collect();
+
+        let mut result = Self {
+            quadrics: vec![Quadric::default(); positions.len()],
+            versions: vec![0; positions.len()],
+            vertex_triangles: vec![Vec::new(); positions.len()],
+            normals: Vec::new(),
+            alive: vec![true; triangles.len()],
+            triangle_count: triangles.len(),
+            heap: BinaryHeap::new(),
+            positions,
+            triangles,
+        };
+        for (index, triangle) in result.triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|vertex| result.positions[vertex]);
+            let cross = (b - a).cross(c - a);
+            let area = cross.length() * 0.5;
+            let quadric = Quadric::from_plane(cross.normalize_or_zero(), a, area);
+            for vertex in triangle {
+                result.quadrics[*vertex] = result.quadrics[*vertex].add(&quadric);
+                result.vertex_triangles[*vertex].push(index);
+                if normal_offset.is_none() {
+                    normal_sums[*vertex] += cross.as_vec3();
+                }
+            }
+        }
+        result.normals = normal_sums.into_iter().map(|normal| normalize_or(normal, Vec3::Z)).collect();
+        result.add_boundary_planes();
+        let mut edges: Vec<(usize, usize)> = result
+            .triangles
+            .iter()
+            .flat_map(|triangle| (0..3).map(|corner| (triangle[corner], triangle[(corner + 1) % 3])))
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        for (a, b) in edges {
+            result.queue(a, b);
+        }
+        result
+    }
+
+    fn add_boundary_planes(&mut self) {
+        let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        for (index, triangle) in self.triangles.iter().enumerate() {
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+                edges.entry((a.min(b), a.max(b))).or_insert((0, index)).0 += 1;
+            }
+        }
+        for ((a, b), (count, triangle)) in edges {
+            if count != 1 {
+                continue;
+            }
+            let [p0, p1, p2] = self.triangles[triangle].map(|vertex| self.positions[vertex]);
+            let face_normal = (p1 - p0).cross(p2 - p0).normalize_or_zero();
+            let edge = self.positions[b] - self.positions[a];
+            let normal = edge.cross(face_normal).normalize_or_zero();
+            let quadric = Quadric::from_plane(normal, self.positions[a], BOUNDARY_WEIGHT * edge.length_squared());
+            self.quadrics[a] = self.quadrics[a].add(&quadric);
+            self.quadrics[b] = self.quadrics[b].add(&quadric);
+        }
+    }
+
+    fn queue(&mut self, a: usize, b: usize) {
+        let quadric = self.quadrics[a].add(&self.quadrics[b]);
+        let candidates = [self.positions[a], self.positions[b], (self.positions[a] + self.positions[b]) * 0.5];
+        let position = quadric.optimal().unwrap_or_else(|| {
+            candidates.into_iter().min_by(|x, y| quadric.evaluate(*x).total_cmp(&quadric.evaluate(*y))).unwrap()
+        });
+        // Keep the vertex the new position is closer to, so its normal carries over best
+        let (keep, remove) = match position.distance_squared(self.positions[a]) <= position.distance_squared(self.positions[b]) {
+            true => (a, b),
+            false => (b, a),
+        };
+        self.heap.push(Collapse {
+            cost: quadric.evaluate(position).max(0.0),
+            keep,
+            remove,
+            position,
+            versions: (self.versions[keep], self.versions[remove]),
+        });
+    }
+
+    fn neighbours(&self, vertex: usize) -> Vec<usize> {
+        let mut result: Vec<usize> = self.vertex_triangles[vertex]
+            .iter()
+            .filter(|triangle| self.alive[**triangle])
+            .flat_map(|triangle| self.triangles[*triangle])
+            .filter(|other| *other != vertex)
+            .collect();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    /* Whether the collapse keeps the surface a manifold and doesn't fold any triangle over */
+    fn is_valid(&self, collapse: &Collapse) -> bool {
+        let (keep, remove) = (collapse.keep, collapse.remove);
+        let keep_neighbours = self.neighbours(keep);
+        let shared = self.neighbours(remove).iter().filter(|vertex| keep_neighbours.contains(vertex)).count();
+        let shared_triangles = self.vertex_triangles[keep]
+            .iter()
+            .filter(|triangle| self.alive[**triangle] && self.triangles[**triangle].contains(&remove))
+            .count();
+        if shared != shared_triangles {
+            return false;
+        }
+        for vertex in [keep, remove] {
+            for triangle in &self.vertex_triangles[vertex] {
+                let corners = self.triangles[*triangle];
+                if !self.alive[*triangle] || (corners.contains(&keep) && corners.contains(&remove)) {
+                    continue;
+                }
+                let old = corners.map(|corner| self.positions[corner]);
+                let new = corners.map(|corner| if corner == vertex { collapse.position } else { self.positions[corner] });
+                let old_normal = (old[1] - old[0]).cross(old[2] - old[0]).normalize_or_zero();
+                let new_normal = (new[1] - new[0]).cross(new[2] - new[0]).normalize_or_zero();
+                if new_normal == DVec3::ZERO || old_normal.dot(new_normal) < MIN_NORMAL_COS {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn collapse(&mut self, collapse: &Collapse) {
+        let (keep, remove) = (collapse.keep, collapse.remove);
+        let old_keep = self.positions[keep];
+        let edge = self.positions[remove] - old_keep;
+        let t = match edge.length_squared() > 0.0 {
+            true => ((collapse.position - old_keep).dot(edge) / edge.length_squared()).clamp(0.0, 1.0) as f32,
+            false => 0.0,
+        };
+        self.normals[keep] = normalize_or(self.normals[keep].lerp(self.normals[remove], t), self.normals[keep]);
+        self.positions[keep] = collapse.position;
+        self.quadrics[keep] = self.quadrics[keep].add(&self.quadrics[remove]);
+        self.versions[keep] += 1;
+        self.versions[remove] += 1;
+
+        for triangle in std::mem::take(&mut self.vertex_triangles[remove]) {
+            if !self.alive[triangle] {
+                continue;
+            }
+            if self.triangles[triangle].contains(&keep) {
+                self.alive[triangle] = false;
+                self.triangle_count -= 1;
+            } else {
+                for corner in self.triangles[triangle].iter_mut() {
+                    if *corner == remove {
+                        *corner = keep;
+                    }
+                }
+                self.vertex_triangles[keep].push(triangle);
+            }
+        }
+        let alive = &self.alive;
+        self.vertex_triangles[keep].retain(|triangle| alive[*triangle]);
+        for neighbour in self.neighbours(keep) {
+            self.versions[neighbour] += 1;
+        }
+        for neighbour in self.neighbours(keep) {
+            self.queue(keep, neighbour);
+            // The neighbour's other edges changed cost too since its version moved on
+            for other in self.neighbours(neighbour) {
+                if other != keep {
+                    self.queue(neighbour, other);
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        while self.triangle_count > target_triangles {
+            let collapse = match self.heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            if (self.versions[collapse.keep], self.versions[collapse.remove]) != collapse.versions {
+                continue;
+            }
+            if self.is_valid(&collapse) {
+                self.collapse(&collapse);
+            }
+        }
+    }
+
+    fn into_data(self) -> MeshData {
+        let mut remap: Vec<Option<u32>> = vec![None; self.positions.len()];
+        let mut vertexes = Vec::new();
+        let mut indexes = Vec::with_capacity(self.triangle_count * 3);
+        for (triangle, corners) in self.triangles.iter().enumerate() {
+            if !self.alive[triangle] {
+                continue;
+            }
+            for corner in corners {
+                let index = *remap[*corner].get_or_insert_with(|| {
+                    vertexes.extend_from_slice(&self.positions[*corner].as_vec3().to_array());
+                    vertexes.extend_from_slice(&self.normals[*corner].to_array());
+                    (vertexes.len() / 6 - 1) as u32
+                });
+                indexes.push(index);
+            }
+        }
+        MeshData {
+            vertexes,
+            indexes: Some(indexes),
+            shader: VertexShader::default_simple_with_normal(),
+        }
+    }
+}
+
+/* normal scaled to unit length, or fallback if it has none */
+fn normalize_or(normal: Vec3, fallback: Vec3) -> Vec3 {
+    match normal.try_normalize() {
+        Some(normal) => normal,
+        None => fallback,
+    }
+}
+
+/*
+ * @brief
+ * Collapses the edges of data that change the surface least until at most target_triangles are left,
+ * or no collapse is possible without folding triangles over or tearing the surface.
+ * The result uses VertexShader::default_simple_with_normal. Normals are carried along from data,
+ * or worked out from the triangles if it has none. Hard edges, where vertexes at the same position
+ * have different normals, are kept like the border of an open surface.
+ */
+pub fn simplify(data: &MeshData, target_triangles: usize) -> MeshData {
+    let mut simplifier = Simplifier::new(data);
+    simplifier.run(target_triangles);
+    simplifier.into_data()
+}
+
+pub fn get_triangle_count(data: &MeshData) -> usize {
+    match &data.indexes {
+        Some(indexes) => indexes.len() / 3,
+        None => data.vertexes.len() / data.shader.get_vertex_size() / 3,
+    }
+}
+
+/*
+ * The levels of detail of data, finest first and without data itself.
+ * Every level is simplified from the one before it and has about half its triangles.
+ * Objects keep the program made for data when drawing a level, so only data laid out
+ * like VertexShader::default_simple_with_normal gets any.
+ */
+pub fn build_lod_chain(data: &MeshData) -> Vec<MeshData> {
+    let mut result: Vec<MeshData> = Vec::new();
+    let layout = VertexShader::default_simple_with_normal();
+    if data.shader.get_vertex_size() != layout.get_vertex_size() || data.shader.get_property_offset("normal") != layout.get_property_offset("normal") {
+        return result;
+    }
+    let mut triangles = get_triangle_count(data);
+    while result.len() < LOD_MAX_LEVELS {
+        let target = (triangles as f64 * LOD_REDUCTION) as usize;
+        if target < LOD_MIN_TRIANGLES {
+            break;
+        }
+        let level = simplify(result.last().unwrap_or(data), target);
+        let level_triangles = get_triangle_count(&level);
+        // Stuck, the mesh can't get simpler without breaking
+        if level_triangles as f64 > triangles as f64 * 0.9 {
+            break;
+        }
+        triangles = level_triangles;
+        result.push(level);
+    }
+    result
+}
+
+/*
+ * Which level to draw when the mesh is screen_size pixels tall. triangle_counts holds the counts
+ * of the full mesh and of each level, finest first. The coarsest level with enough triangles wins.
+ */
+pub fn select_lod(triangle_counts: &[usize], screen_size: f64) -> usize {
+    let wanted = screen_size * screen_size * TRIANGLES_PER_PIXEL;
+    triangle_counts
+        .iter()
+        .rposition(|count| *count as f64 >= wanted)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::mesh_normals::{generate_normals, NormalOptions};
+    use crate::resources::primitives::cylinder;
+    use crate::util::test_util::{icosphere, is_closed};
+
+    fn positions(data: &MeshData) -> Vec<Vec3> {
+        data.vertexes.chunks_exact(6).map(|vertex| Vec3::from_slice(&vertex[..3])).collect()
+    }
+
+    #[test]
+    fn sphere_keeps_its_shape() {
+        let sphere = icosphere();
+        let result = simplify(&sphere, 80);
+        let triangles = get_triangle_count(&result);
+        assert!((40..=80).contains(&triangles), "{} triangles", triangles);
+        assert!(is_closed(&result));
+        for vertex in result.vertexes.chunks_exact(6) {
+            let position = Vec3::from_slice(&vertex[..3]);
+            let normal = Vec3::from_slice(&vertex[3..]);
+            assert!((position.length() - 1.0).abs() < 0.2, "{:?} left the sphere", position);
+            assert!((normal.length() - 1.0).abs() < 1e-4);
+            assert!(normal.dot(position.normalize()) > 0.8);
+        }
+    }
+
+    #[test]
+    fn hard_edges_stay_hard() {
+        // The rims have a vertex with an up or down normal for the cap and a sideways one for the side
+        let cylinder = generate_normals(&cylinder(64), &NormalOptions::default());
+        let result = simplify(&cylinder, 100);
+        assert!(get_triangle_count(&result) < get_triangle_count(&cylinder) / 2);
+        for vertex in result.vertexes.chunks_exact(6) {
+            let normal = Vec3::from_slice(&vertex[3..]);
+            assert!(normal.y.abs() > 0.999 || normal.y.abs() < 1e-3, "{:?} was averaged across the rim", normal);
+        }
+    }
+
+    /* size by size quads in the z = 0 plane, without normals */
+    fn grid(size: u32) -> MeshData {
+        let mut vertexes = Vec::new();
+        for y in 0..=size {
+            for x in 0..=size {
+                vertexes.extend_from_slice(&[x as f32, y as f32, 0.0]);
+            }
+        }
+        let mut indexes = Vec::new();
+        let row = size + 1;
+        for y in 0..size {
+            for x in 0..size {
+                let corner = y * row + x;
+                indexes.extend_from_slice(&[corner, corner + 1, corner + row + 1, corner, corner + row + 1, corner + row]);
+            }
+        }
+        MeshData { vertexes, indexes: Some(indexes), shader: VertexShader::simple(Vec::new()) }
+    }
+
+    #[test]
+    fn flat_grid_keeps_its_border() {
+        let grid = grid(10);
+        let result = simplify(&grid, 2);
+        assert!(get_triangle_count(&result) < 50);
+
+        let points = positions(&result);
+        assert!(points.iter().all(|point| point.z.abs() < 1e-5));
+        let min = points.iter().fold(Vec3::splat(f32::MAX), |min, point| min.min(*point));
+        let max = points.iter().fold(Vec3::splat(f32::MIN), |max, point| max.max(*point));
+        assert!(min.abs_diff_eq(Vec3::ZERO, 1e-4) && max.abs_diff_eq(Vec3::new(10.0, 10.0, 0.0), 1e-4));
+        for normal in result.vertexes.chunks_exact(6).map(|vertex| Vec3::from_slice(&vertex[3..])) {
+            assert!(normal.abs_diff_eq(Vec3::Z, 1e-4));
+        }
+    }
+
+    #[test]
+    fn lod_selection() {
+        let counts = [100000, 50000, 25000, 12000];
+        assert_eq!(select_lod(&counts, 2000.0), 0);
+        // 300 pixels want 45000 triangles
+        assert_eq!(select_lod(&counts, 300.0), 1);
+        assert_eq!(select_lod(&counts, 200.0), 2);
+        assert_eq!(select_lod(&counts, 10.0), 3);
+        assert_eq!(select_lod(&[500], 10.0), 0);
+        // Small meshes don't get levels
+        assert!(build_lod_chain(&icosphere()).is_empty());
+    }
+
+    #[test]
+    fn lod_chain_gets_coarser() {
+        // Without normals the levels wouldn't fit the object's program
+        assert!(build_lod_chain(&grid(60)).is_empty());
+        let grid = generate_normals(&grid(60), &NormalOptions::default());
+        let chain = build_lod_chain(&grid);
+        assert!(!chain.is_empty() && chain.len() <= LOD_MAX_LEVELS);
+        let mut previous = get_triangle_count(&grid);
+        for level in &chain {
+            let triangles = get_triangle_count(level);
+            assert!(triangles < previous && triangles >= LOD_MIN_TRIANGLES);
+            previous = triangles;
+        }
+    }
+}
@@ -0,0 +1,394 @@
+/* loop and catmull-clark subdivision surfaces with sharp creases */
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::util::error::Error;
+
+use super::mesh::MeshData;
+use super::mesh_normals::{generate_normals_from, weld_positions, NormalMode, NormalOptions, NormalWeighting};
+use super::polygon_mesh::PolygonMesh;
+
+/* Subdivisions that would end up with more triangles than this are refused */
+pub const MAX_TRIANGLES: usize = 2_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubdivisionScheme {
+    /* Loop for meshes made only of triangles, Catmull-Clark for anything else */
+    Auto,
+    /* Splits every triangle into 4, other faces are split into triangles first */
+    Loop,
+    /* Splits every face into quads, one for each corner */
+    CatmullClark,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SubdivisionOptions {
+    pub scheme: SubdivisionScheme,
+    pub levels: u32,
+    /* Edges between faces meeting at a sharper angle than this, in radians, stay sharp. Open edges always do */
+    pub crease_angle: f32,
+}
+
+impl Default for SubdivisionOptions {
+    fn default() -> Self {
+        Self {
+            scheme: SubdivisionScheme::Auto,
+            levels: 1,
+            crease_angle: 60.0_f32.to_radians(),
+        }
+    }
+}
+
+/* Connectivity of a mesh welded by position */
+struct Topology {
+    positions: Vec<Vec3>,
+    faces: Vec<Vec<usize>>,
+    /* Every edge as (smaller, larger) vertex with the faces around it */
+    edges: HashMap<(usize, usize), Vec<usize>>,
+    creases: HashMap<(usize, usize), bool>,
+    /* Other ends of the edges at every vertex */
+    neighbours: Vec<Vec<usize>>,
+    min_cos: f32,
+}
+
+impl Topology {
+    fn new(positions: Vec<Vec3>, faces: Vec<Vec<usize>>, crease_angle: f32) -> Self {
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        let mut neighbours = vec![Vec::new(); positions.len()];
+        for (index, face) in faces.iter().enumerate() {
+            for corner in 0..face.len() {
+                let (a, b) = (face[corner], face[(corner + 1) % face.len()]);
+                let faces = edges.entry(edge_key(a, b)).or_default();
+                if faces.is_empty() {
+                    neighbours[a].push(b);
+                    neighbours[b].push(a);
+                }
+                faces.push(index);
+            }
+        }
+        let face_normals: Vec<Vec3> = faces.iter().map(|face| face_normal(&positions, face)).collect();
+        let min_cos = crease_angle.cos();
+        let creases = edges
+            .iter()
+            .map(|(edge, faces)| {
+                let is_crease = match faces.as_slice() {
+                    [a, b] => face_normals[*a].dot(face_normals[*b]) < min_cos,
+                    // Open and non manifold edges have no smooth side
+                    _ => true,
+                };
+                (*edge, is_crease)
+            })
+            .collect();
+        Self { positions, faces, edges, creases, neighbours, min_cos }
+    }
+
+    fn is_crease(&self, a: usize, b: usize) -> bool {
+        self.creases[&edge_key(a, b)]
+    }
+
+    /*
+     * Where a vertex moves to. Smooth vertexes get smooth_position, ones on exactly 2 creases
+     * slide along them like a curve. Corners, where more or fewer creases meet or the 2 creases
+     * turn sharper than the crease angle, stay put.
+     */
+    fn vertex_position(&self, vertex: usize, smooth_position: impl FnOnce() -> Vec3) -> Vec3 {
+        let creases: Vec<usize> = self.neighbours[vertex]
+            .iter()
+            .copied()
+            .filter(|other| self.is_crease(vertex, *other))
+            .collect();
+        let position = self.positions[vertex];
+        match creases.len() {
+            0 => smooth_position(),
+            2 => {
+                let (previous, next) = (self.positions[creases[0]], self.positions[creases[1]]);
+                let turn = (position - previous).normalize_or_zero().dot((next - position).normalize_or_zero());
+                match turn < self.min_cos {
+                    true => position,
+                    false => position * 0.75 + (previous + next) * 0.125,
+                }
+            }
+            _ => position,
+        }
+    }
+
+    fn loop_step(&self) -> (Vec<Vec3>, Vec<Vec<usize>>) {
+        let mut positions: Vec<Vec3> = (0..self.positions.len())
+            .map(|vertex| self.vertex_position(vertex, || {
+                let valence = self.neighbours[vertex].len();
+                let beta = match valence {
+                    3 => 3.0 / 16.0,
+                    valence => 3.0 / (8.0 * valence as f32),
+                };
+                let sum = self.neighbours[vertex].iter().fold(Vec3::ZERO, |sum, other| sum + self.positions[*other]);
+                self.positions[vertex] * (1.0 - valence as f32 * beta) + sum * beta
+            }))
+            .collect();
+
+        let mut edge_points: HashMap<(usize, usize), usize> = HashMap::with_capacity(self.edges.len());
+        for (&(a, b), faces) in &self.edges {
+            let middle = (self.positions[a] + self.positions[b]) * 0.5;
+            let position = match self.creases[&(a, b)] {
+                true => middle,
+                false => {
+                    let opposite: Vec3 = faces
+                        .iter()
+                        .map(|face| self.positions[*self.faces[*face].iter().find(|corner| **corner != a && **corner != b).unwrap()])
+                        .fold(Vec3::ZERO, |sum, position| sum + position);
+                    middle * 0.75 + opposite * 0.125
+                }
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(position);
+        }
+
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        for face in &self.faces {
+            let [a, b, c] = [face[0], face[1], face[2]];
+            let [ab, bc, ca] = [(a, b), (b, c), (c, a)].map(|(x, y)| edge_points[&edge_key(x, y)]);
+            faces.extend([vec![a, ab, ca], vec![ab, b, bc], vec![ca, bc, c], vec![ab, bc, ca]]);
+        }
+        (positions, faces)
+    }
+
+    fn catmull_clark_step(&self) -> (Vec<Vec3>, Vec<Vec<usize>>) {
+        let face_points: Vec<Vec3> = self.faces
+            .iter()
+            .map(|face| face.iter().map(|corner| self.positions[*corner]).fold(Vec3::ZERO, |sum, position| sum + position) / face.len() as f32)
+            .collect();
+
+        let mut faces_around: Vec<Vec<usize>> = vec![Vec::new(); self.positions.len()];
+        for (index, face) in self.faces.iter().enumerate() {
+            for corner in face {
+                faces_around[*corner].push(index);
+            }
+        }
+        let mut positions: Vec<Vec3> = (0..self.positions.len())
+            .map(|vertex| self.vertex_position(vertex, || {
+                let valence = self.neighbours[vertex].len() as f32;
+                let face_average = faces_around[vertex].iter().map(|face| face_points[*face]).fold(Vec3::ZERO, |sum, position| sum + position)
+                    / faces_around[vertex].len() as f32;
+                let edge_average = self.neighbours[vertex]
+                    .iter()
+                    .map(|other| (self.positions[vertex] + self.positions[*other]) * 0.5)
+                    .fold(Vec3::ZERO, |sum, position| sum + position) / valence;
+                (face_average + edge_average * 2.0 + self.positions[vertex] * (valence - 3.0)) / valence
+            }))
+            .collect();
+
+        let face_start = positions.len();
+        positions.extend_from_slice(&face_points);
+
+        let mut edge_points: HashMap<(usize, usize), usize> = HashMap::with_capacity(self.edges.len());
+        for (&(a, b), faces) in &self.edges {
+            let position = match self.creases[&(a, b)] {
+                true => (self.positions[a] + self.positions[b]) * 0.5,
+                false => (self.positions[a] + self.positions[b] + face_points[faces[0]] + face_points[faces[1]]) * 0.25,
+            };
+            edge_points.insert((a, b), positions.len());
+            positions.push(position);
+        }
+
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        for (index, face) in self.faces.iter().enumerate() {
+            let count = face.len();
+            for corner in 0..count {
+                let (previous, current, next) = (face[(corner + count - 1) % count], face[corner], face[(corner + 1) % count]);
+                faces.push(vec![
+                    current,
+                    edge_points[&edge_key(current, next)],
+                    face_start + index,
+                    edge_points[&edge_key(previous, current)],
+                ]);
+            }
+        }
+        (positions, faces)
+    }
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+/* Newell's normal, so faces that aren't flat still get a sensible one */
+fn face_normal(positions: &[Vec3], face: &[usize]) -> Vec3 {
+    let mut normal = Vec3::ZERO;
+    for corner in 0..face.len() {
+        let current = positions[face[corner]];
+        let next = positions[face[(corner + 1) % face.len()]];
+        normal += (current - next).cross(current + next) * 0.5;
+    }
+    normal.normalize_or_zero()
+}
+
+/* Positions of mesh merged where they are equal so faces split for hard edges connect again */
+fn weld(mesh: &PolygonMesh) -> (Vec<Vec3>, Vec<Vec<usize>>) {
+    let (positions, welded) = weld_positions(mesh.get_positions());
+    let faces = mesh.faces
+        .iter()
+        .map(|face| {
+            let mut face: Vec<usize> = face.iter().map(|corner| welded[*corner as usize]).collect();
+            face.dedup();
+            while face.len() > 1 && face.first() == face.last() {
+                face.pop();
+            }
+            face
+        })
+        .filter(|face| face.len() >= 3)
+        .collect();
+    (positions, faces)
+}
+
+/*
+ * @brief
+ * Subdivides mesh options.levels times. Only positions are carried over, normals are
+ * generated afterwards with the crease angle so the creases look sharp too.
+ * Loop turns every triangle into 4, Catmull-Clark turns an n sided face into n quads.
+ */
+pub fn subdivide(mesh: &PolygonMesh, options: &SubdivisionOptions) -> PolygonMesh {
+    let (mut positions, mut faces) = weld(mesh);
+    let use_loop = uses_loop(&faces, options.scheme);
+    if use_loop {
+        faces = faces
+            .into_iter()
+            .flat_map(|face| (2..face.len()).map(move |corner| vec![face[0], face[corner - 1], face[corner]]).collect::<Vec<_>>())
+            .collect();
+    }
+    for _ in 0..options.levels {
+        let topology = Topology::new(positions, faces, options.crease_angle);
+        (positions, faces) = match use_loop {
+            true => topology.loop_step(),
+            false => topology.catmull_clark_step(),
+        };
+    }
+    let faces = faces.into_iter().map(|face| face.into_iter().map(|corner| corner as u32).collect()).collect();
+    PolygonMesh::from_positions(&positions, faces)
+}
+
+fn uses_loop(faces: &[Vec<usize>], scheme: SubdivisionScheme) -> bool {
+    match scheme {
+        SubdivisionScheme::Auto => faces.iter().all(|face| face.len() == 3),
+        SubdivisionScheme::Loop => true,
+        SubdivisionScheme::CatmullClark => false,
+    }
+}
+
+/* How many triangles subdivide_to_data would give, without subdividing */
+pub fn get_subdivided_triangle_count(mesh: &PolygonMesh, options: &SubdivisionOptions) -> usize {
+    let (_, faces) = weld(mesh);
+    let growth = 4_usize.saturating_pow(options.levels);
+    match uses_loop(&faces, options.scheme) {
+        true => faces.iter().map(|face| face.len() - 2).sum::<usize>().saturating_mul(growth),
+        // The first step turns every face into a quad per corner, each quad is 2 triangles
+        false if options.levels > 0 => faces.iter().map(Vec::len).sum::<usize>().saturating_mul(growth / 4 * 2),
+        false => faces.iter().map(|face| face.len() - 2).sum(),
+    }
+}
+
+/* subdivide, split into triangles and given normals that keep the creases. Refused past MAX_TRIANGLES */
+pub fn subdivide_to_data(mesh: &PolygonMesh, options: &SubdivisionOptions) -> Result<MeshData, Error> {
+    let triangles = get_subdivided_triangle_count(mesh, options);
+    if triangles > MAX_TRIANGLES {
+        return Err(Error::too_many_triangles(triangles, MAX_TRIANGLES));
+    }
+    let data = subdivide(mesh, options).triangulate();
+    let normal_options = NormalOptions {
+        mode: NormalMode::Smooth(NormalWeighting::Angle),
+        crease_angle: options.crease_angle,
+    };
+    Ok(generate_normals_from(&data.vertexes, data.indexes.as_deref(), data.shader.get_vertex_size(), &normal_options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::error::ErrorKind;
+    use crate::util::test_util::{cube_corner, icosphere_polygons};
+
+    fn cube() -> PolygonMesh {
+        let positions: Vec<Vec3> = (0..8).map(cube_corner).collect();
+        let faces = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        PolygonMesh::from_positions(&positions, faces.iter().map(|face| face.to_vec()).collect())
+    }
+
+    fn smooth(levels: u32) -> SubdivisionOptions {
+        SubdivisionOptions { levels, crease_angle: std::f32::consts::PI, ..Default::default() }
+    }
+
+    #[test]
+    fn catmull_clark_cube() {
+        let result = subdivide(&cube(), &smooth(1));
+        // 8 corners, 6 face points and 12 edge points
+        assert_eq!(result.get_vertex_count(), 26);
+        assert_eq!(result.faces.len(), 24);
+        assert!(result.faces.iter().all(|face| face.len() == 4));
+        // A smooth cube shrinks towards a sphere, corners move in the most
+        let corners = result.get_positions()[..8].to_vec();
+        for corner in corners {
+            assert!(corner.abs().abs_diff_eq(Vec3::splat(5.0 / 9.0), 1e-6), "{:?}", corner);
+        }
+
+        let result = subdivide(&cube(), &smooth(3));
+        let radii: Vec<f32> = result.get_positions().iter().map(|position| position.length()).collect();
+        let (min, max) = radii.iter().fold((f32::MAX, 0.0_f32), |(min, max), radius| (min.min(*radius), max.max(*radius)));
+        assert!(max - min < 0.15, "{} to {}", min, max);
+    }
+
+    #[test]
+    fn creases_keep_the_cube() {
+        // Every edge of a cube is 90 degrees, sharper than the default crease angle
+        let result = subdivide(&cube(), &SubdivisionOptions { levels: 2, ..Default::default() });
+        assert_eq!(result.faces.len(), 96);
+        for position in result.get_positions() {
+            assert!((position.abs().max_element() - 1.0).abs() < 1e-6, "{:?} left the cube", position);
+        }
+        let data = subdivide_to_data(&cube(), &SubdivisionOptions::default()).unwrap();
+        for normal in data.vertexes.chunks_exact(6).map(|vertex| Vec3::from_slice(&vertex[3..])) {
+            assert!(normal.abs().max_element() > 0.999, "{:?} isn't an axis", normal);
+        }
+    }
+
+    #[test]
+    fn loop_sphere() {
+        let sphere = icosphere_polygons();
+        let result = subdivide(&sphere, &smooth(1));
+        // Every edge gets a vertex, every triangle becomes 4
+        assert_eq!(result.get_vertex_count(), 162 + 480);
+        assert_eq!(result.faces.len(), 320 * 4);
+        assert!(result.is_triangles());
+        // Loop is approximating so the surface moves in a little, but stays round
+        for position in result.get_positions() {
+            assert!(position.length() < 1.0 + 1e-5 && position.length() > 0.95);
+        }
+    }
+
+    #[test]
+    fn open_edges_stay() {
+        // Two triangles making a flat square, all edges are open but the diagonal
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        let square = PolygonMesh::from_positions(&positions, vec![vec![0, 1, 2], vec![0, 2, 3]]);
+        let result = subdivide(&square, &SubdivisionOptions { levels: 2, ..Default::default() });
+        assert_eq!(result.faces.len(), 32);
+        let positions = result.get_positions();
+        assert!(positions.iter().all(|position| position.z == 0.0));
+        // The open edges meet at right angles, sharper than the crease angle, so the corners stay
+        for corner in [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y] {
+            assert!(positions.contains(&corner));
+        }
+    }
+
+    #[test]
+    fn triangle_budget() {
+        let options = SubdivisionOptions { levels: 2, ..Default::default() };
+        let count = get_subdivided_triangle_count(&cube(), &options);
+        assert_eq!(count, subdivide_to_data(&cube(), &options).unwrap().indexes.unwrap().len() / 3);
+
+        let sphere = icosphere_polygons();
+        assert_eq!(get_subdivided_triangle_count(&sphere, &smooth(3)), 320 * 64);
+        // 320 * 4^10 is past the budget, nothing is subdivided
+        let result = subdivide_to_data(&sphere, &smooth(10));
+        assert!(matches!(result.map_err(Error::get_kind), Err(ErrorKind::TooManyTriangles(count, MAX_TRIANGLES)) if count == 320 << 20));
+    }
+}
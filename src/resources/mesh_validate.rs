@@ -0,0 +1,437 @@
+/* checks imported meshes for broken data and optionally repairs them */
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use glam::Vec3;
+
+use crate::util::error::Error;
+use crate::util::log::{self, Subsystem};
+
+use super::mesh::MeshData;
+use super::mesh_normals::{ensure_normals, position_key};
+
+static REPAIR_ON_IMPORT: AtomicBool = AtomicBool::new(false);
+
+/* Turned on with --repair-meshes, imported meshes then get every repair step instead of failing on errors */
+pub fn set_repair_on_import(enabled: bool) {
+    REPAIR_ON_IMPORT.store(enabled, Ordering::Relaxed);
+}
+pub fn is_repair_on_import() -> bool {
+    REPAIR_ON_IMPORT.load(Ordering::Relaxed)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    /* The mesh draws but may look or pick wrong */
+    Warning,
+    /* Drawing the mesh would read past its buffers or break the bounds */
+    Error,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum MeshIssue {
+    /* The vertex data doesn't end on a whole vertex, or the indexes on a whole triangle */
+    IncompleteData { remaining_floats: usize, remaining_indexes: usize },
+    IndexOutOfRange { triangle: usize, index: u32 },
+    NonFinitePosition { vertex: usize },
+    /* Two corners are the same vertex or the triangle has no area */
+    DegenerateTriangle { triangle: usize },
+    /* Uses the same vertexes as an earlier triangle, in any order */
+    DuplicateTriangle { triangle: usize, first: usize },
+    /* More than two triangles share the edge between the positions of a and b */
+    NonManifoldEdge { a: u32, b: u32, triangle_count: usize },
+    UnreferencedVertex { vertex: usize },
+}
+
+impl MeshIssue {
+    pub fn get_severity(&self) -> Severity {
+        match self {
+            Self::IncompleteData { .. } | Self::IndexOutOfRange { .. } | Self::NonFinitePosition { .. } => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+
+    /* Short name of the kind of issue, used to count them */
+    pub fn get_kind_name(&self) -> &'static str {
+        match self {
+            Self::IncompleteData { .. } => "incomplete data",
+            Self::IndexOutOfRange { .. } => "out of range indexes",
+            Self::NonFinitePosition { .. } => "non finite positions",
+            Self::DegenerateTriangle { .. } => "degenerate triangles",
+            Self::DuplicateTriangle { .. } => "duplicate triangles",
+            Self::NonManifoldEdge { .. } => "non manifold edges",
+            Self::UnreferencedVertex { .. } => "unreferenced vertexes",
+        }
+    }
+}
+
+impl std::fmt::Display for MeshIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IncompleteData { remaining_floats, remaining_indexes } => write!(f, "{} floats and {} indexes left over", remaining_floats, remaining_indexes),
+            Self::IndexOutOfRange { triangle, index } => write!(f, "triangle {} uses vertex {} which doesn't exist", triangle, index),
+            Self::NonFinitePosition { vertex } => write!(f, "vertex {} has a position that isn't a number", vertex),
+            Self::DegenerateTriangle { triangle } => write!(f, "triangle {} has no area", triangle),
+            Self::DuplicateTriangle { triangle, first } => write!(f, "triangle {} repeats triangle {}", triangle, first),
+            Self::NonManifoldEdge { a, b, triangle_count } => write!(f, "edge from vertex {} to {} is shared by {} triangles", a, b, triangle_count),
+            Self::UnreferencedVertex { vertex } => write!(f, "vertex {} isn't used by any triangle", vertex),
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ValidationReport {
+    issues: Vec<MeshIssue>,
+}
+
+impl ValidationReport {
+    pub fn get_issues(&self) -> &Vec<MeshIssue> {
+        &self.issues
+    }
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.get_severity() == Severity::Error)
+    }
+
+    /* How many issues of each kind with the given severity, like "3 degenerate triangles, 1 non manifold edges" */
+    pub fn summary(&self, severity: Severity) -> String {
+        let mut counts: Vec<(&'static str, usize)> = Vec::new();
+        for issue in self.issues.iter().filter(|issue| issue.get_severity() == severity) {
+            match counts.iter_mut().find(|(name, _)| *name == issue.get_kind_name()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((issue.get_kind_name(), 1)),
+            }
+        }
+        counts.iter().map(|(name, count)| format!("{} {}", count, name)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/* The triangles of data as vertex indexes, non indexed data uses every 3 vertexes in order */
+fn get_triangles(data: &MeshData) -> Vec<[u32; 3]> {
+    match &data.indexes {
+        Some(indexes) => indexes.chunks_exact(3).map(|triangle| [triangle[0], triangle[1], triangle[2]]).collect(),
+        None => (0..(data.vertexes.len() / data.shader.get_vertex_size() / 3) as u32)
+            .map(|triangle| [triangle * 3, triangle * 3 + 1, triangle * 3 + 2])
+            .collect(),
+    }
+}
+
+fn get_position(data: &MeshData, vertex: usize) -> Vec3 {
+    Vec3::from_slice(&data.vertexes[vertex * data.shader.get_vertex_size()..])
+}
+
+fn is_degenerate(positions: [Vec3; 3]) -> bool {
+    let [a, b, c] = positions;
+    (b - a).cross(c - a).length_squared() == 0.0
+}
+
+/* Every problem found in data, in the order listed in MeshIssue */
+pub fn validate(data: &MeshData) -> ValidationReport {
+    let mut issues = Vec::new();
+    let vertex_size = data.shader.get_vertex_size();
+    let vertex_count = data.vertexes.len() / vertex_size;
+    let remaining_floats = data.vertexes.len() % vertex_size;
+    let remaining_indexes = data.indexes.as_ref().map_or(0, |indexes| indexes.len() % 3);
+    if remaining_floats != 0 || remaining_indexes != 0 {
+        issues.push(MeshIssue::IncompleteData { remaining_floats, remaining_indexes });
+    }
+
+    let triangles = get_triangles(data);
+    let mut in_range = vec![true; triangles.len()];
+    for (triangle, corners) in triangles.iter().enumerate() {
+        for index in corners.iter().filter(|index| **index as usize >= vertex_count) {
+            issues.push(MeshIssue::IndexOutOfRange { triangle, index: *index });
+            in_range[triangle] = false;
+        }
+    }
+    let positions: Vec<Vec3> = (0..vertex_count).map(|vertex| get_position(data, vertex)).collect();
+    for (vertex, position) in positions.iter().enumerate() {
+        if !position.is_finite() {
+            issues.push(MeshIssue::NonFinitePosition { vertex });
+        }
+    }
+
+    let mut seen: HashMap<[u32; 3], usize> = HashMap::new();
+    // Keyed by the positions of the ends, the vertexes first seen on the edge and how many triangles use it
+    let mut edges: HashMap<[[u32; 3]; 2], (u32, u32, usize)> = HashMap::new();
+    let mut referenced = vec![false; vertex_count];
+    for (triangle, corners) in triangles.iter().enumerate().filter(|(triangle, _)| in_range[*triangle]) {
+        for index in corners {
+            referenced[*index as usize] = true;
+        }
+        let corner_positions = corners.map(|index| positions[index as usize]);
+        if corners[0] == corners[1] || corners[1] == corners[2] || corners[2] == corners[0] || is_degenerate(corner_positions) {
+            issues.push(MeshIssue::DegenerateTriangle { triangle });
+            continue;
+        }
+        let mut sorted = *corners;
+        sorted.sort_unstable();
+        if let Some(first) = seen.get(&sorted) {
+            issues.push(MeshIssue::DuplicateTriangle { triangle, first: *first });
+            continue;
+        }
+        seen.insert(sorted, triangle);
+        // By position so vertexes split for normals or texture seams still count as one
+        for corner in 0..3 {
+            let (a, b) = (corners[corner], corners[(corner + 1) % 3]);
+            let (key_a, key_b) = (position_key(positions[a as usize]), position_key(positions[b as usize]));
+            edges.entry([key_a.min(key_b), key_a.max(key_b)]).or_insert((a, b, 0)).2 += 1;
+        }
+    }
+    let mut non_manifold: Vec<MeshIssue> = edges
+        .values()
+        .filter(|(_, _, count)| *count > 2)
+        .map(|(a, b, count)| MeshIssue::NonManifoldEdge { a: *a, b: *b, triangle_count: *count })
+        .collect();
+    non_manifold.sort_by_key(|issue| match issue {
+        MeshIssue::NonManifoldEdge { a, b, .. } => (*a, *b),
+        _ => (0, 0),
+    });
+    issues.extend(non_manifold);
+    for (vertex, _) in referenced.iter().enumerate().filter(|(_, referenced)| !**referenced) {
+        issues.push(MeshIssue::UnreferencedVertex { vertex });
+    }
+    ValidationReport { issues }
+}
+
+/*
+ * @brief
+ * Steps of repair, in the order they run. Triangles using missing vertexes or ones with
+ * positions that aren't numbers are always dropped, as is data past the last whole vertex.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct RepairOptions {
+    /* Merges vertexes with positions closer than this and the same other attributes, None keeps them */
+    pub weld: Option<f32>,
+    pub remove_degenerates: bool,
+    pub remove_duplicates: bool,
+    pub remove_unreferenced: bool,
+    /* Recomputes the normals of the vertexes, or generates them if there were none */
+    pub recompute_normals: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            weld: Some(0.0),
+            remove_degenerates: true,
+            remove_duplicates: true,
+            remove_unreferenced: true,
+            recompute_normals: false,
+        }
+    }
+}
+
+/* A repaired copy of data, always indexed and with the same vertex layout unless normals had to be generated */
+pub fn repair(data: MeshData, options: &RepairOptions) -> MeshData {
+    let vertex_size = data.shader.get_vertex_size();
+    let vertex_count = data.vertexes.len() / vertex_size;
+    let mut vertexes = data.vertexes.clone();
+    vertexes.truncate(vertex_count * vertex_size);
+    let mut triangles: Vec<[u32; 3]> = get_triangles(&data)
+        .into_iter()
+        .filter(|corners| corners.iter().all(|index| (*index as usize) < vertex_count
+            && get_position(&data, *index as usize).is_finite()))
+        .collect();
+
+    if let Some(tolerance) = options.weld {
+        let mut ids: HashMap<Vec<u32>, u32> = HashMap::new();
+        let remap: Vec<u32> = vertexes
+            .chunks_exact(vertex_size)
+            .enumerate()
+            .map(|(vertex, values)| {
+                // Snapped to the tolerance, so positions close to a cell border can stay apart
+                let mut key: Vec<u32> = values[..3]
+                    .iter()
+                    .map(|value| match tolerance > 0.0 {
+                        true => ((value / tolerance).round() * tolerance + 0.0).to_bits(),
+                        false => (value + 0.0).to_bits(),
+                    })
+                    .collect();
+                key.extend(values[3..].iter().map(|value| value.to_bits()));
+                *ids.entry(key).or_insert(vertex as u32)
+            })
+            .collect();
+        for corners in triangles.iter_mut() {
+            *corners = corners.map(|index| remap[index as usize]);
+        }
+    }
+
+    let position = |index: u32| Vec3::from_slice(&vertexes[index as usize * vertex_size..]);
+    if options.remove_degenerates {
+        triangles.retain(|corners| {
+            corners[0] != corners[1] && corners[1] != corners[2] && corners[2] != corners[0]
+                && !is_degenerate(corners.map(position))
+        });
+    }
+    if options.remove_duplicates {
+        let mut seen: HashMap<[u32; 3], ()> = HashMap::new();
+        triangles.retain(|corners| {
+            let mut sorted = *corners;
+            sorted.sort_unstable();
+            seen.insert(sorted, ()).is_none()
+        });
+    }
+    if options.remove_unreferenced {
+        let mut remap: Vec<Option<u32>> = vec![None; vertex_count];
+        let mut compacted = Vec::with_capacity(vertexes.len());
+        for corners in triangles.iter_mut() {
+            for index in corners.iter_mut() {
+                *index = *remap[*index as usize].get_or_insert_with(|| {
+                    let start = *index as usize * vertex_size;
+                    compacted.extend_from_slice(&vertexes[start..start + vertex_size]);
+                    (compacted.len() / vertex_size - 1) as u32
+                });
+            }
+        }
+        vertexes = compacted;
+    }
+
+    let mut result = MeshData {
+        vertexes,
+        indexes: Some(triangles.into_iter().flatten().collect()),
+        shader: data.shader,
+    };
+    if options.recompute_normals {
+        result = recompute_normals(result);
+    }
+    result
+}
+
+/* Area weighted normals for every vertex from the triangles using it, vertexes split at hard edges stay split */
+fn recompute_normals(mut data: MeshData) -> MeshData {
+    let offset = match data.shader.get_property_offset("normal") {
+        Some(offset) => offset,
+        None => return ensure_normals(data),
+    };
+    let vertex_size = data.shader.get_vertex_size();
+    let vertex_count = data.vertexes.len() / vertex_size;
+    let mut sums = vec![Vec3::ZERO; vertex_count];
+    for corners in get_triangles(&data) {
+        let [a, b, c] = corners.map(|index| get_position(&data, index as usize));
+        let normal = (b - a).cross(c - a);
+        for index in corners {
+            sums[index as usize] += normal;
+        }
+    }
+    for (vertex, sum) in sums.into_iter().enumerate() {
+        let normal = sum.try_normalize().unwrap_or(Vec3::Z);
+        let start = vertex * vertex_size + offset;
+        data.vertexes[start..start + 3].copy_from_slice(&normal.to_array());
+    }
+    data
+}
+
+/*
+ * @brief
+ * Run on every imported mesh. Warnings are logged, errors fail the import unless
+ * repairing on import is turned on, then the mesh is repaired with every step instead.
+ */
+pub fn check_import(mesh_name: &str, data: MeshData) -> Result<MeshData, Error> {
+    let report = validate(&data);
+    if report.is_clean() {
+        return Ok(data);
+    }
+    if is_repair_on_import() {
+        let options = RepairOptions { recompute_normals: true, ..Default::default() };
+        let repaired = repair(data, &options);
+        let found: Vec<String> = [Severity::Error, Severity::Warning].map(|severity| report.summary(severity)).into_iter().filter(|summary| !summary.is_empty()).collect();
+        log::info(Subsystem::Resource, format!("repaired {} ({})", mesh_name, found.join(", ")));
+        return Ok(repaired);
+    }
+    if report.has_errors() {
+        let first = report.issues.iter().find(|issue| issue.get_severity() == Severity::Error).unwrap();
+        return Err(Error::invalid_mesh(mesh_name, format!("{}, first: {}", report.summary(Severity::Error), first)));
+    }
+    log::warning(Subsystem::Resource, format!("{} has {}", mesh_name, report.summary(Severity::Warning)));
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::mesh::VertexShader;
+    use crate::util::test_util::icosphere;
+
+    /* A unit square of 2 triangles with float3 positions, and room for more vertexes */
+    fn square(extra_vertexes: &[f32], extra_indexes: &[u32]) -> MeshData {
+        let mut vertexes = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        vertexes.extend_from_slice(extra_vertexes);
+        let mut indexes = vec![0, 1, 2, 0, 2, 3];
+        indexes.extend_from_slice(extra_indexes);
+        MeshData { vertexes, indexes: Some(indexes), shader: VertexShader::simple(Vec::new()) }
+    }
+
+    #[test]
+    fn clean_meshes() {
+        assert!(validate(&square(&[], &[])).is_clean());
+        let sphere = icosphere();
+        assert!(validate(&sphere).is_clean(), "{:?}", validate(&sphere));
+    }
+
+    #[test]
+    fn finds_issues() {
+        let data = square(&[0.5, f32::NAN, 0.0, 2.0, 2.0, 2.0], &[0, 1, 9, 2, 1, 0, 1, 1, 3, 0, 1, 4]);
+        let report = validate(&data);
+        assert!(report.has_errors());
+        let issues = report.get_issues();
+        assert!(issues.contains(&MeshIssue::IndexOutOfRange { triangle: 2, index: 9 }));
+        assert!(issues.contains(&MeshIssue::NonFinitePosition { vertex: 4 }));
+        assert!(issues.contains(&MeshIssue::DuplicateTriangle { triangle: 3, first: 0 }));
+        assert!(issues.contains(&MeshIssue::DegenerateTriangle { triangle: 4 }));
+        assert!(issues.contains(&MeshIssue::UnreferencedVertex { vertex: 5 }));
+        assert_eq!(report.summary(Severity::Error), "1 out of range indexes, 1 non finite positions");
+
+        // A third triangle on the diagonal
+        let fan = square(&[0.5, 0.5, 1.0], &[0, 4, 2]);
+        let issues = validate(&fan).issues;
+        assert!(issues.iter().any(|issue| matches!(issue, MeshIssue::NonManifoldEdge { triangle_count: 3, .. })));
+
+        let incomplete = MeshData { vertexes: vec![0.0; 10], indexes: Some(vec![0, 1]), shader: VertexShader::simple(Vec::new()) };
+        assert!(validate(&incomplete).issues.contains(&MeshIssue::IncompleteData { remaining_floats: 1, remaining_indexes: 2 }));
+    }
+
+    #[test]
+    fn repairs() {
+        // A copy of corner 2 splitting the square, a triangle out of range, a duplicate, an unused vertex
+        let mut data = square(&[1.0, 1.0, 0.0, 5.0, 5.0, 5.0], &[0, 1, 9, 0, 2, 1]);
+        data.indexes.as_mut().unwrap()[4] = 4;
+        let repaired = repair(data, &RepairOptions::default());
+        assert!(validate(&repaired).is_clean(), "{:?}", validate(&repaired));
+        assert_eq!(repaired.vertexes.len(), 4 * 3);
+        assert_eq!(repaired.indexes.as_ref().map(Vec::len), Some(6));
+
+        // Normals are generated when there were none, and point the way the triangles face
+        let options = RepairOptions { recompute_normals: true, ..Default::default() };
+        let repaired = repair(square(&[], &[]), &options);
+        assert!(repaired.shader.get_normal().is_some());
+        for vertex in repaired.vertexes.chunks_exact(6) {
+            assert_eq!(Vec3::from_slice(&vertex[3..]), Vec3::Z);
+        }
+        // and are recomputed in place when there were
+        let mut flipped = repaired;
+        for vertex in flipped.vertexes.chunks_exact_mut(6) {
+            vertex[5] = -1.0;
+        }
+        let fixed = repair(flipped, &options);
+        assert_eq!(fixed.vertexes.chunks_exact(6).filter(|vertex| vertex[5] == 1.0).count(), 4);
+
+        // Welding within a tolerance closes the crack between two squares
+        let mut vertexes = square(&[], &[]).vertexes;
+        vertexes.extend_from_slice(&[1.00001, 0.0, 0.0, 2.0, 0.0, 0.0, 2.0, 1.0, 0.0, 1.00001, 1.0, 0.0]);
+        let two = MeshData { vertexes, indexes: Some(vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]), shader: VertexShader::simple(Vec::new()) };
+        let welded = repair(two, &RepairOptions { weld: Some(0.001), ..Default::default() });
+        assert_eq!(welded.vertexes.len(), 6 * 3);
+    }
+
+    #[test]
+    fn import_check() {
+        let broken = square(&[], &[0, 1, 7]);
+        assert!(check_import("broken", broken).is_err());
+        let warned = square(&[3.0, 3.0, 3.0], &[]);
+        assert!(check_import("unused vertex", warned).is_ok());
+    }
+}
@@ -1,9 +1,23 @@
+pub mod asset_paths;
+pub mod asset_watcher;
 pub mod container;
+pub mod embedded;
 pub mod mesh;
+pub mod mesh_cache;
+pub mod mesh_loader;
+pub mod mesh_normals;
+pub mod mesh_simplify;
+pub mod mesh_subdivide;
+pub mod mesh_validate;
+pub mod line_renderer;
 pub mod object;
 pub mod material;
 pub mod resource_file;
 pub mod shader_gen;
 pub mod shader_attribute;
+pub mod shader_log;
 pub mod parser;
+pub mod polygon_mesh;
+pub mod primitives;
+pub mod program;
 pub mod texture;
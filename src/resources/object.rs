@@ -1,13 +1,24 @@
+use glam::DMat4;
 use glow::*;
 
 use crate::resources::container::{Container, ContainerRef};
 use crate::resources::material::Material;
 use crate::resources::mesh::Mesh;
 
+use crate::structures::bounds::{Aabb, BoundingSphere};
 use crate::structures::camera::Camera;
+use crate::structures::light::DirectionalLight;
+use crate::structures::ray::Ray;
+use crate::structures::transform::Transform;
 use crate::util::error::Error;
+use crate::util::log::{self, Subsystem};
+use crate::util::util::split_dvec3;
 
+use super::program;
 use super::shader_gen;
+use super::shader_log::ShaderMessage;
+
+const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 0.6, 0.1, 0.35];
 
 pub struct Object {
     name: String,
@@ -16,106 +27,286 @@ pub struct Object {
     mesh: ContainerRef<Mesh>,
     vertex_shader: Option<NativeShader>,
     fragment_shader: Option<NativeShader>,
+    transform: Transform,
+    visible: bool,
+    parent: Option<String>,
+    /* revision of the material when the program was compiled */
+    material_revision: u64,
+    /* Why the last attempt to build the program failed, the error material is used until one succeeds */
+    error: Option<String>,
+    /* Drawing failed and error says why, the object isn't drawn until its program is rebuilt */
+    render_failed: bool,
+    /* What the compiler said about the last sources, see get_vertex_source */
+    shader_messages: Vec<ShaderMessage>,
+    /* Sources of the last build, generated or custom */
+    vertex_source: String,
+    fragment_source: String,
+    /* (vertex, fragment) edited in the shader editor, used instead of generating them */
+    custom_sources: Option<(String, String)>,
+    reload_requested: bool,
+    /* Bumped when the object moves, is shown or hidden or gets another parent, see Scene::pick */
+    revision: u64,
 }
 
 impl Object {
 
     pub fn create(material: ContainerRef<Material>, mesh: ContainerRef<Mesh>, container: &Container, gl: &glow::Context, name: &str) -> Result<Self, Error> {
-        let mut result = Self {
+        let mut result = Self::without_program(material, mesh, name);
+        unsafe {
+            if let Err(err) = result.load_program(container, gl) {
+                log::error(&format!("Could not build the shaders of {}, using the error material", name), &err);
+                result.error = Some(err.to_string());
+                result.shader_messages = err.get_shader_messages();
+                result.load_error_program(gl)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /* An object that is never drawn, for tests of the scene */
+    #[cfg(test)]
+    pub fn without_gl(material: ContainerRef<Material>, mesh: ContainerRef<Mesh>, name: &str) -> Self {
+        Self::without_program(material, mesh, name)
+    }
+
+    fn without_program(material: ContainerRef<Material>, mesh: ContainerRef<Mesh>, name: &str) -> Self {
+        Self {
             name: String::from(name),
             program: None,
             material,
             mesh,
             vertex_shader: None,
             fragment_shader: None,
-        };
-        unsafe {
-            if let Err(err) = result.load_program(container, gl) {
-                todo!("Error loading object: {:?}", err);
-            }
+            transform: Transform::new(),
+            visible: true,
+            parent: None,
+            material_revision: 0,
+            error: None,
+            render_failed: false,
+            shader_messages: Vec::new(),
+            vertex_source: String::new(),
+            fragment_source: String::new(),
+            custom_sources: None,
+            reload_requested: false,
+            revision: 0,
         }
-        Ok(result)
     }
 
-    pub fn render(&self, gl: &glow::Context, camera: &Camera) {
+    /* world_matrix is the transform of the object combined with its parents */
+    pub fn render(&self, gl: &glow::Context, camera: &Camera, light: &DirectionalLight, world_matrix: &DMat4, highlighted: bool) -> Result<(), Error> {
         unsafe {
             gl.use_program(self.program);
         }
         if let Some(program) = self.program {
-            self.mesh.lock().unwrap().render(gl, camera, &program);
+            unsafe {
+                // Ray marching shaders can't rebuild the camera position from the rebased matrix
+                // so it is passed in object space as two floats (high + low).
+                let (offset_high, offset_low) = split_dvec3(camera.get_local_position(world_matrix));
+                let location = gl.get_uniform_location(program, "camera_offset_high");
+                gl.uniform_3_f32_slice(location.as_ref(), &offset_high.to_array());
+                let location = gl.get_uniform_location(program, "camera_offset_low");
+                gl.uniform_3_f32_slice(location.as_ref(), &offset_low.to_array());
+
+                let highlight = match highlighted {
+                    true => HIGHLIGHT_COLOR,
+                    false => [0.0; 4],
+                };
+                let location = gl.get_uniform_location(program, "highlight_color");
+                gl.uniform_4_f32_slice(location.as_ref(), &highlight);
+
+                let location = gl.get_uniform_location(program, "light_direction");
+                gl.uniform_3_f32_slice(location.as_ref(), &light.get_direction().as_vec3().to_array());
+                let location = gl.get_uniform_location(program, "light_color");
+                gl.uniform_3_f32_slice(location.as_ref(), &light.get_radiance().to_array());
+            }
+            self.material.lock().unwrap().apply_uniforms(gl, &program);
+            let camera_matrix = camera.get_object_matrix(world_matrix);
+            let mesh = self.mesh.lock().unwrap();
+            let screen_size = camera.get_screen_size(&mesh.get_bounding_sphere().transformed(world_matrix));
+            return mesh.get_lod(screen_size).render(gl, &camera_matrix, &program);
         }
+        Ok(())
     }
 
-    unsafe fn load_program(&mut self, container: &Container, gl: &glow::Context) -> Result<(), Error> {
-        let program = gl.create_program().expect("Cannot create program");
+    /* Bounds of the mesh in world space */
+    pub fn get_world_bounds(&self, world_matrix: &DMat4) -> Aabb {
+        self.mesh.lock().unwrap().get_bounds().transformed(world_matrix)
+    }
+    pub fn get_world_bounding_sphere(&self, world_matrix: &DMat4) -> BoundingSphere {
+        self.mesh.lock().unwrap().get_bounding_sphere().transformed(world_matrix)
+    }
 
-        let mesh = self.mesh.lock().unwrap();
-        let material = self.material.lock().unwrap();
+    /* Distance along ray (in world space) to the closest triangle of the object */
+    pub fn intersect_ray(&self, ray: &Ray, world_matrix: &DMat4) -> Option<f64> {
+        let local_ray = ray.transformed(&world_matrix.inverse());
+        self.mesh.lock().unwrap().intersect_ray(&local_ray)
+    }
 
-        let vertex_shader_source = shader_gen::gen_vertex_shader(&mesh);
-        println!("the vertex shader is:\n{}", vertex_shader_source);
+    /* Whether ray (in world space) hits the object before max_distance */
+    pub fn any_hit(&self, ray: &Ray, world_matrix: &DMat4, max_distance: f64) -> bool {
+        let local_ray = ray.transformed(&world_matrix.inverse());
+        self.mesh.lock().unwrap().any_hit(&local_ray, max_distance)
+    }
 
-        let vertex_shader = match Self::get_shader(&vertex_shader_source, glow::VERTEX_SHADER, gl) {
-            Ok(shader) => shader,
-            Err(err) => return Err(err),
-        };
+    /* Whether the material or the sources changed in a way that needs the program to be regenerated */
+    pub fn needs_reload(&self) -> bool {
+        self.reload_requested || self.material.lock().unwrap().get_revision() != self.material_revision
+    }
 
-        let fragment_shader_source = shader_gen::gen_material_shader(&material, &mesh);
-        println!("the fragment shader is:\n{}", fragment_shader_source);
+    /* Rebuilds the program on the next frame, e.g. after a template was edited */
+    pub fn request_reload(&mut self) {
+        self.reload_requested = true;
+    }
 
-        let fragment_shader = match Self::get_shader(&fragment_shader_source, glow::FRAGMENT_SHADER, gl) {
-            Ok(shader) => shader,
-            Err(err) => return Err(err),
-        };
+    /* Regenerates the program. The old program is kept if the new one fails to compile. */
+    pub fn reload_program(&mut self, container: &Container, gl: &glow::Context) -> Result<(), Error> {
+        let old_program = (self.program.take(), self.vertex_shader.take(), self.fragment_shader.take());
+        self.reload_requested = false;
+        let result = unsafe { self.load_program(container, gl) };
+        match &result {
+            Ok(()) => {
+                program::delete_program(gl, old_program.0, old_program.1, old_program.2);
+                self.error = None;
+                self.render_failed = false;
+                self.shader_messages.clear();
+            }
+            Err(err) => {
+                (self.program, self.vertex_shader, self.fragment_shader) = old_program;
+                // Don't try again every frame, wait for the next change instead
+                self.material_revision = self.material.lock().unwrap().get_revision();
+                self.error = Some(err.to_string());
+                self.shader_messages = err.get_shader_messages();
+            }
+        }
+        result
+    }
 
+    unsafe fn load_program(&mut self, _container: &Container, gl: &glow::Context) -> Result<(), Error> {
+        let mesh = self.mesh.lock().unwrap();
+        let material = self.material.lock().unwrap();
+        let material_revision = material.get_revision();
 
-        gl.attach_shader(program, vertex_shader);
-        gl.attach_shader(program, fragment_shader);
+        let (vertex_shader_source, fragment_shader_source) = match &self.custom_sources {
+            Some(sources) => sources.clone(),
+            None => (shader_gen::gen_vertex_shader(&mesh)?, shader_gen::gen_material_shader(&material, &mesh)?),
+        };
+        log::debug(Subsystem::Shader, format!("vertex shader for {}:\n{}", self.name, vertex_shader_source));
+        log::debug(Subsystem::Shader, format!("fragment shader for {}:\n{}", self.name, fragment_shader_source));
+        self.vertex_source = vertex_shader_source.clone();
+        self.fragment_source = fragment_shader_source.clone();
 
-        gl.link_program(program);
+        let (program, vertex_shader, fragment_shader) = program::link_program(&vertex_shader_source, &fragment_shader_source, gl)?;
+        self.vertex_shader = Some(vertex_shader);
+        self.fragment_shader = Some(fragment_shader);
+        self.program = Some(program);
+        self.material_revision = material_revision;
 
-        if !gl.get_program_link_status(program) {
-            return Err(Error::invalid_gl_program(gl.get_program_info_log(program)));
-        }
+        Ok(())
+    }
 
+    /* The magenta checker used when the real shaders can't be built */
+    unsafe fn load_error_program(&mut self, gl: &glow::Context) -> Result<(), Error> {
+        let vertex_shader_source = shader_gen::gen_error_vertex_shader(&self.mesh.lock().unwrap());
+        let fragment_shader_source = shader_gen::gen_error_fragment_shader();
+        let (program, vertex_shader, fragment_shader) = program::link_program(&vertex_shader_source, &fragment_shader_source, gl)?;
         self.vertex_shader = Some(vertex_shader);
         self.fragment_shader = Some(fragment_shader);
         self.program = Some(program);
-
+        self.material_revision = self.material.lock().unwrap().get_revision();
         Ok(())
+    }
 
+    /* Why the shaders of the object could not be built or it could not be drawn, if they couldn't */
+    pub fn get_error(&self) -> Option<&str> {
+        self.error.as_deref()
     }
 
-    unsafe fn get_shader(
-        shader_source: &str,
-        shader_type: u32,
-        gl: &glow::Context)-> Result<NativeShader, Error>
-    {
-        let shader = gl.create_shader(shader_type)
-            .expect("Cannot create shader");
-        gl.shader_source(shader, shader_source);
-        gl.compile_shader(shader);
-        if !gl.get_shader_compile_status(shader) {
-            let info = gl.get_shader_info_log(shader);
-            return Err(Error::invalid_shader_source(info));
+    /* Keeps the object from being drawn until its program is rebuilt, so the error isn't hit every frame */
+    pub fn set_render_error(&mut self, err: &Error) {
+        self.error = Some(err.to_string());
+        self.render_failed = true;
+    }
+    pub fn is_render_failed(&self) -> bool {
+        self.render_failed
+    }
+
+    pub fn get_shader_messages(&self) -> &Vec<ShaderMessage> {
+        &self.shader_messages
+    }
+
+    /* Sources the program was last built from, even if building it failed */
+    pub fn get_vertex_source(&self) -> &str {
+        &self.vertex_source
+    }
+    pub fn get_fragment_source(&self) -> &str {
+        &self.fragment_source
+    }
+
+    pub fn has_custom_sources(&self) -> bool {
+        self.custom_sources.is_some()
+    }
+    /* (vertex, fragment) to build the program from, None goes back to generating them */
+    pub fn set_custom_sources(&mut self, sources: Option<(String, String)>) {
+        if let Some((vertex_source, fragment_source)) = &sources {
+            self.vertex_source = vertex_source.clone();
+            self.fragment_source = fragment_source.clone();
         }
-        Ok(shader)
+        self.custom_sources = sources;
+        self.reload_requested = true;
     }
 
     pub fn get_name(&self) -> &str { &self.name }
+    pub fn set_name(&mut self, name: &str) {
+        self.name = String::from(name);
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+    pub fn get_mut_visible(&mut self) -> &mut bool {
+        &mut self.visible
+    }
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        self.revision += 1;
+    }
+
+    /* Name of the object this one is attached to */
+    pub fn get_parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+    pub fn set_parent(&mut self, parent: Option<&str>) {
+        self.parent = parent.map(String::from);
+        self.revision += 1;
+    }
+
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+    /* Counts as a change even if nothing is written, prefer set_transform */
+    pub fn get_mut_transform(&mut self) -> &mut Transform {
+        self.revision += 1;
+        &mut self.transform
+    }
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.revision += 1;
+    }
+
+    pub fn get_revision(&self) -> u64 {
+        self.revision
+    }
 
 
+    pub fn get_mesh(&self) -> ContainerRef<Mesh> {
+        self.mesh.clone()
+    }
+
+    pub fn get_material(&self) -> ContainerRef<Material> {
+        self.material.clone()
+    }
+
     pub fn destroy(&self, gl: &glow::Context) {
-        unsafe {
-            if let Some(program) = self.program {
-                gl.delete_program(program);
-            }
-            if let Some(vertex_shader) = self.vertex_shader {
-                gl.delete_shader(vertex_shader);
-            }
-            if let Some(fragment_shader) = self.fragment_shader {
-                gl.delete_shader(fragment_shader);
-            }
-        }
+        program::delete_program(gl, self.program, self.vertex_shader, self.fragment_shader);
     }
 }
@@ -1,8 +1,15 @@
-use std::{path::PathBuf, mem::size_of};
+use std::collections::HashMap;
+use std::mem::size_of;
 
 use crate::util::error::Error;
+use crate::util::log::{self, Subsystem};
 
-use super::{mesh::Mesh, resource_file::load_file_raw_vec};
+use super::mesh::{Mesh, MeshData, VertexShader};
+use super::mesh_loader::LoadProgress;
+use super::polygon_mesh::PolygonMesh;
+
+/* Faces parsed between checks for cancellation */
+const PROGRESS_INTERVAL: u32 = 4096;
 
 type ParserIterator<'a> = std::slice::Iter<'a, u8>;
 
@@ -84,7 +91,7 @@ impl<'a> TextParser<'a> {
         }
         let start = self.get_ptr();
         let mut end = self.get_ptr();
-        while !self.peek_char().unwrap_or('_').is_ascii_whitespace() {
+        while !self.peek_char().unwrap_or(' ').is_ascii_whitespace() {
             _ = self.next_char();
             end = self.get_ptr();
         }
@@ -234,8 +241,8 @@ impl<'a> TextParser<'a> {
     pub fn get_remaining_bytes(&self) -> usize {
         self.iterator.size_hint().0
     }
-    pub fn print_remaining_bytes(&self) {
-        println!("remaining size is {}", self.get_remaining_bytes());
+    pub fn log_remaining_bytes(&self) {
+        log::debug(Subsystem::Parser, format!("remaining size is {}", self.get_remaining_bytes()));
     }
 }
 
@@ -268,26 +275,33 @@ impl<'a> RawParser<'a> {
         let ptr = self.get_ptr();
         if let Some(_) = self.iterator.nth(3) {
             unsafe {
+                // Nothing lines the data up, it starts right after the header
                 let ptr = ptr as *const u32;
-                Ok(*ptr)
+                Ok(ptr.read_unaligned())
             }
         } else {
             Err(Error::parser_end_of_file())
         }
     }
-    pub fn next_slice<T>(&mut self, size: usize) -> Result<&'a [T], Error> {
+    /* Copies the values out since the data may not be aligned for T */
+    pub fn next_vec<T: Copy>(&mut self, size: usize) -> Result<Vec<T>, Error> {
         let ptr = self.get_ptr() as *const T;
-        if self.iterator.nth(size * size_of::<T>() - 1).is_none() {
-            return Err(Error::parser_end_of_file());
+        if size == 0 {
+            return Ok(Vec::new());
         }
-        let result = unsafe { std::slice::from_raw_parts(ptr, size) };
+        // Sizes come from the file, so they can be anything
+        match size.checked_mul(size_of::<T>()) {
+            Some(bytes) if bytes <= self.get_remaining_bytes() => self.iterator.nth(bytes - 1),
+            _ => return Err(Error::parser_end_of_file()),
+        };
+        let result = (0..size).map(|i| unsafe { ptr.add(i).read_unaligned() }).collect();
         return Ok(result);
     }
     pub fn get_remaining_bytes(&self) -> usize {
         self.iterator.size_hint().0
     }
-    pub fn print_remaining_bytes(&self) {
-        println!("remaining size is {}", self.get_remaining_bytes());
+    pub fn log_remaining_bytes(&self) {
+        log::debug(Subsystem::Parser, format!("remaining size is {}", self.get_remaining_bytes()));
     }
 }
 impl<'a> From<&'a TextParser<'a>> for RawParser<'a> {
@@ -298,10 +312,33 @@ impl<'a> From<&'a TextParser<'a>> for RawParser<'a> {
     }
 }
 
-pub fn parse_mesh(dir: PathBuf, gl: &glow::Context, name: &str) -> Result<Mesh, Error> {
-    let mut data = load_file_raw_vec(dir)?;
+/* data is the whole ply file, see resource_file::load_mesh */
+pub fn parse_mesh(data: Vec<u8>, gl: &glow::Context, name: &str) -> Result<Mesh, Error> {
+    log::debug(Subsystem::Parser, format!("loading mesh {} ({} bytes)", name, data.len()));
+    Mesh::from_data(parse_mesh_data(data, &LoadProgress::new())?, gl, name)
+}
+
+/*
+ * @brief
+ * The part of parse_mesh that doesn't need gl, so it can run on a worker thread.
+ * Reports how far it got through progress and stops with an error once it is cancelled.
+ * Faces with more than 3 corners are split into triangles.
+ */
+pub fn parse_mesh_data(data: Vec<u8>, progress: &LoadProgress) -> Result<MeshData, Error> {
+    Ok(parse_polygon_mesh(data, progress)?.triangulate())
+}
+
+/* A ply or, for anything not starting like a ply, an obj file with its faces kept whole */
+pub fn parse_polygon_mesh(data: Vec<u8>, progress: &LoadProgress) -> Result<PolygonMesh, Error> {
+    match data.starts_with(b"ply") {
+        true => parse_ply_polygons(data, progress),
+        false => parse_obj_polygons(data, progress),
+    }
+}
+
+/* Binary little endian plys with float positions, optionally float normals, and faces of any size */
+pub fn parse_ply_polygons(mut data: Vec<u8>, progress: &LoadProgress) -> Result<PolygonMesh, Error> {
     let mut parser = TextParser::create_from_vec(&mut data);
-    parser.print_remaining_bytes();
 
     parser.require_line("ply")?;
     parser.require_line("format binary_little_endian 1.0")?;
@@ -314,50 +351,169 @@ pub fn parse_mesh(dir: PathBuf, gl: &glow::Context, name: &str) -> Result<Mesh,
     parser.require_line("property float x")?;
     parser.require_line("property float y")?;
     parser.require_line("property float z")?;
-    parser.require_line("property float nx")?;
-    parser.require_line("property float ny")?;
-    parser.require_line("property float nz")?;
+    let has_normals = parser.require_line("property float nx").is_ok();
+    if has_normals {
+        parser.require_line("property float ny")?;
+        parser.require_line("property float nz")?;
+    }
 
     parser.require_words(vec!["element", "face"])?;
     let face_count = parser.next_ascii_uint32()?;
 
-    parser.require_line("property list uchar uint vertex_indices")?;
+    // Some exporters write the indexes as int, they are read the same
+    if parser.require_line("property list uchar int vertex_indices").is_err() {
+        parser.require_line("property list uchar uint vertex_indices")?;
+    }
     parser.require_line("end_header")?;
     
 
     let mut parser = RawParser::from(&parser);
 
-
-    let vertex_data_count = (vertex_count * 6) as usize;
-    let vertex_data: &[f32] = parser.next_slice(vertex_data_count)?;
-
-    for _ in 0..1000 {
-        //println!("byte {}", parser.next_raw_u8().unwrap_or(255));
+    let (vertex_size, shader) = match has_normals {
+        true => (6, VertexShader::default_simple_with_normal()),
+        false => (3, VertexShader::simple(Vec::new())),
+    };
+    let vertex_data_count = vertex_count as usize * vertex_size;
+    let vertex_data: Vec<f32> = parser.next_vec(vertex_data_count)?;
+
+    // The header count is not trusted further than the bytes left could hold, a face is at least
+    // a count and three corners
+    let most_faces = parser.get_remaining_bytes() / (1 + 3 * size_of::<u32>());
+    let mut faces: Vec<Vec<u32>> = Vec::with_capacity((face_count as usize).min(most_faces));
+    for face in 0..face_count {
+        if face % PROGRESS_INTERVAL == 0 {
+            if progress.is_cancelled() {
+                return Err(Error::load_cancelled());
+            }
+            progress.set_fraction(face as f32 / face_count as f32);
+        }
+        let count = parser.next_raw_u8()?;
+        if count < 3 {
+            return Err(Error::ply_parser(format!("Face {} has only {} corners", face, count)));
+        }
+        let corners: Vec<u32> = parser.next_vec(count as usize)?;
+        if let Some(corner) = corners.iter().find(|corner| **corner >= vertex_count) {
+            return Err(Error::ply_parser(format!("Face {} uses vertex {} of {}", face, corner, vertex_count)));
+        }
+        faces.push(corners);
     }
 
+    progress.set_fraction(1.0);
 
-    let face_data_count = (face_count * 3) as usize;
-    let mut face_data: Vec<u32> = Vec::with_capacity(face_data_count);
-    for _ in 0..face_count {
-        let count = parser.next_raw_u8()?;
-        if count != 3 {
-            return Err(Error::ply_parser(format!("Vertex count {} for face is not implimented", count)));
+    Ok(PolygonMesh {
+        vertexes: vertex_data,
+        shader,
+        faces,
+    })
+}
+
+/*
+ * @brief
+ * Reads the positions, normals and faces of an obj, everything else (texture coordinates,
+ * groups, materials) is skipped. Normals are only kept when every corner of every face has one.
+ */
+pub fn parse_obj_polygons(mut data: Vec<u8>, progress: &LoadProgress) -> Result<PolygonMesh, Error> {
+    let total_bytes = data.len().max(1);
+    let mut parser = TextParser::create_from_vec(&mut data);
+    let mut positions: Vec<f32> = Vec::new();
+    let mut normals: Vec<f32> = Vec::new();
+    let mut faces: Vec<Vec<(u32, Option<u32>)>> = Vec::new();
+    let mut line_number = 0;
+    while let Ok(line) = parser.next_line() {
+        line_number += 1;
+        if line_number % PROGRESS_INTERVAL as usize == 0 {
+            if progress.is_cancelled() {
+                return Err(Error::load_cancelled());
+            }
+            progress.set_fraction(1.0 - parser.get_remaining_bytes() as f32 / total_bytes as f32);
         }
-        for _ in 0..count {
-            let next_face_thing = parser.next_raw_u32()?;
-            face_data.push(next_face_thing);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => positions.extend_from_slice(&parse_obj_floats(words, line_number)?),
+            Some("vn") => normals.extend_from_slice(&parse_obj_floats(words, line_number)?),
+            Some("f") => {
+                let corners = words
+                    .map(|word| parse_obj_corner(word, positions.len() / 3, normals.len() / 3, line_number))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                if corners.len() < 3 {
+                    return Err(Error::obj_parser(format!("Face has only {} corners", corners.len()), line_number));
+                }
+                faces.push(corners);
+            }
+            _ => (),
         }
     }
 
-    //println!("face indexes: {:?}", face_data);
-    //println!("vertex data: {:?}", vertex_data);
+    progress.set_fraction(1.0);
+
+    let has_normals = faces.iter().flatten().all(|(_, normal)| normal.is_some());
+    if !has_normals {
+        return Ok(PolygonMesh {
+            vertexes: positions,
+            shader: VertexShader::simple(Vec::new()),
+            faces: faces.into_iter().map(|face| face.into_iter().map(|(position, _)| position).collect()).collect(),
+        });
+    }
+    // A position used with different normals becomes a vertex for each
+    let mut vertexes = Vec::new();
+    let mut created: HashMap<(u32, u32), u32> = HashMap::new();
+    let faces = faces
+        .into_iter()
+        .map(|face| face.into_iter().map(|(position, normal)| {
+            let normal = normal.unwrap_or(0);
+            *created.entry((position, normal)).or_insert_with(|| {
+                let (position, normal) = (position as usize * 3, normal as usize * 3);
+                vertexes.extend_from_slice(&positions[position..position + 3]);
+                vertexes.extend_from_slice(&normals[normal..normal + 3]);
+                (vertexes.len() / 6 - 1) as u32
+            })
+        }).collect())
+        .collect();
+    Ok(PolygonMesh {
+        vertexes,
+        shader: VertexShader::default_simple_with_normal(),
+        faces,
+    })
+}
+
+/* The first 3 numbers of words, a 4th w of positions is ignored */
+fn parse_obj_floats<'a>(mut words: impl Iterator<Item = &'a str>, line_number: usize) -> Result<[f32; 3], Error> {
+    let mut result = [0.0; 3];
+    for value in result.iter_mut() {
+        let word = words.next().ok_or_else(|| Error::obj_parser(String::from("Expected 3 numbers"), line_number))?;
+        *value = word.parse().map_err(|_| Error::obj_parser(format!("{} is not a number", word), line_number))?;
+    }
+    Ok(result)
+}
 
-    return Mesh::create_indexed(vertex_data.to_vec(), face_data, gl, super::mesh::VertexShader::default_simple_with_normal(), name);
+/* Position and normal index of a corner like 3, 3/1, 3//2 or 3/1/2, made 0 based */
+fn parse_obj_corner(word: &str, position_count: usize, normal_count: usize, line_number: usize) -> Result<(u32, Option<u32>), Error> {
+    let mut parts = word.split('/');
+    let position = resolve_obj_index(parts.next().unwrap_or(""), position_count, line_number)?;
+    let _texture_coordinate = parts.next();
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => Some(resolve_obj_index(normal, normal_count, line_number)?),
+        _ => None,
+    };
+    Ok((position, normal))
+}
+
+/* Indexes start at 1, negative ones count back from the last element read so far */
+fn resolve_obj_index(word: &str, count: usize, line_number: usize) -> Result<u32, Error> {
+    let index: i64 = word.parse().map_err(|_| Error::obj_parser(format!("{} is not an index", word), line_number))?;
+    let resolved = match index {
+        index if index > 0 => index - 1,
+        index => count as i64 + index,
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(Error::obj_parser(format!("Index {} is out of range", index), line_number));
+    }
+    Ok(resolved as u32)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::TextParser;
+    use super::*;
 
     #[test]
     fn get_line() {
@@ -450,4 +606,57 @@ mod tests {
         parser.skip_single_line_comment("#");
         assert_eq!(parser.peek_line(), Ok("//rust version"));
     }
+
+    #[test]
+    fn ply_polygons() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 5\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 2\nproperty list uchar int vertex_indices\nend_header\n".to_vec();
+        for value in [0.0_f32, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 2.0, 0.0, 0.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for face in [vec![0_u32, 1, 2, 3], vec![1, 4, 2]] {
+            data.push(face.len() as u8);
+            for index in face {
+                data.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+        let polygons = parse_polygon_mesh(data.clone(), &LoadProgress::new()).unwrap();
+        assert_eq!(polygons.faces, vec![vec![0, 1, 2, 3], vec![1, 4, 2]]);
+        assert_eq!(polygons.shader.get_vertex_size(), 3);
+        let triangles = parse_mesh_data(data, &LoadProgress::new()).unwrap();
+        assert_eq!(triangles.indexes, Some(vec![0, 1, 2, 0, 2, 3, 1, 4, 2]));
+    }
+
+    #[test]
+    fn ply_bogus_face_count() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+            element face 4000000000\nproperty list uchar int vertex_indices\nend_header\n".to_vec();
+        for value in [0.0_f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.push(3);
+        for index in [0_u32, 1, 2] {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        assert!(parse_polygon_mesh(data, &LoadProgress::new()).is_err());
+    }
+
+    #[test]
+    fn obj_polygons() {
+        let text = "# a quad and a triangle\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0 1.0\nvt 0 0\nvn 0 0 1\n\
+            o square\nf 1//1 2//1 3//1 4//1\nf -4/1/-1 -2/1/1 -1//1\n";
+        let polygons = parse_polygon_mesh(text.as_bytes().to_vec(), &LoadProgress::new()).unwrap();
+        assert_eq!(polygons.faces, vec![vec![0, 1, 2, 3], vec![0, 2, 3]]);
+        assert!(polygons.shader.get_normal().is_some());
+        assert_eq!(polygons.get_position(3), glam::Vec3::Y);
+        assert_eq!(&polygons.vertexes[3..6], &[0.0, 0.0, 1.0]);
+
+        // Without normals on every corner only the positions are kept
+        let polygons = parse_obj_polygons(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".to_vec(), &LoadProgress::new()).unwrap();
+        assert_eq!(polygons.faces, vec![vec![0, 1, 2]]);
+        assert_eq!(polygons.shader.get_vertex_size(), 3);
+
+        assert!(parse_obj_polygons(b"v 0 0 0\nf 1 2 3\n".to_vec(), &LoadProgress::new()).is_err());
+        assert!(parse_obj_polygons(b"v 0 0 0\nv 1 0 0\nf 1 2\n".to_vec(), &LoadProgress::new()).is_err());
+    }
 }
@@ -0,0 +1,81 @@
+/* meshes as files describe them, before their faces are split into triangles */
+
+use glam::Vec3;
+
+use crate::util::error::Error;
+
+use super::mesh::{MeshData, VertexShader};
+use super::mesh_cache::load_polygon_mesh;
+
+/*
+ * @brief
+ * Vertexes laid out as shader describes, the position being the first 3 floats of each,
+ * and faces with any number of corners. Subdivision needs the quads of a file kept whole.
+ */
+pub struct PolygonMesh {
+    pub vertexes: Vec<f32>,
+    pub shader: VertexShader,
+    /* Indexes into vertexes for the corners of every face, in order around it */
+    pub faces: Vec<Vec<u32>>,
+}
+
+impl PolygonMesh {
+    /* Vertexes with only a position */
+    pub fn from_positions(positions: &[Vec3], faces: Vec<Vec<u32>>) -> Self {
+        Self {
+            vertexes: positions.iter().flat_map(|position| position.to_array()).collect(),
+            shader: VertexShader::simple(Vec::new()),
+            faces,
+        }
+    }
+
+    pub fn get_vertex_count(&self) -> usize {
+        self.vertexes.len() / self.shader.get_vertex_size()
+    }
+
+    pub fn get_position(&self, index: usize) -> Vec3 {
+        let start = index * self.shader.get_vertex_size();
+        Vec3::from_slice(&self.vertexes[start..start + 3])
+    }
+
+    pub fn get_positions(&self) -> Vec<Vec3> {
+        (0..self.get_vertex_count()).map(|index| self.get_position(index)).collect()
+    }
+
+    pub fn is_triangles(&self) -> bool {
+        self.faces.iter().all(|face| face.len() == 3)
+    }
+
+    /* Splits every face into a fan of triangles around its first corner, faces with less than 3 corners are dropped */
+    pub fn triangulate(self) -> MeshData {
+        let mut indexes = Vec::with_capacity(self.faces.len() * 3);
+        for face in &self.faces {
+            for corner in 2..face.len() {
+                indexes.extend_from_slice(&[face[0], face[corner - 1], face[corner]]);
+            }
+        }
+        MeshData {
+            vertexes: self.vertexes,
+            indexes: Some(indexes),
+            shader: self.shader,
+        }
+    }
+}
+
+/* Where the faces of a mesh come from, files are read again so their quads stay whole */
+pub enum PolygonSource {
+    /* A file in the meshes asset directory */
+    File(String),
+    /* Faces taken from the triangles of a mesh */
+    Mesh(Box<PolygonMesh>),
+}
+
+impl PolygonSource {
+    /* Reads the file if there is one, so it can be done on a worker thread */
+    pub fn load(self) -> Result<PolygonMesh, Error> {
+        match self {
+            PolygonSource::File(file_name) => load_polygon_mesh(&file_name),
+            PolygonSource::Mesh(mesh) => Ok(*mesh),
+        }
+    }
+}
@@ -0,0 +1,413 @@
+/* generated meshes with normals and texture coordinates, laid out for VertexShader::default_simple_with_normal_uv */
+
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use glam::{Vec2, Vec3};
+
+use super::mesh::{MeshData, VertexShader};
+
+/*
+ * @brief
+ * The meshes that can be added to a scene without a file. All are centered on the origin with y up
+ * and about 2 units across, objects scale them. Segments go around the y axis, rings from bottom to top.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Primitive {
+    UvSphere { segments: u32, rings: u32 },
+    /* An icosahedron with every triangle split into 4, subdivisions times */
+    Icosphere { subdivisions: u32 },
+    Cylinder { segments: u32 },
+    Cone { segments: u32 },
+    /* Ring of radius 1 around the y axis */
+    Torus { segments: u32, tube_segments: u32, tube_radius: f32 },
+    /* In the xz plane facing up */
+    Plane { segments: u32 },
+    /* A cylinder of length with half spheres of radius 1 on its ends, rings are per half sphere */
+    Capsule { segments: u32, rings: u32, length: f32 },
+}
+
+impl Primitive {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::UvSphere { .. } => "uv_sphere",
+            Self::Icosphere { .. } => "icosphere",
+            Self::Cylinder { .. } => "cylinder",
+            Self::Cone { .. } => "cone",
+            Self::Torus { .. } => "torus",
+            Self::Plane { .. } => "plane",
+            Self::Capsule { .. } => "capsule",
+        }
+    }
+
+    pub fn generate(&self) -> MeshData {
+        match *self {
+            Self::UvSphere { segments, rings } => uv_sphere(segments, rings),
+            Self::Icosphere { subdivisions } => icosphere(subdivisions),
+            Self::Cylinder { segments } => cylinder(segments),
+            Self::Cone { segments } => cone(segments),
+            Self::Torus { segments, tube_segments, tube_radius } => torus(segments, tube_segments, tube_radius),
+            Self::Plane { segments } => plane(segments),
+            Self::Capsule { segments, rings, length } => capsule(segments, rings, length),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    vertexes: Vec<f32>,
+    indexes: Vec<u32>,
+}
+
+impl Builder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3, uv: Vec2) -> u32 {
+        self.vertexes.extend_from_slice(&position.to_array());
+        self.vertexes.extend_from_slice(&normal.to_array());
+        self.vertexes.extend_from_slice(&uv.to_array());
+        (self.vertexes.len() / 8 - 1) as u32
+    }
+
+    fn get_position(&self, index: u32) -> Vec3 {
+        Vec3::from_slice(&self.vertexes[index as usize * 8..])
+    }
+
+    /* Counter clockwise seen from the front. Triangles with 2 corners in the same place, like at poles, are left out */
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let [pa, pb, pc] = [a, b, c].map(|index| self.get_position(index));
+        if pa == pb || pb == pc || pc == pa {
+            return;
+        }
+        self.indexes.extend_from_slice(&[a, b, c]);
+    }
+
+    /*
+     * Turns a profile in the (distance from the y axis, height) plane around the y axis.
+     * The profile goes up the outside of the surface, each point being a row of vertexes.
+     */
+    fn revolve(&mut self, profile: &[ProfilePoint], segments: u32) {
+        let start = self.vertexes.len() as u32 / 8;
+        for point in profile {
+            for segment in 0..=segments {
+                let around = around(segment, segments);
+                let position = Vec3::new(around.x * point.radius, point.y, around.y * point.radius);
+                let normal = Vec3::new(around.x * point.normal.x, point.normal.y, around.y * point.normal.x);
+                self.vertex(position, normal.normalize(), Vec2::new(segment as f32 / segments as f32, point.v));
+            }
+        }
+        let row = segments + 1;
+        for ring in 0..profile.len() as u32 - 1 {
+            for segment in 0..segments {
+                let a = start + ring * row + segment;
+                let d = a + row;
+                self.triangle(a, a + 1, d + 1);
+                self.triangle(a, d + 1, d);
+            }
+        }
+    }
+
+    /* A flat disk closing a revolved surface at height y, facing up or down */
+    fn cap(&mut self, y: f32, segments: u32, up: bool) {
+        let normal = if up { Vec3::Y } else { Vec3::NEG_Y };
+        let center = self.vertex(Vec3::new(0.0, y, 0.0), normal, Vec2::splat(0.5));
+        for segment in 0..=segments {
+            let around = around(segment, segments);
+            // Seen from the side it faces, the texture is upright either way
+            let uv = Vec2::new(0.5 + around.x * 0.5, 0.5 + if up { -around.y } else { around.y } * 0.5);
+            self.vertex(Vec3::new(around.x, y, around.y), normal, uv);
+        }
+        for segment in 0..segments {
+            let (a, b) = (center + 1 + segment, center + 2 + segment);
+            match up {
+                true => self.triangle(center, a, b),
+                false => self.triangle(center, b, a),
+            }
+        }
+    }
+
+    fn finish(self) -> MeshData {
+        MeshData {
+            vertexes: self.vertexes,
+            indexes: Some(self.indexes),
+            shader: VertexShader::default_simple_with_normal_uv(),
+        }
+    }
+}
+
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    /* In the (distance from the y axis, height) plane */
+    normal: Vec2,
+    v: f32,
+}
+
+/* (x, z) of the segment'th of segments directions around the y axis, counter clockwise seen from above. The last equals the first exactly */
+fn around(segment: u32, segments: u32) -> Vec2 {
+    let angle = match segment == segments {
+        true => 0.0,
+        false => segment as f32 / segments as f32 * TAU,
+    };
+    Vec2::new(angle.cos(), -angle.sin())
+}
+
+/* Direction at latitude in the (distance from the y axis, height) plane, exactly on the axis at the poles so they close */
+fn latitude_normal(latitude: f32) -> Vec2 {
+    match latitude {
+        latitude if latitude <= -FRAC_PI_2 => Vec2::NEG_Y,
+        latitude if latitude >= FRAC_PI_2 => Vec2::Y,
+        latitude => Vec2::new(latitude.cos(), latitude.sin()),
+    }
+}
+
+pub fn uv_sphere(segments: u32, rings: u32) -> MeshData {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let profile: Vec<ProfilePoint> = (0..=rings)
+        .map(|ring| {
+            let v = ring as f32 / rings as f32;
+            let normal = latitude_normal(-FRAC_PI_2 + v * PI);
+            ProfilePoint { radius: normal.x, y: normal.y, normal, v }
+        })
+        .collect();
+    let mut builder = Builder::default();
+    builder.revolve(&profile, segments);
+    builder.finish()
+}
+
+/*
+ * Every vertex is about as far from its neighbours as any other, unlike on a uv sphere.
+ * Triangles across the texture seam get their own vertexes with u past 1 so the texture doesn't run backwards.
+ */
+pub fn icosphere(subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+    ].iter().map(|(x, y, z)| Vec3::new(*x, *y, *z).normalize()).collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+    for _ in 0..subdivisions.min(7) {
+        let mut middles: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut middle = |a: u32, b: u32, positions: &mut Vec<Vec3>| {
+            *middles.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push((positions[a as usize] + positions[b as usize]).normalize());
+                positions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|[a, b, c]| {
+                let ab = middle(*a, *b, &mut positions);
+                let bc = middle(*b, *c, &mut positions);
+                let ca = middle(*c, *a, &mut positions);
+                [[*a, ab, ca], [ab, *b, bc], [ca, bc, *c], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut builder = Builder::default();
+    let mut created: HashMap<(u32, [u32; 2]), u32> = HashMap::new();
+    for triangle in triangles {
+        let corners = triangle.map(|index| positions[index as usize]);
+        let mut uvs = corners.map(|position| Vec2::new(
+            (-position.z).atan2(position.x).rem_euclid(TAU) / TAU,
+            position.y.clamp(-1.0, 1.0).asin() / PI + 0.5,
+        ));
+        let (min_u, max_u) = uvs.iter().fold((f32::MAX, f32::MIN), |(min, max), uv| (min.min(uv.x), max.max(uv.x)));
+        if max_u - min_u > 0.5 {
+            for uv in uvs.iter_mut().filter(|uv| uv.x < 0.5) {
+                uv.x += 1.0;
+            }
+        }
+        let indexes: Vec<u32> = (0..3).map(|corner| {
+            *created.entry((triangle[corner], uvs[corner].to_array().map(f32::to_bits))).or_insert_with(|| {
+                builder.vertex(corners[corner], corners[corner], uvs[corner])
+            })
+        }).collect();
+        builder.triangle(indexes[0], indexes[1], indexes[2]);
+    }
+    builder.finish()
+}
+
+/* Radius 1 and height 2, closed on both ends */
+pub fn cylinder(segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let mut builder = Builder::default();
+    builder.revolve(&[
+        ProfilePoint { radius: 1.0, y: -1.0, normal: Vec2::X, v: 0.0 },
+        ProfilePoint { radius: 1.0, y: 1.0, normal: Vec2::X, v: 1.0 },
+    ], segments);
+    builder.cap(-1.0, segments, false);
+    builder.cap(1.0, segments, true);
+    builder.finish()
+}
+
+/* Base of radius 1 at y = -1 and the tip at y = 1 */
+pub fn cone(segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let normal = Vec2::new(2.0, 1.0).normalize();
+    let mut builder = Builder::default();
+    builder.revolve(&[
+        ProfilePoint { radius: 1.0, y: -1.0, normal, v: 0.0 },
+        ProfilePoint { radius: 0.0, y: 1.0, normal, v: 1.0 },
+    ], segments);
+    builder.cap(-1.0, segments, false);
+    builder.finish()
+}
+
+pub fn torus(segments: u32, tube_segments: u32, tube_radius: f32) -> MeshData {
+    let (segments, tube_segments) = (segments.max(3), tube_segments.max(3));
+    let tube_radius = tube_radius.clamp(0.01, 0.99);
+    // Starts on the outside of the ring and goes up over the top
+    let profile: Vec<ProfilePoint> = (0..=tube_segments)
+        .map(|ring| {
+            let direction = around(ring, tube_segments) * Vec2::new(1.0, -1.0);
+            ProfilePoint {
+                radius: 1.0 + direction.x * tube_radius,
+                y: direction.y * tube_radius,
+                normal: direction,
+                v: ring as f32 / tube_segments as f32,
+            }
+        })
+        .collect();
+    let mut builder = Builder::default();
+    builder.revolve(&profile, segments);
+    builder.finish()
+}
+
+/* 2 by 2 with segments by segments quads, v goes towards -z */
+pub fn plane(segments: u32) -> MeshData {
+    let segments = segments.max(1);
+    let mut builder = Builder::default();
+    for row in 0..=segments {
+        for column in 0..=segments {
+            let uv = Vec2::new(column as f32, row as f32) / segments as f32;
+            builder.vertex(Vec3::new(uv.x * 2.0 - 1.0, 0.0, 1.0 - uv.y * 2.0), Vec3::Y, uv);
+        }
+    }
+    for row in 0..segments {
+        for column in 0..segments {
+            let a = row * (segments + 1) + column;
+            let d = a + segments + 1;
+            builder.triangle(a, a + 1, d + 1);
+            builder.triangle(a, d + 1, d);
+        }
+    }
+    builder.finish()
+}
+
+pub fn capsule(segments: u32, rings: u32, length: f32) -> MeshData {
+    let (segments, rings) = (segments.max(3), rings.max(1));
+    let length = length.max(0.0);
+    // v follows the distance along the profile so the texture isn't stretched on the straight part
+    let total = PI + length;
+    let mut profile = Vec::new();
+    for (offset, start, arc_start) in [(-length * 0.5, -FRAC_PI_2, 0.0), (length * 0.5, 0.0, FRAC_PI_2 + length)] {
+        for ring in 0..=rings {
+            let along = ring as f32 / rings as f32 * FRAC_PI_2;
+            let normal = latitude_normal(start + along);
+            profile.push(ProfilePoint { radius: normal.x, y: normal.y + offset, normal, v: (arc_start + along) / total });
+        }
+    }
+    let mut builder = Builder::default();
+    builder.revolve(&profile, segments);
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::is_closed;
+
+    fn all() -> Vec<Primitive> {
+        vec![
+            Primitive::UvSphere { segments: 32, rings: 16 },
+            Primitive::Icosphere { subdivisions: 3 },
+            Primitive::Cylinder { segments: 32 },
+            Primitive::Cone { segments: 32 },
+            Primitive::Torus { segments: 32, tube_segments: 16, tube_radius: 0.25 },
+            Primitive::Plane { segments: 4 },
+            Primitive::Capsule { segments: 32, rings: 8, length: 1.0 },
+        ]
+    }
+
+    fn triangles(data: &MeshData) -> Vec<[Vec3; 3]> {
+        let position = |index: u32| Vec3::from_slice(&data.vertexes[index as usize * 8..]);
+        data.indexes.as_ref().unwrap().chunks_exact(3).map(|triangle| [0, 1, 2].map(|corner| position(triangle[corner]))).collect()
+    }
+
+    /* Of a closed mesh facing outwards, by the divergence theorem */
+    fn volume(data: &MeshData) -> f32 {
+        triangles(data).iter().map(|[a, b, c]| a.dot(b.cross(*c)) / 6.0).sum()
+    }
+
+    #[test]
+    fn normals_and_uvs() {
+        for primitive in all() {
+            let data = primitive.generate();
+            assert_eq!(data.shader.get_vertex_size(), 8);
+            let vertexes: Vec<&[f32]> = data.vertexes.chunks_exact(8).collect();
+            let indexes = data.indexes.as_ref().unwrap();
+            assert!(indexes.iter().all(|index| (*index as usize) < vertexes.len()));
+            for vertex in &vertexes {
+                assert!((Vec3::from_slice(&vertex[3..6]).length() - 1.0).abs() < 1e-5, "{}", primitive.get_name());
+                let uv = Vec2::from_slice(&vertex[6..8]);
+                // Only the icosphere's seam goes a little past 1
+                assert!(uv.x >= 0.0 && uv.x <= 1.2 && uv.y >= 0.0 && uv.y <= 1.0, "{} uv {:?}", primitive.get_name(), uv);
+            }
+            // The normals are on the side the triangles face
+            for triangle in indexes.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|corner| Vec3::from_slice(vertexes[triangle[corner] as usize]));
+                let face = (b - a).cross(c - a);
+                assert!(face.length() > 0.0);
+                for corner in triangle {
+                    let normal = Vec3::from_slice(&vertexes[*corner as usize][3..6]);
+                    assert!(face.normalize().dot(normal) > 0.1, "{} faces away from its normals", primitive.get_name());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn closed_with_the_right_volume() {
+        let expected = [
+            4.0 / 3.0 * PI,
+            4.0 / 3.0 * PI,
+            2.0 * PI,
+            2.0 / 3.0 * PI,
+            2.0 * PI * PI * 0.25 * 0.25,
+            0.0,
+            PI + 4.0 / 3.0 * PI,
+        ];
+        for (primitive, expected) in all().into_iter().zip(expected) {
+            let data = primitive.generate();
+            if let Primitive::Plane { .. } = primitive {
+                assert!(!is_closed(&data));
+                assert_eq!(triangles(&data).len(), 32);
+                continue;
+            }
+            assert!(is_closed(&data), "{} has holes", primitive.get_name());
+            let volume = volume(&data);
+            // Polygons cut a little off the curved surfaces
+            assert!((volume - expected).abs() < expected * 0.05, "{} has volume {} instead of {}", primitive.get_name(), volume, expected);
+        }
+    }
+
+    #[test]
+    fn sphere_vertexes() {
+        for data in [uv_sphere(8, 4), icosphere(2)] {
+            for vertex in data.vertexes.chunks_exact(8) {
+                let position = Vec3::from_slice(vertex);
+                assert!((position.length() - 1.0).abs() < 1e-5);
+                assert!(position.abs_diff_eq(Vec3::from_slice(&vertex[3..6]), 1e-5));
+            }
+        }
+        assert_eq!(icosphere(2).indexes.unwrap().len(), 20 * 16 * 3);
+        // Degenerate triangles at the poles are left out
+        assert_eq!(uv_sphere(8, 4).indexes.unwrap().len(), (8 * 4 * 2 - 16) * 3);
+    }
+}
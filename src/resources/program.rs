@@ -0,0 +1,82 @@
+/* compiling and linking gl programs */
+
+use glow::*;
+
+use crate::util::error::Error;
+
+use super::shader_log::ShaderStage;
+
+/* Compiles and links the sources, cleaning up after itself if anything fails */
+pub fn link_program(
+    vertex_shader_source: &str,
+    fragment_shader_source: &str,
+    gl: &glow::Context) -> Result<(NativeProgram, NativeShader, NativeShader), Error>
+{
+    unsafe {
+        let vertex_shader = get_shader(vertex_shader_source, ShaderStage::Vertex, gl)?;
+        let fragment_shader = match get_shader(fragment_shader_source, ShaderStage::Fragment, gl) {
+            Ok(shader) => shader,
+            Err(err) => {
+                gl.delete_shader(vertex_shader);
+                return Err(err);
+            }
+        };
+        let program = match gl.create_program() {
+            Ok(program) => program,
+            Err(err) => {
+                delete_program(gl, None, Some(vertex_shader), Some(fragment_shader));
+                return Err(Error::invalid_gl_program(err));
+            }
+        };
+
+        gl.attach_shader(program, vertex_shader);
+        gl.attach_shader(program, fragment_shader);
+
+        gl.link_program(program);
+
+        if !gl.get_program_link_status(program) {
+            let info = gl.get_program_info_log(program);
+            delete_program(gl, Some(program), Some(vertex_shader), Some(fragment_shader));
+            return Err(Error::invalid_gl_program(info));
+        }
+        Ok((program, vertex_shader, fragment_shader))
+    }
+}
+
+unsafe fn get_shader(
+    shader_source: &str,
+    stage: ShaderStage,
+    gl: &glow::Context)-> Result<NativeShader, Error>
+{
+    let shader = match gl.create_shader(stage.get_gl_type()) {
+        Ok(shader) => shader,
+        Err(err) => return Err(Error::invalid_shader_source(err)),
+    };
+    gl.shader_source(shader, shader_source);
+    gl.compile_shader(shader);
+    if !gl.get_shader_compile_status(shader) {
+        let info = gl.get_shader_info_log(shader);
+        gl.delete_shader(shader);
+        return Err(Error::shader_compile(stage, info));
+    }
+    Ok(shader)
+}
+
+pub fn delete_program(
+    gl: &glow::Context,
+    program: Option<NativeProgram>,
+    vertex_shader: Option<NativeShader>,
+    fragment_shader: Option<NativeShader>)
+{
+    unsafe {
+        if let Some(program) = program {
+            gl.delete_program(program);
+        }
+        if let Some(vertex_shader) = vertex_shader {
+            gl.delete_shader(vertex_shader);
+        }
+        if let Some(fragment_shader) = fragment_shader {
+            gl.delete_shader(fragment_shader);
+        }
+    }
+}
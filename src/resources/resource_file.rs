@@ -9,12 +9,30 @@ use image;
 
 use crate::util::error::Error;
 
+use super::asset_paths::{find_asset, user_asset_dir, AssetSource};
+
 pub fn load_shader(file_name: &str) -> Result<String, Error> {
-    let dir = match shader_dir(file_name) {
-        Ok(dir) => dir,
-        Err(err) => return Err(err),
+    let data = load_asset(shader_dir(file_name)?)?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/* Writes an edited template over the file it was loaded from, or to the user asset directory */
+pub fn save_shader(file_name: &str, source: &str) -> Result<(), Error> {
+    let path = match shader_dir(file_name) {
+        Ok(AssetSource::File(path)) => path,
+        _ => {
+            let mut path = asset_dir()?;
+            path.push("shaders");
+            path.push(file_name);
+            path
+        }
     };
-    load_file(dir)
+    save_file(path, source)
+}
+
+/* The raw bytes of a mesh file */
+pub fn load_mesh(mesh_name: &str) -> Result<Vec<u8>, Error> {
+    load_asset(mesh_dir(mesh_name)?)
 }
 
 pub fn load_system_texture(image_name: &str, gl: &egui::Context) -> Result<TextureHandle, Error> {
@@ -26,16 +44,8 @@ pub fn load_system_texture(image_name: &str, gl: &egui::Context) -> Result<Textu
 }
 
 pub fn load_system_image(image_name: &str) -> Result<egui::ColorImage, Error> {
-    let path = match system_image_dir(image_name) {
-        Ok(value) => value,
-        Err(err) => return Err(err),
-    };
-    let image = match image::io::Reader::open(&path) {
-        Ok(value) => value,
-        Err(err) => return Err(Error::loading_file(err, String::from(image_name))),
-    };
-  
-    let image = image.decode()?;
+    let data = load_asset(system_image_dir(image_name)?)?;
+    let image = image::load_from_memory(&data)?;
     let size = [image.width() as _, image.height() as _];
     let image_buffer = image.to_rgba8();
     let pixels = image_buffer.as_flat_samples();
@@ -45,45 +55,66 @@ pub fn load_system_image(image_name: &str) -> Result<egui::ColorImage, Error> {
     ))
 }
 
+fn load_asset(source: AssetSource) -> Result<Vec<u8>, Error> {
+    match source {
+        AssetSource::File(path) => load_file_raw_vec(path),
+        AssetSource::Embedded(data) => Ok(data.to_vec()),
+    }
+}
+
+/* Where files written by the app go */
 fn asset_dir() -> Result<PathBuf, Error> {
-    let mut result = match dirs::home_dir() {
-        Some(dir) => dir,
-        None => return Err(Error::invalid_home_dir()),
-    };
-    result.push(".rusty_fractal");
-    Ok(result)
+    match user_asset_dir() {
+        Some(dir) => Ok(dir),
+        None => Err(Error::invalid_home_dir()),
+    }
 }
 
-fn shader_dir(shader_name: &str) -> Result<PathBuf, Error> {
-    let mut result = match asset_dir() {
-        Ok(dir) => dir,
-        Err(err) => return Err(err),
-    };
-    result.push("shaders");
-    result.push(shader_name);
-    Ok(result)
+/* The following look through the asset roots in order, see AssetPaths */
+pub fn shader_dir(shader_name: &str) -> Result<AssetSource, Error> {
+    find_asset(&format!("shaders/{}", shader_name))
 }
 
-fn system_image_dir(image_name: &str) -> Result<PathBuf, Error> {
-    let mut result = match asset_dir() {
-        Ok(dir) => dir,
-        Err(err) => return Err(err),
-    };
-    result.push("system_images");
-    result.push(image_name);
+pub fn system_image_dir(image_name: &str) -> Result<AssetSource, Error> {
+    find_asset(&format!("system_images/{}", image_name))
+}
+
+pub fn mesh_dir(mesh_name: &str) -> Result<AssetSource, Error> {
+    find_asset(&format!("meshes/{}", mesh_name))
+}
+
+pub fn key_bindings_dir() -> Result<PathBuf, Error> {
+    let mut result = asset_dir()?;
+    result.push("key_bindings.txt");
     Ok(result)
 }
 
-pub fn mesh_dir(mesh_name: &str) -> Result<PathBuf, Error> {
-    let mut result = match asset_dir() {
-        Ok(dir) => dir,
-        Err(err) => return Err(err),
-    };
+/* Parsed meshes, see MeshCache */
+pub fn mesh_cache_dir() -> Result<PathBuf, Error> {
+    let mut result = asset_dir()?;
+    result.push("cache");
     result.push("meshes");
-    result.push(mesh_name);
     Ok(result)
 }
 
+pub fn screenshot_dir() -> Result<PathBuf, Error> {
+    let mut result = asset_dir()?;
+    result.push("screenshots");
+    Ok(result)
+}
+
+pub fn save_file(dir: PathBuf, contents: &str) -> Result<(), Error> {
+    if let Some(parent) = dir.parent() {
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            return Err(Error::loading_file(err, String::from(parent.to_str().unwrap_or("__UNKNOWN_DIR__"))));
+        }
+    }
+    match std::fs::write(&dir, contents) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::loading_file(err, String::from(dir.to_str().unwrap_or("__UNKNOWN_DIR__")))),
+    }
+}
+
 pub fn load_file(dir: PathBuf) -> Result<String, Error> {
     let file = match File::open(&dir) {
         Ok(file) => file,
@@ -99,7 +130,7 @@ pub fn load_file(dir: PathBuf) -> Result<String, Error> {
     let mut result = String::new();
     match buf_read.read_to_string(&mut result) {
         Ok(_) => (),
-        Err(err) => return Err(Error::loading_file(err, String::from(dir.to_str().unwrap()))),
+        Err(err) => return Err(Error::loading_file(err, String::from(dir.to_str().unwrap_or("__UNKNOWN_DIR__")))),
     }
     Ok(result)
 }
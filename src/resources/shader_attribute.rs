@@ -2,6 +2,8 @@ use std::mem::size_of;
 
 use glow::HasContext;
 
+use crate::util::error::Error;
+
 /*
  * @brief
  * Represents a vertex attributes that is passed on to the fragment shader.
@@ -26,6 +28,34 @@ impl ShaderAttributePair {
             attribute_out: ShaderAttribute::output_float3(name),
         }
     }
+    pub fn float2(index: u32, name: String) -> Self {
+        Self {
+            name: name.clone(),
+            attribute_in: ShaderAttribute::float2(index, name.clone() + "_in"),
+            attribute_out: ShaderAttribute::output_float2(name),
+        }
+    }
+    /* Any size and format, the value is passed on as it was read */
+    pub fn new(index: u32, name: String, size: i32, format: AttributeFormat) -> Self {
+        Self {
+            name: name.clone(),
+            attribute_in: ShaderAttribute::input(index, name.clone() + "_in", size, format),
+            attribute_out: ShaderAttribute::output(name, size, format),
+        }
+    }
+    /* rgba stored as a byte per channel, read as 0.0 to 1.0 */
+    pub fn color_u8(index: u32, name: String) -> Self {
+        Self::new(index, name, 4, AttributeFormat::NormalizedU8)
+    }
+    pub fn color_u16(index: u32, name: String) -> Self {
+        Self::new(index, name, 4, AttributeFormat::NormalizedU16)
+    }
+    pub fn int(index: u32, name: String) -> Self {
+        Self::new(index, name, 1, AttributeFormat::Int)
+    }
+    pub fn uint(index: u32, name: String) -> Self {
+        Self::new(index, name, 1, AttributeFormat::UnsignedInt)
+    }
     pub fn get_name(&self) -> &String {
         &self.name
     }
@@ -37,12 +67,96 @@ impl ShaderAttributePair {
     }
 }
 
+/*
+ * @brief
+ * How the components of an attribute are stored in the vertex buffer. Vertexes are always
+ * kept as floats until they are uploaded, integers are exact up to 2^24 that way.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AttributeFormat {
+    Float,
+    /* 0 to 255, read as 0.0 to 1.0 in the shader */
+    NormalizedU8,
+    /* 0 to 65535, read as 0.0 to 1.0 in the shader */
+    NormalizedU16,
+    /* Read as int in the shader and passed on without interpolation */
+    Int,
+    UnsignedInt,
+}
+
+impl AttributeFormat {
+    pub fn get_data_type(&self) -> u32 {
+        match self {
+            Self::Float => glow::FLOAT,
+            Self::NormalizedU8 => glow::UNSIGNED_BYTE,
+            Self::NormalizedU16 => glow::UNSIGNED_SHORT,
+            Self::Int => glow::INT,
+            Self::UnsignedInt => glow::UNSIGNED_INT,
+        }
+    }
+    /* Bytes used by a single component */
+    pub fn get_component_size(&self) -> usize {
+        match self {
+            Self::NormalizedU8 => 1,
+            Self::NormalizedU16 => 2,
+            Self::Float | Self::Int | Self::UnsignedInt => 4,
+        }
+    }
+    pub fn is_normalized(&self) -> bool {
+        matches!(self, Self::NormalizedU8 | Self::NormalizedU16)
+    }
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Self::Int | Self::UnsignedInt)
+    }
+    /* Number used for the format in files like the mesh cache */
+    pub fn get_id(&self) -> u32 {
+        match self {
+            Self::Float => 0,
+            Self::NormalizedU8 => 1,
+            Self::NormalizedU16 => 2,
+            Self::Int => 3,
+            Self::UnsignedInt => 4,
+        }
+    }
+    pub fn from_id(id: u32) -> Option<Self> {
+        [Self::Float, Self::NormalizedU8, Self::NormalizedU16, Self::Int, Self::UnsignedInt]
+            .into_iter()
+            .find(|format| format.get_id() == id)
+    }
+
+    /* The glsl type of an attribute with size components of this format */
+    fn get_glsl_type(&self, size: i32) -> String {
+        let (scalar, vector) = match self {
+            Self::Int => ("int", "ivec"),
+            Self::UnsignedInt => ("uint", "uvec"),
+            _ => ("float", "vec"),
+        };
+        match size {
+            1 => String::from(scalar),
+            size => format!("{}{}", vector, size),
+        }
+    }
+
+    /* Appends value as stored in the buffer, normalized values are clamped to 0.0 to 1.0 */
+    pub fn write(&self, value: f32, result: &mut Vec<u8>) {
+        match self {
+            Self::Float => result.extend_from_slice(&value.to_ne_bytes()),
+            Self::NormalizedU8 => result.push((value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8),
+            Self::NormalizedU16 => result.extend_from_slice(&((value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16).to_ne_bytes()),
+            Self::Int => result.extend_from_slice(&(value as i32).to_ne_bytes()),
+            Self::UnsignedInt => result.extend_from_slice(&(value as u32).to_ne_bytes()),
+        }
+    }
+}
+
 pub struct ShaderAttribute {
     index: Option<u32>,
     size: i32,
     data_type: u32,
+    format: AttributeFormat,
     name: String,
     cpp_type: String,
+    /* Bytes the attribute takes in a vertex, padded to 4 so the next one stays aligned */
     stride: i32,
 }
 
@@ -56,6 +170,9 @@ impl ShaderAttribute {
     pub fn data_type(&self) -> u32 {
         self.data_type
     }
+    pub fn get_format(&self) -> AttributeFormat {
+        self.format
+    }
     pub fn name(&self) -> &String {
         &self.name
     }
@@ -66,32 +183,43 @@ impl ShaderAttribute {
         self.stride
     }
 
-    pub fn apply_attrib(&self, gl: &glow::Context) {
-        self.apply_attrib_with_stride_offset(gl, 0, 0);
+    pub fn apply_attrib(&self, gl: &glow::Context) -> Result<(), Error> {
+        self.apply_attrib_with_stride_offset(gl, 0, 0)
     }
-    pub fn apply_attrib_with_stride_offset(&self, gl: &glow::Context, stride: i32, offset: i32) {
+    pub fn apply_attrib_with_stride_offset(&self, gl: &glow::Context, stride: i32, offset: i32) -> Result<(), Error> {
         unsafe {
             if let Some(index) = self.index() {
                 //println!("vertex attrib, index: {}, size: {}, data_type: {}, size: {}, offset: {}", index, self.size(), self.data_type(), size_of::<f32>() as i32 * self.size(), 0);
-                gl.vertex_attrib_pointer_f32(index, self.size(), self.data_type(), false, stride, offset);
+                match self.format.is_integer() {
+                    true => gl.vertex_attrib_pointer_i32(index, self.size(), self.data_type(), stride, offset),
+                    false => gl.vertex_attrib_pointer_f32(index, self.size(), self.data_type(), self.format.is_normalized(), stride, offset),
+                }
+                Ok(())
             } else {
-                todo!("add error logging")
+                Err(Error::missing_attribute_index(&self.name))
             }
         }
     }
 
-    pub fn as_vertex_shader_in(&self) -> String {
+    pub fn as_vertex_shader_in(&self) -> Result<String, Error> {
         if let Some(index) = self.index {
-            format!("layout(location = {}) in {} {};\n", index, self.cpp_type, self.name)
+            Ok(format!("layout(location = {}) in {} {};\n", index, self.cpp_type, self.name))
         } else {
-            todo!("error logging");
+            Err(Error::missing_attribute_index(&self.name))
         }
     }
     pub fn as_vertex_shader_out(&self) -> String {
-        format!("out {} {};\n", self.cpp_type, self.name)
+        format!("{}out {} {};\n", self.get_interpolation(), self.cpp_type, self.name)
     }
     pub fn as_fragment_shader_in(&self) -> String {
-        format!("in {} {};\n", self.cpp_type, self.name)
+        format!("{}in {} {};\n", self.get_interpolation(), self.cpp_type, self.name)
+    }
+    /* Integers can't be interpolated between vertexes */
+    fn get_interpolation(&self) -> &'static str {
+        match self.format.is_integer() {
+            true => "flat ",
+            false => "",
+        }
     }
     pub fn as_fragment_shader_out(&self) -> String {
         format!("out {} {};\n", self.cpp_type, self.name)
@@ -102,42 +230,71 @@ impl ShaderAttribute {
 }
 
 impl ShaderAttribute {
-    pub fn float4(index: u32, name: String) -> Self {
+    /* A vertex attribute read from the buffer at location index */
+    pub fn input(index: u32, name: String, size: i32, format: AttributeFormat) -> Self {
         Self {
             index: Some(index),
-            size: 4,
-            data_type: glow::FLOAT,
+            ..Self::output(name, size, format)
+        }
+    }
+    pub fn output(name: String, size: i32, format: AttributeFormat) -> Self {
+        let bytes = format.get_component_size() * size as usize;
+        Self {
+            index: None,
+            size,
+            data_type: format.get_data_type(),
+            format,
             name,
-            cpp_type: String::from("vec4"),
-            stride: size_of::<f32>() as i32 * 4,
+            cpp_type: format.get_glsl_type(size),
+            stride: bytes.next_multiple_of(4) as i32,
         }
     }
+    pub fn float4(index: u32, name: String) -> Self {
+        Self::input(index, name, 4, AttributeFormat::Float)
+    }
     pub fn output_float4(name: String) -> Self {
+        Self::output(name, 4, AttributeFormat::Float)
+    }
+    pub fn float3(index: u32, name: String) -> Self {
+        Self::input(index, name, 3, AttributeFormat::Float)
+    }
+    pub fn output_float3(name: String) -> Self {
+        Self::output(name, 3, AttributeFormat::Float)
+    }
+    pub fn float2(index: u32, name: String) -> Self {
+        Self::input(index, name, 2, AttributeFormat::Float)
+    }
+    pub fn output_float2(name: String) -> Self {
+        Self::output(name, 2, AttributeFormat::Float)
+    }
+    pub fn uniform_float(name: String) -> Self {
         Self {
             index: None,
-            size: 4,
+            size: 1,
             data_type: glow::FLOAT,
+            format: AttributeFormat::Float,
             name,
-            cpp_type: String::from("vec4"),
-            stride: size_of::<f32>() as i32 * 4,
+            cpp_type: String::from("float"),
+            stride: size_of::<f32>() as i32,
         }
     }
-    pub fn float3(index: u32, name: String) -> Self {
+    pub fn uniform_float4(name: String) -> Self {
         Self {
-            index: Some(index),
-            size: 3,
+            index: None,
+            size: 4,
             data_type: glow::FLOAT,
+            format: AttributeFormat::Float,
             name,
-            cpp_type: String::from("vec3"),
-            stride: size_of::<f32>() as i32 * 3,
-
+            cpp_type: String::from("vec4"),
+            stride: size_of::<f32>() as i32 * 4,
         }
     }
-    pub fn output_float3(name: String) -> Self {
+    pub fn uniform_float3(name: String) -> Self {
         Self {
             index: None,
             size: 3,
             data_type: glow::FLOAT,
+            format: AttributeFormat::Float,
             name,
             cpp_type: String::from("vec3"),
             stride: size_of::<f32>() as i32 * 3,
@@ -148,6 +305,7 @@ impl ShaderAttribute {
             index: None,
             size: -1,
             data_type: 0,
+            format: AttributeFormat::Float,
             name,
             cpp_type: String::from("mat4"),
             stride: size_of::<f32>() as i32 * 16,
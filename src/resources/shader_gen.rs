@@ -1,47 +1,54 @@
 use glam::Vec3;
 
-use super::{resource_file::load_shader, mesh::{Mesh, SimpleVertexShader, VertexShader}, material::{Material, SolidColorMaterial, MaterialType}};
+use crate::util::error::Error;
 
-pub fn gen_material_shader(material: &Material, mesh: &Mesh) -> String {
+use super::{shader_attribute::ShaderAttribute, resource_file::load_shader, mesh::{Mesh, SimpleVertexShader, VertexShader}, material::{Material, SolidColorMaterial, MaterialType}};
+
+/* The templates the shaders are generated from, in the shaders asset directory */
+pub const TEMPLATE_FILES: [&str; 2] = ["template_vertex.glsl", "template_fragment.glsl"];
+
+pub fn gen_material_shader(material: &Material, mesh: &Mesh) -> Result<String, Error> {
     match material.get_material_type() {
         MaterialType::SolidColor(solid_material) => get_solid_color_material_shader(solid_material, mesh),
     }
 }
 
-fn get_solid_color_material_shader(shader: &SolidColorMaterial, mesh: &Mesh) -> String {
+fn get_solid_color_material_shader(shader: &SolidColorMaterial, mesh: &Mesh) -> Result<String, Error> {
     let outputs = shader.get_color_out().as_fragment_shader_out();
-    let mut fragment_code = format!("{} = {};\n", shader.get_color_out().name(), vec3_as_glsl_vec4(shader.get_color(), 1.0));
-    let mut inputs = String::new();
-    if let Some(normal) = mesh.get_shader().get_normal() {
+    let mut fragment_code = format!("{} = vec4({}, 1.0);\n", shader.get_color_out().name(), shader.get_color_uniform().name());
+    let mut inputs = camera_offset_uniforms();
+    inputs += &shader.get_color_uniform().as_uniform();
+    if let (Some(normal), true) = (mesh.get_shader().get_normal(), shader.is_shaded()) {
         inputs += &normal.get_attribute_out().as_fragment_shader_in();
+        inputs += &shader.get_ambient_uniform().as_uniform();
+        inputs += &shader.get_diffuse_uniform().as_uniform();
+        inputs += &light_uniforms();
 
-        fragment_code += "vec3 _light_dir = normalize(vec3(-1.0, -1.0, 0.0));\n";
-        fragment_code += &format!("float _diffuse = dot(_light_dir, {}) * 0.4 + 0.5;\n", normal.get_name());
+        fragment_code += &format!("vec3 _diffuse = dot(light_direction, {}) * {} * light_color + {};\n", normal.get_name(), shader.get_diffuse_uniform().name(), shader.get_ambient_uniform().name());
         fragment_code += &format!("{}.xyz *= _diffuse;\n", shader.get_color_out().name());
     }
+    inputs += &highlight_uniform().as_uniform();
+    fragment_code += &format!("{0}.xyz = mix({0}.xyz, {1}.xyz, {1}.w);\n", shader.get_color_out().name(), highlight_uniform().name());
 
     let properties = vec![
         (String::from("INPUTS"), inputs),
         (String::from("OUTPUTS"), outputs),
         (String::from("FRAGMENT_CODE"), fragment_code),
     ];
-    let template_fragment_source = match load_shader("template_fragment.glsl") {
-        Ok(source) => source,
-        Err(_) => todo!("add error handling"),
-    };
-    replace_comments(String::from(template_fragment_source), properties)
+    let template_fragment_source = load_shader("template_fragment.glsl")?;
+    Ok(replace_comments(template_fragment_source, properties))
 }
 
-pub fn gen_vertex_shader(mesh: &Mesh) -> String {
+pub fn gen_vertex_shader(mesh: &Mesh) -> Result<String, Error> {
     match mesh.get_shader() {
         VertexShader::Simple(simple_shader) => get_simple_vertex_shader(&simple_shader),
     }
 }
 
-fn get_simple_vertex_shader(shader: &SimpleVertexShader) -> String {
-    let mut inputs = shader.get_vertex_in().as_vertex_shader_in();
+fn get_simple_vertex_shader(shader: &SimpleVertexShader) -> Result<String, Error> {
+    let mut inputs = shader.get_vertex_in().as_vertex_shader_in()?;
     for property in shader.get_vertex_properties() {
-        inputs += property.get_attribute_in().as_vertex_shader_in().as_str();
+        inputs += property.get_attribute_in().as_vertex_shader_in()?.as_str();
     }
 
     let mut outputs = String::new();
@@ -59,17 +66,89 @@ fn get_simple_vertex_shader(shader: &SimpleVertexShader) -> String {
         (String::from("OUTPUTS"), outputs),
         (String::from("VERTEX_CODE"), vertex_code),
     ];
-    let template_vertex_source = match load_shader("template_vertex.glsl") {
-        Ok(source) => source,
-        Err(_) => todo!("add error handling"),
-    };
-    replace_comments(String::from(template_vertex_source), properties)
+    let template_vertex_source = load_shader("template_vertex.glsl")?;
+    Ok(replace_comments(template_vertex_source, properties))
+}
+
+/*
+ * Shaders used when the real ones can't be built. They don't need the templates
+ * and draw the object as a magenta checker so it is obvious something went wrong.
+ */
+pub fn gen_error_vertex_shader(mesh: &Mesh) -> String {
+    let VertexShader::Simple(shader) = mesh.get_shader();
+    let location = shader.get_vertex_in().index().unwrap_or(0);
+    format!(
+        "#version 330 core\n\
+        layout(location = {0}) in vec3 _error_position_in;\n\
+        uniform mat4 {1};\n\
+        out vec3 _error_position;\n\
+        void main() {{\n\
+            _error_position = _error_position_in;\n\
+            gl_Position = {1} * vec4(_error_position_in, 1.0);\n\
+        }}\n",
+        location, shader.get_camera_matrix().name())
+}
+
+pub fn gen_error_fragment_shader() -> String {
+    format!(
+        "#version 330 core\n\
+        in vec3 _error_position;\n\
+        {0}\
+        out vec4 color_out;\n\
+        void main() {{\n\
+            vec3 _cell = floor(_error_position * 8.0);\n\
+            float _checker = mod(_cell.x + _cell.y + _cell.z, 2.0);\n\
+            color_out = mix(vec4(1.0, 0.0, 1.0, 1.0), vec4(0.1, 0.0, 0.1, 1.0), _checker);\n\
+            color_out.xyz = mix(color_out.xyz, {1}.xyz, {1}.w);\n\
+        }}\n",
+        highlight_uniform().as_uniform(), highlight_uniform().name())
+}
+
+/* Flat colored lines for overlays such as placeholder boxes, see LineRenderer */
+pub fn gen_line_vertex_shader() -> String {
+    String::from(
+        "#version 330 core\n\
+        layout(location = 0) in vec3 _line_position;\n\
+        uniform mat4 camera_matrix;\n\
+        void main() {\n\
+            gl_Position = camera_matrix * vec4(_line_position, 1.0);\n\
+        }\n")
+}
+
+pub fn gen_line_fragment_shader() -> String {
+    String::from(
+        "#version 330 core\n\
+        uniform vec4 line_color;\n\
+        out vec4 color_out;\n\
+        void main() {\n\
+            color_out = line_color;\n\
+        }\n")
+}
+
+/*
+ * The camera position in object space split into two floats. Ray marching code
+ * can use (camera_offset_high + camera_offset_low) to keep precision on deep zooms.
+ */
+fn camera_offset_uniforms() -> String {
+    ShaderAttribute::uniform_float3(String::from("camera_offset_high")).as_uniform()
+        + &ShaderAttribute::uniform_float3(String::from("camera_offset_low")).as_uniform()
+}
+
+/* Set from the DirectionalLight of the scene */
+fn light_uniforms() -> String {
+    ShaderAttribute::uniform_float3(String::from("light_direction")).as_uniform()
+        + &ShaderAttribute::uniform_float3(String::from("light_color")).as_uniform()
+}
+
+/* Tint mixed over selected objects. The alpha is how much of the tint is used */
+fn highlight_uniform() -> ShaderAttribute {
+    ShaderAttribute::uniform_float4(String::from("highlight_color"))
 }
 
 fn _vec3_as_glsl_vec3(value: Vec3) -> String {
     format!("vec3({}, {}, {})", value.x, value.y, value.z)
 }
-fn vec3_as_glsl_vec4(value: Vec3, alpha: f32) -> String {
+fn _vec3_as_glsl_vec4(value: Vec3, alpha: f32) -> String {
     format!("vec4({}, {}, {}, {})", value.x, value.y, value.z, alpha)
 }
 
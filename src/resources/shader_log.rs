@@ -0,0 +1,149 @@
+/* reading the info logs of the gl compiler so errors can be shown next to the source */
+
+use std::fmt::Display;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    pub fn get_gl_type(&self) -> u32 {
+        match self {
+            Self::Vertex => glow::VERTEX_SHADER,
+            Self::Fragment => glow::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl Display for ShaderStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Vertex => write!(f, "vertex"),
+            Self::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+/* One line of an info log. line is 1 based and None when the driver didn't give one */
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ShaderMessage {
+    pub stage: ShaderStage,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/*
+ * @brief
+ * Splits an info log into messages. Drivers disagree on the format, the ones handled are
+ *   mesa:         0:12(5): error: ...
+ *   nvidia:       0(12) : error C1008: ...
+ *   amd / intel:  ERROR: 0:12: ...
+ */
+pub fn parse_info_log(stage: ShaderStage, info_log: &str) -> Vec<ShaderMessage> {
+    info_log
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| ShaderMessage {
+            stage,
+            line: parse_line_number(line),
+            message: String::from(line),
+        })
+        .collect()
+}
+
+fn parse_line_number(line: &str) -> Option<usize> {
+    let line = line
+        .strip_prefix("ERROR:")
+        .or_else(|| line.strip_prefix("WARNING:"))
+        .unwrap_or(line)
+        .trim_start();
+    // Skip the source string index
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == line.len() {
+        return None;
+    }
+    let rest = rest.strip_prefix(':').or_else(|| rest.strip_prefix('('))?;
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/* Whether line holds one of the comment marks replaced by shader_gen, e.g. / * INPUTS * / */
+fn is_mark_line(line: &str) -> bool {
+    match (line.find("/* "), line.find(" */")) {
+        (Some(start), Some(end)) if start + 3 < end => {
+            line[start + 3..end].chars().all(|c| c.is_ascii_uppercase() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/*
+ * @brief
+ * For every line of a source generated from template, the line of the template it came from.
+ * Lines are 1 based. The lines a comment mark expanded to all map to the line of the mark.
+ */
+pub fn map_generated_lines(template: &str, generated: &str) -> Vec<usize> {
+    let template_lines: Vec<&str> = template.lines().collect();
+    let generated_lines: Vec<&str> = generated.lines().collect();
+    let mut result = Vec::with_capacity(generated_lines.len());
+    let mut current = 0;
+    for (i, template_line) in template_lines.iter().enumerate() {
+        if current >= generated_lines.len() {
+            break;
+        }
+        if !is_mark_line(template_line) {
+            result.push(i + 1);
+            current += 1;
+            continue;
+        }
+        // The expansion runs until the next line that came from the template as is
+        let end = match template_lines[i + 1..].iter().find(|line| !is_mark_line(line)) {
+            Some(next) => generated_lines[current..]
+                .iter()
+                .position(|line| line == next)
+                .map_or(generated_lines.len(), |offset| current + offset),
+            None => generated_lines.len(),
+        };
+        // A mark replaced by nothing still leaves its line
+        let end = end.max(current + 1).min(generated_lines.len());
+        result.extend(std::iter::repeat_n(i + 1, end - current));
+        current = end;
+    }
+    let last = template_lines.len().max(1);
+    result.resize(generated_lines.len(), last);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_drivers() {
+        let mesa = parse_info_log(ShaderStage::Fragment, "0:12(5): error: `foo' undeclared\n");
+        assert_eq!(mesa.len(), 1);
+        assert_eq!(mesa[0].line, Some(12));
+        assert_eq!(mesa[0].stage, ShaderStage::Fragment);
+
+        let nvidia = parse_info_log(ShaderStage::Vertex, "0(7) : error C1008: undefined variable \"foo\"");
+        assert_eq!(nvidia[0].line, Some(7));
+
+        let amd = parse_info_log(ShaderStage::Vertex, "ERROR: 0:3: 'foo' : undeclared identifier\nERROR: 1 compilation errors.");
+        assert_eq!(amd.len(), 2);
+        assert_eq!(amd[0].line, Some(3));
+        assert_eq!(amd[1].line, None);
+    }
+
+    #[test]
+    fn map_lines() {
+        let template = "#version 330 core\n/* INPUTS */\nvoid main() {\n/* CODE */\n}\n";
+        let generated = "#version 330 core\nin vec3 a;\nin vec3 b;\nvoid main() {\n\n}\n";
+        assert_eq!(map_generated_lines(template, generated), vec![1, 2, 2, 3, 4, 5]);
+
+        let unchanged = "a\nb\n";
+        assert_eq!(map_generated_lines(unchanged, unchanged), vec![1, 2]);
+    }
+}
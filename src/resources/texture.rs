@@ -1,4 +1,5 @@
-use eframe::epaint::TextureHandle;
+use eframe::epaint::{ColorImage, TextureHandle};
+use eframe::egui::TextureFilter;
 
 pub struct Texture {
     texture_handle: TextureHandle,
@@ -16,6 +17,10 @@ impl Texture {
     pub fn get_handle(&self) -> &TextureHandle {
         return &self.texture_handle
     }
+    /* Replaces the pixels, the handle stays valid */
+    pub fn set_image(&mut self, image: ColorImage) {
+        self.texture_handle.set(image, TextureFilter::Linear);
+    }
     pub fn get_name(&self) -> &str {
         return &self.name
     }
@@ -0,0 +1,203 @@
+use glam::{DMat4, DVec3};
+
+use super::ray::Ray;
+
+/*
+ * @brief
+ * Axis aligned bounding box. An empty box has min above max so that
+ * extending it by anything gives that thing's bounds.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    min: DVec3,
+    max: DVec3,
+}
+
+impl Aabb {
+    pub fn new(min: DVec3, max: DVec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: DVec3::splat(f64::INFINITY),
+            max: DVec3::splat(f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = DVec3>) -> Self {
+        let mut result = Self::empty();
+        for point in points {
+            result.extend(point);
+        }
+        result
+    }
+
+    pub fn get_min(&self) -> DVec3 {
+        self.min
+    }
+    pub fn get_max(&self) -> DVec3 {
+        self.max
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn get_center(&self) -> DVec3 {
+        (self.min + self.max) * 0.5
+    }
+    pub fn get_size(&self) -> DVec3 {
+        match self.is_empty() {
+            true => DVec3::ZERO,
+            false => self.max - self.min,
+        }
+    }
+
+    pub fn get_surface_area(&self) -> f64 {
+        let size = self.get_size();
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn extend(&mut self, point: DVec3) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /* Distance along ray to where it enters the box, 0 if it starts inside. None past max_distance */
+    pub fn intersect_ray(&self, ray: &Ray, max_distance: f64) -> Option<f64> {
+        if self.is_empty() {
+            return None;
+        }
+        self.intersect_inverse(ray.get_origin(), ray.get_direction().recip(), max_distance)
+    }
+
+    /* intersect_ray with the reciprocal of the direction already worked out, for testing many boxes. The box can't be empty */
+    pub fn intersect_inverse(&self, origin: DVec3, inverse_direction: DVec3, max_distance: f64) -> Option<f64> {
+        let t1 = (self.min - origin) * inverse_direction;
+        let t2 = (self.max - origin) * inverse_direction;
+        let enter = t1.min(t2).max_element().max(0.0);
+        let exit = t1.max(t2).min_element().min(max_distance);
+        (enter <= exit).then_some(enter)
+    }
+
+    pub fn get_corners(&self) -> [DVec3; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| DVec3::new(
+            if i & 1 == 0 { self.min.x } else { self.max.x },
+            if i & 2 == 0 { self.min.y } else { self.max.y },
+            if i & 4 == 0 { self.min.z } else { self.max.z }))
+    }
+
+    /* The box around the transformed corners, so it may be larger than the transformed contents need */
+    pub fn transformed(&self, matrix: &DMat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(self.get_corners().map(|corner| matrix.transform_point3(corner)))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoundingSphere {
+    center: DVec3,
+    radius: f64,
+}
+
+impl BoundingSphere {
+    pub fn new(center: DVec3, radius: f64) -> Self {
+        Self { center, radius }
+    }
+
+    /* Centered on the bounds of points, not the smallest sphere but close enough for framing and culling */
+    pub fn from_points(points: impl IntoIterator<Item = DVec3> + Clone) -> Self {
+        let center = Aabb::from_points(points.clone()).get_center();
+        if !center.is_finite() {
+            return Self::new(DVec3::ZERO, 0.0);
+        }
+        let radius = points.into_iter().map(|point| point.distance(center)).fold(0.0, f64::max);
+        Self { center, radius }
+    }
+
+    /* The sphere around the box's corners */
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        match aabb.is_empty() {
+            true => Self::new(DVec3::ZERO, 0.0),
+            false => Self::new(aabb.get_center(), aabb.get_size().length() * 0.5),
+        }
+    }
+
+    pub fn get_center(&self) -> DVec3 {
+        self.center
+    }
+    pub fn get_radius(&self) -> f64 {
+        self.radius
+    }
+
+    /* Scaled by the largest axis scale of matrix so it still holds the contents */
+    pub fn transformed(&self, matrix: &DMat4) -> Self {
+        let scale = matrix.x_axis.truncate().length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{DMat4, DQuat, DVec3};
+
+    use super::*;
+
+    #[test]
+    fn aabb() {
+        let mut aabb = Aabb::empty();
+        assert!(aabb.is_empty());
+        assert_eq!(aabb.get_size(), DVec3::ZERO);
+        aabb.extend(DVec3::new(1.0, -2.0, 0.0));
+        aabb.extend(DVec3::new(-1.0, 2.0, 0.5));
+        assert_eq!(aabb, Aabb::new(DVec3::new(-1.0, -2.0, 0.0), DVec3::new(1.0, 2.0, 0.5)));
+        assert_eq!(aabb.union(&Aabb::empty()), aabb);
+
+        let rotated = aabb.transformed(&DMat4::from_quat(DQuat::from_rotation_z(std::f64::consts::FRAC_PI_2)));
+        assert!(rotated.get_min().abs_diff_eq(DVec3::new(-2.0, -1.0, 0.0), 1e-9));
+        assert!(rotated.get_max().abs_diff_eq(DVec3::new(2.0, 1.0, 0.5), 1e-9));
+        assert!(Aabb::empty().transformed(&DMat4::from_translation(DVec3::ONE)).is_empty());
+        assert_eq!(aabb.get_surface_area(), 2.0 * (8.0 + 2.0 + 1.0));
+    }
+
+    #[test]
+    fn intersect_ray() {
+        let aabb = Aabb::new(DVec3::splat(-1.0), DVec3::splat(1.0));
+        let ray = Ray::new(DVec3::new(0.5, 0.0, 5.0), DVec3::NEG_Z);
+        assert_eq!(aabb.intersect_ray(&ray, f64::INFINITY), Some(4.0));
+        assert_eq!(aabb.intersect_ray(&ray, 3.0), None);
+        assert_eq!(aabb.intersect_ray(&Ray::new(DVec3::ZERO, DVec3::X), 1.0), Some(0.0));
+        assert_eq!(aabb.intersect_ray(&Ray::new(DVec3::new(2.0, 0.0, 5.0), DVec3::NEG_Z), f64::INFINITY), None);
+        assert_eq!(aabb.intersect_ray(&Ray::new(DVec3::new(0.0, 0.0, 5.0), DVec3::Z), f64::INFINITY), None);
+        assert_eq!(Aabb::empty().intersect_ray(&ray, f64::INFINITY), None);
+    }
+
+    #[test]
+    fn sphere() {
+        let points = [DVec3::new(-1.0, 0.0, 0.0), DVec3::new(3.0, 0.0, 0.0), DVec3::new(1.0, 1.0, 0.0)];
+        let sphere = BoundingSphere::from_points(points);
+        assert_eq!(sphere.get_center(), DVec3::new(1.0, 0.5, 0.0));
+        assert!(points.iter().all(|point| point.distance(sphere.get_center()) <= sphere.get_radius()));
+
+        let matrix = DMat4::from_scale_rotation_translation(DVec3::new(1.0, 3.0, 2.0), DQuat::IDENTITY, DVec3::X);
+        let transformed = sphere.transformed(&matrix);
+        assert_eq!(transformed.get_center(), DVec3::new(2.0, 1.5, 0.0));
+        assert_eq!(transformed.get_radius(), sphere.get_radius() * 3.0);
+        assert_eq!(BoundingSphere::from_points(Vec::new()).get_radius(), 0.0);
+    }
+}
@@ -0,0 +1,398 @@
+use glam::DVec3;
+
+use super::bounds::Aabb;
+use super::ray::Ray;
+
+/* Split candidates per axis when building */
+const BIN_COUNT: usize = 16;
+/* Nodes with more primitives are always split if they can be */
+const MAX_LEAF_SIZE: usize = 8;
+/* Cost of visiting a node compared to intersecting one primitive */
+const TRAVERSAL_COST: f64 = 1.0;
+
+struct BvhNode {
+    bounds: Aabb,
+    /* Index of the left child (the right one follows it) or of the first primitive for leaves */
+    first: u32,
+    /* Number of primitives, 0 for interior nodes */
+    count: u32,
+}
+
+/*
+ * @brief
+ * Bounding volume hierarchy over anything that has bounds, built with the surface area heuristic.
+ * It only knows the primitives by index, the queries call back for the actual intersection test.
+ * Used over the triangles of a mesh and over the objects of a scene.
+ */
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /* Primitive indexes, each leaf covers a range of these */
+    primitives: Vec<u32>,
+}
+
+impl Bvh {
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut result = Self {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            primitives: (0..bounds.len() as u32).collect(),
+        };
+        if bounds.is_empty() {
+            return result;
+        }
+        let centroids: Vec<DVec3> = bounds.iter().map(Aabb::get_center).collect();
+        result.nodes.push(BvhNode { bounds: Aabb::empty(), first: 0, count: bounds.len() as u32 });
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if let Some((left, right)) = result.subdivide(node, bounds, &centroids) {
+                stack.push(left);
+                stack.push(right);
+            }
+        }
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.bounds)
+    }
+
+    /* Works out the bounds of node and splits it if that pays off, returning the children */
+    fn subdivide(&mut self, node: usize, bounds: &[Aabb], centroids: &[DVec3]) -> Option<(usize, usize)> {
+        let first = self.nodes[node].first as usize;
+        let count = self.nodes[node].count as usize;
+        let range = first..first + count;
+        let mut node_bounds = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+        for primitive in &self.primitives[range.clone()] {
+            node_bounds = node_bounds.union(&bounds[*primitive as usize]);
+            centroid_bounds.extend(centroids[*primitive as usize]);
+        }
+        self.nodes[node].bounds = node_bounds;
+        if count <= 2 {
+            return None;
+        }
+
+        let (axis, split, cost) = self.find_split(&self.primitives[range.clone()], bounds, centroids, &centroid_bounds)?;
+        let leaf_cost = count as f64;
+        let split_cost = TRAVERSAL_COST + cost / node_bounds.get_surface_area().max(f64::MIN_POSITIVE);
+        if split_cost >= leaf_cost && count <= MAX_LEAF_SIZE {
+            return None;
+        }
+
+        // Partition in place around the chosen bin boundary
+        let primitives = &mut self.primitives[range];
+        let mut left_count = 0;
+        for i in 0..primitives.len() {
+            if bin_of(centroids[primitives[i] as usize], axis, &centroid_bounds) < split {
+                primitives.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+        if left_count == 0 || left_count == count {
+            return None;
+        }
+        let left = self.nodes.len();
+        self.nodes.push(BvhNode { bounds: Aabb::empty(), first: first as u32, count: left_count as u32 });
+        self.nodes.push(BvhNode { bounds: Aabb::empty(), first: (first + left_count) as u32, count: (count - left_count) as u32 });
+        self.nodes[node].first = left as u32;
+        self.nodes[node].count = 0;
+        Some((left, left + 1))
+    }
+
+    /* (axis, first bin of the right side, summed area times count of both sides) of the cheapest split */
+    fn find_split(&self, primitives: &[u32], bounds: &[Aabb], centroids: &[DVec3], centroid_bounds: &Aabb) -> Option<(usize, usize, f64)> {
+        let mut result: Option<(usize, usize, f64)> = None;
+        let extent = centroid_bounds.get_size();
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+            let mut bin_bounds = [Aabb::empty(); BIN_COUNT];
+            let mut bin_counts = [0usize; BIN_COUNT];
+            for primitive in primitives {
+                let bin = bin_of(centroids[*primitive as usize], axis, centroid_bounds);
+                bin_bounds[bin] = bin_bounds[bin].union(&bounds[*primitive as usize]);
+                bin_counts[bin] += 1;
+            }
+            // Costs of everything left of each boundary, then add the right side sweeping back
+            let mut left_costs = [0.0; BIN_COUNT];
+            let mut left_bounds = Aabb::empty();
+            let mut left_count = 0;
+            for split in 1..BIN_COUNT {
+                left_bounds = left_bounds.union(&bin_bounds[split - 1]);
+                left_count += bin_counts[split - 1];
+                left_costs[split] = left_bounds.get_surface_area() * left_count as f64;
+            }
+            let mut right_bounds = Aabb::empty();
+            let mut right_count = 0;
+            for split in (1..BIN_COUNT).rev() {
+                right_bounds = right_bounds.union(&bin_bounds[split]);
+                right_count += bin_counts[split];
+                if right_count == 0 || right_count == primitives.len() {
+                    continue;
+                }
+                let cost = left_costs[split] + right_bounds.get_surface_area() * right_count as f64;
+                if result.is_none_or(|(_, _, best)| cost < best) {
+                    result = Some((axis, split, cost));
+                }
+            }
+        }
+        result
+    }
+
+    /*
+     * The closest primitive hit before max_distance. intersect gives the distance along ray
+     * to a primitive, or None if it is missed.
+     */
+    pub fn closest_hit(&self, ray: &Ray, max_distance: f64, mut intersect: impl FnMut(usize) -> Option<f64>) -> Option<(usize, f64)> {
+        let mut result: Option<(usize, f64)> = None;
+        let mut closest = max_distance;
+        self.traverse(ray, max_distance, |primitive| {
+            if let Some(distance) = intersect(primitive) {
+                if distance < closest {
+                    closest = distance;
+                    result = Some((primitive, distance));
+                }
+            }
+            closest
+        });
+        result
+    }
+
+    /* Any primitive hit before max_distance, stops at the first one found */
+    pub fn any_hit(&self, ray: &Ray, max_distance: f64, mut intersect: impl FnMut(usize) -> Option<f64>) -> Option<usize> {
+        let mut result = None;
+        self.traverse(ray, max_distance, |primitive| {
+            match intersect(primitive) {
+                Some(distance) if distance < max_distance => {
+                    result = Some(primitive);
+                    // Nothing can be closer than the origin, which ends the traversal
+                    -1.0
+                }
+                _ => max_distance,
+            }
+        });
+        result
+    }
+
+    /*
+     * Visits the leaves ray passes through before max_distance, nearest first. visit is called for
+     * every primitive in them and returns how far along the ray anything is still wanted.
+     */
+    fn traverse(&self, ray: &Ray, max_distance: f64, mut visit: impl FnMut(usize) -> f64) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let origin = ray.get_origin();
+        let inverse_direction = ray.get_direction().recip();
+        let mut limit = max_distance;
+        let mut stack: Vec<(usize, f64)> = Vec::with_capacity(64);
+        if let Some(distance) = self.nodes[0].bounds.intersect_inverse(origin, inverse_direction, limit) {
+            stack.push((0, distance));
+        }
+        while let Some((node, distance)) = stack.pop() {
+            if distance > limit {
+                continue;
+            }
+            let node = &self.nodes[node];
+            if node.count > 0 {
+                let first = node.first as usize;
+                for primitive in &self.primitives[first..first + node.count as usize] {
+                    limit = limit.min(visit(*primitive as usize));
+                    if limit < 0.0 {
+                        return;
+                    }
+                }
+                continue;
+            }
+            let left = node.first as usize;
+            let hits = [left, left + 1].map(|child| {
+                self.nodes[child].bounds.intersect_inverse(origin, inverse_direction, limit).map(|distance| (child, distance))
+            });
+            match hits {
+                [Some(left), Some(right)] => {
+                    // The nearer child goes on top
+                    let (near, far) = if left.1 <= right.1 { (left, right) } else { (right, left) };
+                    stack.push(far);
+                    stack.push(near);
+                }
+                [Some(hit), None] | [None, Some(hit)] => stack.push(hit),
+                [None, None] => (),
+            }
+        }
+    }
+}
+
+fn bin_of(centroid: DVec3, axis: usize, centroid_bounds: &Aabb) -> usize {
+    let min = centroid_bounds.get_min()[axis];
+    let extent = centroid_bounds.get_max()[axis] - min;
+    let bin = ((centroid[axis] - min) / extent * BIN_COUNT as f64) as usize;
+    bin.min(BIN_COUNT - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::resources::asset_paths::{find_asset, AssetSource};
+    use crate::resources::mesh::MeshData;
+    use crate::resources::mesh_loader::LoadProgress;
+    use crate::resources::parser::parse_mesh_data;
+    use crate::util::test_util;
+
+    /* Small deterministic generator so failures can be reproduced */
+    struct Random(u64);
+    impl Random {
+        fn next(&mut self) -> f64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 11) as f64 / (1u64 << 53) as f64
+        }
+        fn next_vec(&mut self) -> DVec3 {
+            DVec3::new(self.next(), self.next(), self.next()) * 2.0 - 1.0
+        }
+    }
+
+    struct Triangles(Vec<[DVec3; 3]>);
+    impl Triangles {
+        fn from_data(data: &MeshData) -> Self {
+            let size = data.shader.get_vertex_size();
+            let position = |index: u32| {
+                let start = index as usize * size;
+                DVec3::new(data.vertexes[start] as f64, data.vertexes[start + 1] as f64, data.vertexes[start + 2] as f64)
+            };
+            Self(data.indexes.as_ref().unwrap().chunks_exact(3).map(|t| [position(t[0]), position(t[1]), position(t[2])]).collect())
+        }
+        fn build(&self) -> Bvh {
+            Bvh::build(&self.0.iter().map(|triangle| Aabb::from_points(*triangle)).collect::<Vec<_>>())
+        }
+        fn intersect(&self, ray: &Ray, triangle: usize) -> Option<f64> {
+            let [a, b, c] = self.0[triangle];
+            ray.intersect_triangle(a, b, c)
+        }
+        fn brute_force(&self, ray: &Ray) -> Option<(usize, f64)> {
+            let mut result: Option<(usize, f64)> = None;
+            for triangle in 0..self.0.len() {
+                if let Some(distance) = self.intersect(ray, triangle) {
+                    if result.is_none_or(|(_, closest)| distance < closest) {
+                        result = Some((triangle, distance));
+                    }
+                }
+            }
+            result
+        }
+    }
+
+    /* Rays from around the mesh aimed at points inside its bounds */
+    fn rays(bounds: &Aabb, count: usize, random: &mut Random) -> Vec<Ray> {
+        let radius = bounds.get_size().length();
+        (0..count).map(|_| {
+            let origin = bounds.get_center() + random.next_vec().normalize() * radius;
+            let target = bounds.get_center() + random.next_vec() * bounds.get_size() * 0.5;
+            Ray::new(origin, (target - origin).normalize())
+        }).collect()
+    }
+
+    fn check_against_brute_force(triangles: &Triangles, ray_count: usize) {
+        let bvh = triangles.build();
+        let mut random = Random(7);
+        let mut hits = 0;
+        for ray in rays(&bvh.get_bounds(), ray_count, &mut random) {
+            let expected = triangles.brute_force(&ray);
+            let result = bvh.closest_hit(&ray, f64::INFINITY, |triangle| triangles.intersect(&ray, triangle));
+            // Another triangle may be hit at the exact same distance
+            assert_eq!(result.map(|(_, distance)| distance), expected.map(|(_, distance)| distance));
+            let any = bvh.any_hit(&ray, f64::INFINITY, |triangle| triangles.intersect(&ray, triangle));
+            assert_eq!(any.is_some(), expected.is_some());
+            if let Some((_, distance)) = expected {
+                hits += 1;
+                assert!(bvh.any_hit(&ray, distance * 0.999, |triangle| triangles.intersect(&ray, triangle)).is_none());
+                assert_eq!(bvh.closest_hit(&ray, distance * 0.999, |triangle| triangles.intersect(&ray, triangle)), None);
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    fn parse(data: Vec<u8>) -> Triangles {
+        Triangles::from_data(&parse_mesh_data(data, &LoadProgress::new()).unwrap())
+    }
+
+    fn icosphere() -> Triangles {
+        Triangles::from_data(&test_util::icosphere())
+    }
+
+    /* Isn't in the repository, it has to be put in one of the asset roots */
+    fn gargoyle() -> Triangles {
+        match find_asset("meshes/gargoyle.ply") {
+            Ok(AssetSource::File(path)) => parse(std::fs::read(path).unwrap()),
+            _ => panic!("meshes/gargoyle.ply is not in the asset roots"),
+        }
+    }
+
+    #[test]
+    fn icosphere_matches_brute_force() {
+        check_against_brute_force(&icosphere(), 500);
+    }
+
+    /* cargo test gargoyle_matches_brute_force -- --ignored */
+    #[test]
+    #[ignore]
+    fn gargoyle_matches_brute_force() {
+        check_against_brute_force(&gargoyle(), 500);
+    }
+
+    #[test]
+    fn random_triangles_match_brute_force() {
+        let mut random = Random(3);
+        let triangles = Triangles((0..2000).map(|_| {
+            let center = random.next_vec() * 10.0;
+            [center + random.next_vec(), center + random.next_vec(), center + random.next_vec()]
+        }).collect());
+        let bvh = triangles.build();
+        assert!(bvh.get_node_count() > 1);
+        check_against_brute_force(&triangles, 1000);
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        let empty = Bvh::build(&[]);
+        assert!(empty.is_empty());
+        let ray = Ray::new(DVec3::Z, DVec3::NEG_Z);
+        assert_eq!(empty.closest_hit(&ray, f64::INFINITY, |_| Some(1.0)), None);
+
+        // Everything at the same place can't be split
+        let same = Bvh::build(&vec![Aabb::new(DVec3::splat(-1.0), DVec3::ONE); 100]);
+        assert_eq!(same.get_node_count(), 1);
+        assert_eq!(same.closest_hit(&ray, f64::INFINITY, |primitive| Some(primitive as f64 + 1.0)), Some((0, 1.0)));
+    }
+
+    /* cargo test --release bvh_benchmark -- --ignored --nocapture, needs the gargoyle like gargoyle_matches_brute_force */
+    #[test]
+    #[ignore]
+    fn bvh_benchmark() {
+        let triangles = gargoyle();
+        let start = Instant::now();
+        let bvh = triangles.build();
+        println!("gargoyle: {} triangles, {} nodes built in {:?}", triangles.0.len(), bvh.get_node_count(), start.elapsed());
+
+        let rays = rays(&bvh.get_bounds(), 2000, &mut Random(11));
+        let start = Instant::now();
+        let brute_hits = rays.iter().filter(|ray| triangles.brute_force(ray).is_some()).count();
+        let brute_time = start.elapsed();
+        let start = Instant::now();
+        let bvh_hits = rays.iter()
+            .filter(|ray| bvh.closest_hit(ray, f64::INFINITY, |triangle| triangles.intersect(ray, triangle)).is_some())
+            .count();
+        let bvh_time = start.elapsed();
+        assert_eq!(brute_hits, bvh_hits);
+        println!("{} rays: brute force {:?}, bvh {:?} ({:.1}x)",
+            rays.len(), brute_time, bvh_time, brute_time.as_secs_f64() / bvh_time.as_secs_f64().max(1e-9));
+    }
+}
@@ -2,54 +2,92 @@ use std::f32::consts::PI;
 
 use glam::*;
 
+use super::bounds::BoundingSphere;
+use super::ray::Ray;
+
+const MIN_NEAR: f32 = 0.000001;
+/* Largest far / near the depth buffer is trusted with */
+const MAX_DEPTH_RATIO: f64 = 10000.0;
+
+
+/*
+ * @brief
+ * The camera keeps its position in world space using f64. Nothing is sent to
+ * the gpu in world space; objects are first rebased so that the camera sits at
+ * the origin (a floating origin) and only then converted to f32.
+ */
 pub struct Camera {
-   position: Vec3,
-   rotation: Quat,
+   position: DVec3,
+   rotation: DQuat,
    resolution: Vec2,
    fov_y: f32,
+   movement_mode: MovementMode,
+   speed: f64,
+   min_speed: f64,
+   max_speed: f64,
+   adaptive_factor: f64,
+   near: f32,
+   far: f32,
+   /* Whether the scene moves near and far to fit around the objects every frame */
+   auto_clip: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MovementMode {
+    /* Moves the same distance every frame */
+    Fixed,
+    /* Moves a fraction of the distance to the nearest surface every frame */
+    Adaptive,
 }
 
 impl Camera {
     pub fn new() -> Self {
         Self {
-            position: Vec3::new(0.0, 0.0, -3.0),
-            rotation: Quat::default(),
+            position: DVec3::new(0.0, 0.0, 3.0),
+            rotation: DQuat::default(),
             resolution: Vec2::new(1920.0, 1080.0),
             fov_y: PI / 2.0,
+            movement_mode: MovementMode::Fixed,
+            speed: 0.01,
+            min_speed: 0.000001,
+            max_speed: 0.1,
+            adaptive_factor: 0.02,
+            near: 0.1,
+            far: 1000.0,
+            auto_clip: true,
         }
     }
 
-    pub fn get_position(&self) -> Vec3 {
+    pub fn get_position(&self) -> DVec3 {
         self.position
     }
-    pub fn get_mut_position(&mut self) -> &mut Vec3 {
+    pub fn get_mut_position(&mut self) -> &mut DVec3 {
         &mut self.position
     }
-    pub fn set_position(&mut self, value: Vec3) {
+    pub fn set_position(&mut self, value: DVec3) {
         self.position = value;
     }
-    pub fn change_position(&mut self, value: Vec3) {
+    pub fn change_position(&mut self, value: DVec3) {
         self.position += value;
     }
 
-    pub fn change_relative_position(&mut self, offset: Vec3) {
-        let offset = glam::Vec4::new(offset.x, offset.y, offset.z, 1.0);
-        let offset = self.get_rotation_matrix().inverse() * offset;
-        self.change_position(offset.xyz());
+    pub fn change_relative_position(&mut self, offset: DVec3) {
+        let offset = self.rotation.inverse() * offset;
+        self.change_position(offset);
     }
 
-    pub fn get_rotation_quat(&self) -> Quat {
+    pub fn get_rotation_quat(&self) -> DQuat {
         self.rotation
     }
 
-    pub fn get_mut_rotation_quat(&mut self) -> &mut Quat {
+    pub fn get_mut_rotation_quat(&mut self) -> &mut DQuat {
         &mut self.rotation
     }
-    
-    pub fn set_rotation_quat(&mut self, value: Quat) {
+
+    pub fn set_rotation_quat(&mut self, value: DQuat) {
         self.rotation = value;
     }
-    pub fn rotate_camera(&mut self, value: Quat) {
+    pub fn rotate_camera(&mut self, value: DQuat) {
         self.rotation *= value;
     }
 
@@ -67,25 +105,231 @@ impl Camera {
         self.fov_y = value;
     }
 
-    pub fn pan_camera(&mut self, offset: Vec2) {
-        let rotation = Quat::from_axis_angle(self.get_side_vec(), offset.y) *
-            Quat::from_axis_angle(self.get_up_vec(), offset.x);
+    pub fn get_near(&self) -> f32 {
+        self.near
+    }
+    pub fn get_mut_near(&mut self) -> &mut f32 {
+        &mut self.near
+    }
+    pub fn get_far(&self) -> f32 {
+        self.far
+    }
+    pub fn get_mut_far(&mut self) -> &mut f32 {
+        &mut self.far
+    }
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.far = far.max(MIN_NEAR * 2.0);
+        self.near = near.clamp(MIN_NEAR, self.far * 0.5);
+    }
+
+    pub fn is_auto_clip(&self) -> bool {
+        self.auto_clip
+    }
+    pub fn get_mut_auto_clip(&mut self) -> &mut bool {
+        &mut self.auto_clip
+    }
+
+    /*
+     * Pulls near and far in as tight as spheres allow, which keeps the depth precision where the objects are.
+     * near never drops below far / MAX_DEPTH_RATIO, so parts of objects right in front of the camera may be clipped.
+     */
+    pub fn fit_clip_planes(&mut self, spheres: &[BoundingSphere]) {
+        if spheres.is_empty() {
+            return;
+        }
+        let mut closest = f64::INFINITY;
+        let mut furthest: f64 = 0.0;
+        for sphere in spheres {
+            // Along the view direction, which is -z in view space
+            let distance = -(self.rotation * (sphere.get_center() - self.position)).z;
+            closest = closest.min(distance - sphere.get_radius());
+            furthest = furthest.max(distance + sphere.get_radius());
+        }
+        if furthest <= 0.0 {
+            // Everything is behind the camera
+            return;
+        }
+        let far = furthest * 1.01;
+        let near = (closest * 0.99).max(far / MAX_DEPTH_RATIO);
+        self.set_clip_planes(near as f32, far as f32);
+    }
+
+    /* Moves back along the view direction until sphere fills the view, the rotation is kept */
+    pub fn frame(&mut self, sphere: &BoundingSphere) {
+        let aspect = (self.resolution.x / self.resolution.y) as f64;
+        let half_fov_y = self.fov_y as f64 / 2.0;
+        let half_fov_x = (half_fov_y.tan() * aspect).atan();
+        let radius = sphere.get_radius().max(MIN_NEAR as f64);
+        let distance = radius / half_fov_y.min(half_fov_x).sin();
+        let forward = self.rotation.inverse() * DVec3::NEG_Z;
+        self.position = sphere.get_center() - forward * distance;
+    }
+
+    pub fn get_movement_mode(&self) -> MovementMode {
+        self.movement_mode
+    }
+    pub fn get_mut_movement_mode(&mut self) -> &mut MovementMode {
+        &mut self.movement_mode
+    }
+    pub fn set_movement_mode(&mut self, value: MovementMode) {
+        self.movement_mode = value;
+    }
+
+    pub fn get_mut_speed(&mut self) -> &mut f64 {
+        &mut self.speed
+    }
+    pub fn get_mut_min_speed(&mut self) -> &mut f64 {
+        &mut self.min_speed
+    }
+    pub fn get_mut_max_speed(&mut self) -> &mut f64 {
+        &mut self.max_speed
+    }
+    pub fn get_mut_adaptive_factor(&mut self) -> &mut f64 {
+        &mut self.adaptive_factor
+    }
+
+    /*
+     * Distance to move this frame. distance is the distance estimate to the
+     * nearest surface and is only used when moving adaptively.
+     */
+    pub fn get_speed(&self, distance: Option<f64>) -> f64 {
+        match (self.movement_mode, distance) {
+            (MovementMode::Adaptive, Some(distance)) => {
+                (distance * self.adaptive_factor).clamp(self.min_speed, self.max_speed.max(self.min_speed))
+            }
+            _ => self.speed,
+        }
+    }
+
+    pub fn pan_camera(&mut self, offset: DVec2) {
+        let rotation = DQuat::from_axis_angle(self.get_side_vec(), offset.y) *
+            DQuat::from_axis_angle(self.get_up_vec(), offset.x);
         self.rotate_camera(rotation.normalize());
     }
 
-    pub fn get_rotation_matrix(&self) -> Mat4 {
-        Mat4::from_quat(self.rotation)
+    pub fn get_rotation_matrix(&self) -> DMat4 {
+        DMat4::from_quat(self.rotation)
     }
 
+    pub fn get_projection_matrix(&self) -> Mat4 {
+        Mat4::perspective_rh(self.fov_y, self.resolution.x / self.resolution.y, self.near, self.far)
+    }
+
+    /* Matrix used for things that are already relative to the camera */
     pub fn get_transformation_matrix(&self) -> Mat4 {
-        Mat4::perspective_rh(self.fov_y, self.resolution.x / self.resolution.y, 0.1, 1000.0) * Mat4::from_quat(self.rotation) * Mat4::from_translation(self.position)
+        self.get_projection_matrix() * self.get_rotation_matrix().as_mat4()
+    }
+
+    /*
+     * Rebases model onto the camera before dropping to f32 so the large
+     * world space translation cancels out while still in double precision.
+     */
+    pub fn get_object_matrix(&self, model: &DMat4) -> Mat4 {
+        let mut model = *model;
+        model.w_axis -= self.position.extend(0.0);
+        self.get_projection_matrix() * (self.get_rotation_matrix() * model).as_mat4()
+    }
+
+    /* Position of the camera in the local space of model */
+    pub fn get_local_position(&self, model: &DMat4) -> DVec3 {
+        model.inverse().transform_point3(self.position)
+    }
+
+    /* Ray from the camera through a point given in pixels from the top left of the viewport */
+    pub fn get_ray(&self, screen_position: Vec2) -> Ray {
+        let x = (2.0 * screen_position.x / self.resolution.x - 1.0) as f64;
+        let y = (1.0 - 2.0 * screen_position.y / self.resolution.y) as f64;
+        let tan_fov = (self.fov_y as f64 / 2.0).tan();
+        let aspect = (self.resolution.x / self.resolution.y) as f64;
+        let direction = DVec3::new(x * tan_fov * aspect, y * tan_fov, -1.0);
+        Ray::new(self.position, (self.rotation.inverse() * direction).normalize())
+    }
+
+    /* Position in pixels from the top left of the viewport, None if point is behind the camera */
+    pub fn world_to_screen(&self, point: DVec3) -> Option<Vec2> {
+        let relative = (self.rotation * (point - self.position)).as_vec3();
+        let clip = self.get_projection_matrix() * relative.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.xy() / clip.w;
+        Some(Vec2::new((ndc.x + 1.0) * 0.5 * self.resolution.x, (1.0 - ndc.y) * 0.5 * self.resolution.y))
     }
 
-    fn get_side_vec(&self) -> Vec3 {
-        (self.get_rotation_matrix().inverse() * Vec4::X).xyz()
+    /* Roughly how many pixels tall sphere appears, infinite with the camera inside it */
+    pub fn get_screen_size(&self, sphere: &BoundingSphere) -> f64 {
+        let distance = self.position.distance(sphere.get_center());
+        if distance <= sphere.get_radius() {
+            return f64::INFINITY;
+        }
+        let tan_fov = (self.fov_y as f64 / 2.0).tan();
+        sphere.get_radius() / (distance * tan_fov) * self.resolution.y as f64
+    }
+
+    fn get_side_vec(&self) -> DVec3 {
+        self.rotation.inverse() * DVec3::X
+    }
+
+    fn get_up_vec(&self) -> DVec3 {
+        self.rotation.inverse() * DVec3::Y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{DQuat, DVec3, Vec2};
+
+    use super::*;
+
+    #[test]
+    fn frame() {
+        let mut camera = Camera::new();
+        camera.set_resolution(Vec2::new(200.0, 100.0));
+        camera.set_rotation_quat(DQuat::from_rotation_y(0.3));
+        let sphere = BoundingSphere::new(DVec3::new(5.0, 1.0, -2.0), 2.0);
+        camera.frame(&sphere);
+
+        assert_eq!(camera.get_rotation_quat(), DQuat::from_rotation_y(0.3));
+        let center = camera.get_ray(Vec2::new(100.0, 50.0));
+        let distance = camera.get_position().distance(sphere.get_center());
+        assert!(center.at(distance).abs_diff_eq(sphere.get_center(), 1e-9));
+        // The view is wider than tall, so the sphere touches the top edge
+        let top = camera.get_ray(Vec2::new(100.0, 0.0));
+        let to_center = sphere.get_center() - top.get_origin();
+        let closest = to_center - top.get_direction() * to_center.dot(top.get_direction());
+        assert!((closest.length() - sphere.get_radius()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fit_clip_planes() {
+        let mut camera = Camera::new();
+        camera.set_position(DVec3::ZERO);
+        camera.fit_clip_planes(&[
+            BoundingSphere::new(DVec3::new(0.0, 0.0, -10.0), 1.0),
+            BoundingSphere::new(DVec3::new(3.0, 0.0, -50.0), 5.0),
+        ]);
+        assert!(camera.get_near() <= 9.0 && camera.get_near() > 8.0);
+        assert!(camera.get_far() >= 55.0 && camera.get_far() < 60.0);
+
+        // Inside an object near is only limited by the depth precision
+        camera.fit_clip_planes(&[BoundingSphere::new(DVec3::ZERO, 100.0)]);
+        assert!(camera.get_near() > 0.0);
+        assert!(camera.get_far() / camera.get_near() <= MAX_DEPTH_RATIO as f32 * 1.01);
+
+        // Nothing in front keeps the planes
+        let (near, far) = (camera.get_near(), camera.get_far());
+        camera.fit_clip_planes(&[BoundingSphere::new(DVec3::new(0.0, 0.0, 10.0), 1.0)]);
+        assert_eq!((camera.get_near(), camera.get_far()), (near, far));
     }
 
-    fn get_up_vec(&self) -> Vec3 {
-        (self.get_rotation_matrix().inverse() * Vec4::Y).xyz()
+    #[test]
+    fn screen_size() {
+        let mut camera = Camera::new();
+        camera.set_position(DVec3::ZERO);
+        camera.set_resolution(Vec2::new(200.0, 100.0));
+        // At 90 degrees the view is 2 units tall 1 unit away
+        let size = camera.get_screen_size(&BoundingSphere::new(DVec3::new(0.0, 0.0, -10.0), 1.0));
+        assert!((size - 10.0).abs() < 1e-6);
+        assert_eq!(camera.get_screen_size(&BoundingSphere::new(DVec3::X, 2.0)), f64::INFINITY);
     }
 }
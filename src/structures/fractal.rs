@@ -0,0 +1,94 @@
+use glam::DVec3;
+
+/*
+ * @brief
+ * The fractal formula used by a scene. The distance estimate is evaluated on
+ * the cpu (in f64) so things like the camera can tell how close they are to
+ * the surface.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fractal {
+    Mandelbulb(MandelbulbFractal),
+}
+
+impl Fractal {
+    pub fn default_mandelbulb() -> Self {
+        Self::Mandelbulb(MandelbulbFractal::default())
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Mandelbulb(_) => "Mandelbulb",
+        }
+    }
+
+    /* Lower bound on the distance from point to the surface of the fractal */
+    pub fn distance_estimate(&self, point: DVec3) -> f64 {
+        match self {
+            Self::Mandelbulb(mandelbulb) => mandelbulb.distance_estimate(point),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MandelbulbFractal {
+    power: f64,
+    iterations: u32,
+    bailout: f64,
+}
+
+impl MandelbulbFractal {
+    pub fn new(power: f64, iterations: u32, bailout: f64) -> Self {
+        Self {
+            power,
+            iterations,
+            bailout,
+        }
+    }
+
+    pub fn get_power(&self) -> f64 {
+        self.power
+    }
+    pub fn get_mut_power(&mut self) -> &mut f64 {
+        &mut self.power
+    }
+    pub fn get_iterations(&self) -> u32 {
+        self.iterations
+    }
+    pub fn get_mut_iterations(&mut self) -> &mut u32 {
+        &mut self.iterations
+    }
+    pub fn get_bailout(&self) -> f64 {
+        self.bailout
+    }
+    pub fn get_mut_bailout(&mut self) -> &mut f64 {
+        &mut self.bailout
+    }
+
+    pub fn distance_estimate(&self, point: DVec3) -> f64 {
+        let mut z = point;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > self.bailout || r == 0.0 {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z = zr * DVec3::new(theta.sin() * phi.cos(), phi.sin() * theta.sin(), theta.cos()) + point;
+        }
+        if r == 0.0 {
+            return 0.0;
+        }
+        (0.5 * r.ln() * r / dr).max(0.0)
+    }
+}
+
+impl Default for MandelbulbFractal {
+    fn default() -> Self {
+        Self::new(8.0, 16, 2.0)
+    }
+}
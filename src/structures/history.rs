@@ -0,0 +1,492 @@
+use glam::{DQuat, DVec3};
+
+use crate::resources::container::ContainerRef;
+use crate::resources::material::{Material, MaterialParameters};
+use crate::resources::object::Object;
+use crate::util::error::Error;
+
+use super::fractal::Fractal;
+use super::light::DirectionalLight;
+use super::scene::Scene;
+use super::transform::Transform;
+
+/*
+ * @brief
+ * A reversible edit of the scene.
+ * Commands are recorded after the edit was made, so apply is only used to redo them.
+ */
+pub enum Command {
+    SetTransform {
+        object: ContainerRef<Object>,
+        old: Transform,
+        new: Transform,
+    },
+    SetCamera {
+        old: (DVec3, DQuat),
+        new: (DVec3, DQuat),
+    },
+    SetVisible {
+        object: ContainerRef<Object>,
+        old: bool,
+        new: bool,
+    },
+    Rename {
+        old_name: String,
+        new_name: String,
+    },
+    /* Parents are kept as references so the command survives them being renamed */
+    Reparent {
+        object: ContainerRef<Object>,
+        old_parent: Option<ContainerRef<Object>>,
+        new_parent: Option<ContainerRef<Object>>,
+        old_transform: Transform,
+        new_transform: Transform,
+    },
+    /* children holds the transforms the children had before being moved to the parent of the object */
+    Delete {
+        object: ContainerRef<Object>,
+        index: usize,
+        children: Vec<(ContainerRef<Object>, Transform)>,
+    },
+    /* An object that was added to the scene at index */
+    Add {
+        object: ContainerRef<Object>,
+        index: usize,
+    },
+    /* Edits made together as one step, reverted last first */
+    Group {
+        description: String,
+        commands: Vec<Command>,
+    },
+    SetMaterial {
+        material: ContainerRef<Material>,
+        old: MaterialParameters,
+        new: MaterialParameters,
+    },
+    SetLight {
+        old: DirectionalLight,
+        new: DirectionalLight,
+    },
+    SetFractal {
+        old: Option<Fractal>,
+        new: Option<Fractal>,
+    },
+}
+
+impl Command {
+    pub fn get_description(&self) -> String {
+        match self {
+            Command::SetTransform { object, .. } => format!("Transform {}", object.lock().unwrap().get_name()),
+            Command::SetCamera { .. } => String::from("Move camera"),
+            Command::SetVisible { object, new, .. } => {
+                let action = if *new { "Show" } else { "Hide" };
+                format!("{} {}", action, object.lock().unwrap().get_name())
+            }
+            Command::Rename { old_name, new_name } => format!("Rename {} to {}", old_name, new_name),
+            Command::Reparent { object, .. } => format!("Reparent {}", object.lock().unwrap().get_name()),
+            Command::Delete { object, .. } => format!("Delete {}", object.lock().unwrap().get_name()),
+            Command::Add { object, .. } => format!("Add {}", object.lock().unwrap().get_name()),
+            Command::Group { description, .. } => description.clone(),
+            Command::SetMaterial { material, .. } => format!("Edit {}", material.lock().unwrap().get_name()),
+            Command::SetLight { .. } => String::from("Edit light"),
+            Command::SetFractal { .. } => String::from("Edit fractal"),
+        }
+    }
+
+    pub fn apply(&self, scene: &mut Scene) -> Result<(), Error> {
+        match self {
+            Command::SetTransform { object, new, .. } => object.lock().unwrap().set_transform(*new),
+            Command::SetCamera { new, .. } => Self::set_camera(scene, *new),
+            Command::SetVisible { object, new, .. } => object.lock().unwrap().set_visible(*new),
+            Command::Rename { old_name, new_name } => scene.rename_object(old_name, new_name)?,
+            Command::Reparent { object, new_parent, new_transform, .. } => Self::set_parent(object, new_parent, *new_transform),
+            Command::Delete { object, .. } => {
+                let name = String::from(object.lock().unwrap().get_name());
+                scene.remove_object(&name)?;
+            }
+            Command::Add { object, index } => scene.insert_object(object.clone(), *index)?,
+            Command::Group { commands, .. } => {
+                for command in commands {
+                    command.apply(scene)?;
+                }
+            }
+            Command::SetMaterial { material, new, .. } => material.lock().unwrap().set_parameters(*new),
+            Command::SetLight { new, .. } => *scene.get_mut_light() = *new,
+            Command::SetFractal { new, .. } => scene.set_fractal(*new),
+        }
+        Ok(())
+    }
+
+    pub fn revert(&self, scene: &mut Scene) -> Result<(), Error> {
+        match self {
+            Command::SetTransform { object, old, .. } => object.lock().unwrap().set_transform(*old),
+            Command::SetCamera { old, .. } => Self::set_camera(scene, *old),
+            Command::SetVisible { object, old, .. } => object.lock().unwrap().set_visible(*old),
+            Command::Rename { old_name, new_name } => scene.rename_object(new_name, old_name)?,
+            Command::Reparent { object, old_parent, old_transform, .. } => Self::set_parent(object, old_parent, *old_transform),
+            Command::Delete { object, index, children } => {
+                scene.insert_object(object.clone(), *index)?;
+                let name = String::from(object.lock().unwrap().get_name());
+                for (child, transform) in children {
+                    let mut child = child.lock().unwrap();
+                    child.set_parent(Some(&name));
+                    child.set_transform(*transform);
+                }
+            }
+            Command::Add { object, .. } => {
+                let name = String::from(object.lock().unwrap().get_name());
+                scene.remove_object(&name)?;
+            }
+            Command::Group { commands, .. } => {
+                for command in commands.iter().rev() {
+                    command.revert(scene)?;
+                }
+            }
+            Command::SetMaterial { material, old, .. } => material.lock().unwrap().set_parameters(*old),
+            Command::SetLight { old, .. } => *scene.get_mut_light() = *old,
+            Command::SetFractal { old, .. } => scene.set_fractal(*old),
+        }
+        Ok(())
+    }
+
+    /* Folds other into self if both edit the same value, so a drag becomes a single step */
+    fn try_merge(&mut self, other: &Command) -> bool {
+        match (self, other) {
+            (Command::SetTransform { object, new, .. }, Command::SetTransform { object: other_object, new: other_new, .. })
+                if ContainerRef::ptr_eq(object, other_object) => {
+                *new = *other_new;
+                true
+            }
+            (Command::SetCamera { new, .. }, Command::SetCamera { new: other_new, .. }) => {
+                *new = *other_new;
+                true
+            }
+            (Command::SetMaterial { material, new, .. }, Command::SetMaterial { material: other_material, new: other_new, .. })
+                if ContainerRef::ptr_eq(material, other_material) => {
+                *new = *other_new;
+                true
+            }
+            (Command::SetLight { new, .. }, Command::SetLight { new: other_new, .. }) => {
+                *new = *other_new;
+                true
+            }
+            (Command::SetFractal { new, .. }, Command::SetFractal { new: other_new, .. }) => {
+                *new = *other_new;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn set_camera(scene: &mut Scene, (position, rotation): (DVec3, DQuat)) {
+        let camera = scene.get_camera_mut();
+        camera.set_position(position);
+        camera.set_rotation_quat(rotation);
+    }
+
+    fn set_parent(object: &ContainerRef<Object>, parent: &Option<ContainerRef<Object>>, transform: Transform) {
+        let parent_name = parent.as_ref().map(|parent| String::from(parent.lock().unwrap().get_name()));
+        let mut object = object.lock().unwrap();
+        object.set_parent(parent_name.as_deref());
+        object.set_transform(transform);
+    }
+}
+
+/*
+ * @brief
+ * Undo and redo stacks of commands.
+ * While the history is not sealed new commands are merged into the last one when possible.
+ */
+pub struct History {
+    undo_stack: Vec<Command>,
+    redo_stack: Vec<Command>,
+    sealed: bool,
+    max_length: usize,
+    /* Deleted objects that can't be brought back anymore, their gl resources have to be freed */
+    discarded_objects: Vec<ContainerRef<Object>>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            sealed: true,
+            max_length: 100,
+            discarded_objects: Vec::new(),
+        }
+    }
+
+    /* Records a command that was already applied */
+    pub fn push(&mut self, command: Command) {
+        for command in self.redo_stack.drain(..) {
+            Self::discard(&mut self.discarded_objects, command, false);
+        }
+        if !self.sealed {
+            if let Some(last) = self.undo_stack.last_mut() {
+                if last.try_merge(&command) {
+                    return;
+                }
+            }
+        }
+        self.undo_stack.push(command);
+        self.sealed = false;
+        while self.undo_stack.len() > self.max_length {
+            let command = self.undo_stack.remove(0);
+            Self::discard(&mut self.discarded_objects, command, true);
+        }
+    }
+
+    /* Ends the current step, the next command won't be merged into it */
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    pub fn undo(&mut self, scene: &mut Scene) -> Result<bool, Error> {
+        self.sealed = true;
+        let command = match self.undo_stack.pop() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        command.revert(scene)?;
+        self.redo_stack.push(command);
+        Ok(true)
+    }
+
+    pub fn redo(&mut self, scene: &mut Scene) -> Result<bool, Error> {
+        self.sealed = true;
+        let command = match self.redo_stack.pop() {
+            Some(command) => command,
+            None => return Ok(false),
+        };
+        command.apply(scene)?;
+        self.undo_stack.push(command);
+        Ok(true)
+    }
+
+    /* Oldest command first */
+    pub fn get_undo_stack(&self) -> &Vec<Command> {
+        &self.undo_stack
+    }
+
+    /* Next command to redo last */
+    pub fn get_redo_stack(&self) -> &Vec<Command> {
+        &self.redo_stack
+    }
+
+    pub fn clear(&mut self) {
+        for command in self.undo_stack.drain(..) {
+            Self::discard(&mut self.discarded_objects, command, true);
+        }
+        for command in self.redo_stack.drain(..) {
+            Self::discard(&mut self.discarded_objects, command, false);
+        }
+        self.sealed = true;
+    }
+
+    /* Deleted objects that can be destroyed now, see Scene::destroy_object */
+    pub fn take_discarded_objects(&mut self) -> Vec<ContainerRef<Object>> {
+        std::mem::take(&mut self.discarded_objects)
+    }
+
+    /*
+     * A delete that is dropped from the undo stack stays applied so the object is gone for good.
+     * Dropped from the redo stack it was reverted and the object is back in the scene.
+     * Adds are the other way around.
+     */
+    fn discard(discarded_objects: &mut Vec<ContainerRef<Object>>, command: Command, applied: bool) {
+        match command {
+            Command::Delete { object, .. } if applied => discarded_objects.push(object),
+            Command::Add { object, .. } if !applied => discarded_objects.push(object),
+            Command::Group { commands, .. } => {
+                for command in commands {
+                    Self::discard(discarded_objects, command, applied);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::container::Container;
+    use crate::util::test_util::object;
+
+    fn moved(x: f64) -> Transform {
+        Transform::from_position(DVec3::new(x, 0.0, 0.0))
+    }
+
+    /* Moves object to x the way the ui does, then records it */
+    fn move_to(history: &mut History, object: &ContainerRef<Object>, x: f64) {
+        let old = *object.lock().unwrap().get_transform();
+        object.lock().unwrap().set_transform(moved(x));
+        history.push(Command::SetTransform { object: object.clone(), old, new: moved(x) });
+    }
+
+    fn get_x(object: &ContainerRef<Object>) -> f64 {
+        object.lock().unwrap().get_transform().get_position().x
+    }
+
+    fn contains(objects: &[ContainerRef<Object>], object: &ContainerRef<Object>) -> bool {
+        objects.iter().any(|other| ContainerRef::ptr_eq(other, object))
+    }
+
+    #[test]
+    fn merges_until_sealed() {
+        let (first, second) = (object("first"), object("second"));
+        let mut history = History::new();
+        move_to(&mut history, &first, 1.0);
+        move_to(&mut history, &first, 2.0);
+        move_to(&mut history, &first, 3.0);
+        assert_eq!(history.get_undo_stack().len(), 1);
+        match &history.get_undo_stack()[0] {
+            Command::SetTransform { old, new, .. } => assert_eq!((*old, *new), (Transform::new(), moved(3.0))),
+            _ => panic!("expected a transform"),
+        }
+
+        // Another object is another step
+        move_to(&mut history, &second, 1.0);
+        assert_eq!(history.get_undo_stack().len(), 2);
+        // and so is the next drag of the same object once sealed
+        history.seal();
+        move_to(&mut history, &second, 2.0);
+        assert_eq!(history.get_undo_stack().len(), 3);
+        // Commands that can't merge don't
+        history.push(Command::SetVisible { object: first.clone(), old: true, new: false });
+        history.push(Command::SetVisible { object: first.clone(), old: false, new: true });
+        assert_eq!(history.get_undo_stack().len(), 5);
+    }
+
+    #[test]
+    fn undo_and_redo_in_order() {
+        let mut scene = Scene::new(Container::new());
+        let object = object("moved");
+        let mut history = History::new();
+        for x in [1.0, 2.0, 3.0] {
+            move_to(&mut history, &object, x);
+            history.seal();
+        }
+
+        assert!(history.undo(&mut scene).unwrap());
+        assert_eq!(get_x(&object), 2.0);
+        assert!(history.undo(&mut scene).unwrap());
+        assert_eq!(get_x(&object), 1.0);
+        assert_eq!(history.get_redo_stack().len(), 2);
+        assert!(history.redo(&mut scene).unwrap());
+        assert_eq!(get_x(&object), 2.0);
+        assert!(history.undo(&mut scene).unwrap());
+        assert!(history.undo(&mut scene).unwrap());
+        assert_eq!(get_x(&object), 0.0);
+        assert!(!history.undo(&mut scene).unwrap());
+        for x in [1.0, 2.0, 3.0] {
+            assert!(history.redo(&mut scene).unwrap());
+            assert_eq!(get_x(&object), x);
+        }
+        assert!(!history.redo(&mut scene).unwrap());
+
+        // An undo seals, the edit after it isn't merged into the one before
+        history.undo(&mut scene).unwrap();
+        move_to(&mut history, &object, 5.0);
+        assert_eq!(history.get_undo_stack().len(), 3);
+    }
+
+    #[test]
+    fn push_drops_the_redo_stack() {
+        let mut scene = Scene::new(Container::new());
+        let (added, moved) = (object("added"), object("moved"));
+        let mut history = History::new();
+        scene.insert_object(added.clone(), 0).unwrap();
+        history.push(Command::Add { object: added.clone(), index: 0 });
+        history.seal();
+        move_to(&mut history, &moved, 1.0);
+
+        history.undo(&mut scene).unwrap();
+        history.undo(&mut scene).unwrap();
+        assert!(scene.get_object("added").is_none());
+        assert_eq!(history.get_redo_stack().len(), 2);
+
+        move_to(&mut history, &moved, 2.0);
+        assert!(history.get_redo_stack().is_empty());
+        assert!(!history.redo(&mut scene).unwrap());
+        // The undone add can't come back anymore
+        let discarded = history.take_discarded_objects();
+        assert_eq!(discarded.len(), 1);
+        assert!(contains(&discarded, &added));
+        assert!(history.take_discarded_objects().is_empty());
+    }
+
+    #[test]
+    fn evicts_past_max_length() {
+        let mut scene = Scene::new(Container::new());
+        let (deleted, added, moved) = (object("deleted"), object("added"), object("moved"));
+        let mut history = History::new();
+        history.max_length = 3;
+        scene.insert_object(added.clone(), 0).unwrap();
+        history.push(Command::Delete { object: deleted.clone(), index: 0, children: Vec::new() });
+        history.push(Command::Group {
+            description: String::from("Subdivide"),
+            commands: vec![
+                Command::Add { object: added.clone(), index: 0 },
+                Command::SetVisible { object: moved.clone(), old: true, new: false },
+            ],
+        });
+        history.seal();
+        move_to(&mut history, &moved, 1.0);
+        assert!(history.take_discarded_objects().is_empty());
+
+        // The delete is the oldest, it can't be undone anymore so the object is gone for good
+        history.seal();
+        move_to(&mut history, &moved, 2.0);
+        assert_eq!(history.get_undo_stack().len(), 3);
+        let discarded = history.take_discarded_objects();
+        assert_eq!(discarded.len(), 1);
+        assert!(contains(&discarded, &deleted));
+
+        // The add stays applied when evicted, the object is in the scene
+        history.seal();
+        move_to(&mut history, &moved, 3.0);
+        assert!(history.take_discarded_objects().is_empty());
+        assert!(scene.get_object("added").is_some());
+        assert!(matches!(history.get_undo_stack()[0], Command::SetTransform { .. }));
+    }
+
+    #[test]
+    fn group_is_one_step() {
+        let mut scene = Scene::new(Container::new());
+        let (source, added) = (object("source"), object("added"));
+        scene.insert_object(source.clone(), 0).unwrap();
+        scene.insert_object(added.clone(), 1).unwrap();
+        source.lock().unwrap().set_visible(false);
+        let mut history = History::new();
+        history.push(Command::Group {
+            description: String::from("Subdivide source"),
+            commands: vec![
+                Command::Add { object: added.clone(), index: 1 },
+                Command::SetVisible { object: source.clone(), old: true, new: false },
+            ],
+        });
+        assert_eq!(history.get_undo_stack()[0].get_description(), "Subdivide source");
+
+        history.undo(&mut scene).unwrap();
+        assert!(scene.get_object("added").is_none());
+        assert!(source.lock().unwrap().is_visible());
+        history.redo(&mut scene).unwrap();
+        assert!(ContainerRef::ptr_eq(&scene.get_objects()[1], &added));
+        assert!(!source.lock().unwrap().is_visible());
+
+        // Cleared while undone, the added object is only held by the history
+        history.undo(&mut scene).unwrap();
+        history.clear();
+        let discarded = history.take_discarded_objects();
+        assert_eq!(discarded.len(), 1);
+        assert!(contains(&discarded, &added));
+    }
+}
@@ -0,0 +1,58 @@
+use glam::{DVec3, Vec3};
+
+/*
+ * @brief
+ * The light shading the scene. It is infinitely far away so only its direction matters.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DirectionalLight {
+    /* Points from the surface towards the light */
+    direction: DVec3,
+    color: Vec3,
+    intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: DVec3, color: Vec3, intensity: f32) -> Self {
+        Self {
+            direction: direction.normalize_or_zero(),
+            color,
+            intensity,
+        }
+    }
+
+    pub fn get_direction(&self) -> DVec3 {
+        self.direction
+    }
+    pub fn set_direction(&mut self, value: DVec3) {
+        if value.length_squared() > 0.0 {
+            self.direction = value.normalize();
+        }
+    }
+    pub fn get_color(&self) -> Vec3 {
+        self.color
+    }
+    pub fn set_color(&mut self, value: Vec3) {
+        self.color = value;
+    }
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+    pub fn get_mut_intensity(&mut self) -> &mut f32 {
+        &mut self.intensity
+    }
+    pub fn set_intensity(&mut self, value: f32) {
+        self.intensity = value;
+    }
+
+    /* Color scaled by intensity, as passed to the shaders */
+    pub fn get_radiance(&self) -> Vec3 {
+        self.color * self.intensity
+    }
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self::new(DVec3::new(-1.0, -1.0, 0.0), Vec3::ONE, 1.0)
+    }
+}
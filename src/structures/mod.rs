@@ -1,2 +1,10 @@
 pub mod scene;
 pub mod camera;
+pub mod transform;
+pub mod fractal;
+pub mod ray;
+pub mod bounds;
+pub mod bvh;
+pub mod scene_bvh;
+pub mod history;
+pub mod light;
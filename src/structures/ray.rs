@@ -0,0 +1,95 @@
+use glam::{DMat4, DVec3};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ray {
+    origin: DVec3,
+    direction: DVec3,
+}
+
+impl Ray {
+    pub fn new(origin: DVec3, direction: DVec3) -> Self {
+        Self {
+            origin,
+            direction,
+        }
+    }
+
+    pub fn get_origin(&self) -> DVec3 {
+        self.origin
+    }
+    pub fn get_direction(&self) -> DVec3 {
+        self.direction
+    }
+
+    pub fn at(&self, distance: f64) -> DVec3 {
+        self.origin + self.direction * distance
+    }
+
+    /*
+     * The direction is not normalized again so distances along the
+     * transformed ray match distances along the original one.
+     */
+    pub fn transformed(&self, matrix: &DMat4) -> Self {
+        Self {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+
+    /* Möller–Trumbore intersection. Returns the distance along the ray to the hit */
+    pub fn intersect_triangle(&self, a: DVec3, b: DVec3, c: DVec3) -> Option<f64> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge2.dot(q) * inverse_determinant;
+        if distance > 0.0 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::DVec3;
+
+    use super::Ray;
+
+    #[test]
+    fn intersect_triangle() {
+        let ray = Ray::new(DVec3::new(0.2, 0.2, 5.0), DVec3::new(0.0, 0.0, -1.0));
+        let a = DVec3::new(0.0, 0.0, 0.0);
+        let b = DVec3::new(1.0, 0.0, 0.0);
+        let c = DVec3::new(0.0, 1.0, 0.0);
+        assert_eq!(ray.intersect_triangle(a, b, c), Some(5.0));
+        assert_eq!(ray.intersect_triangle(a + DVec3::X * 2.0, b + DVec3::X * 2.0, c + DVec3::X * 2.0), None);
+
+        let behind = Ray::new(DVec3::new(0.2, 0.2, -5.0), DVec3::new(0.0, 0.0, -1.0));
+        assert_eq!(behind.intersect_triangle(a, b, c), None);
+    }
+
+    #[test]
+    fn transformed() {
+        let ray = Ray::new(DVec3::ZERO, DVec3::X);
+        let matrix = glam::DMat4::from_scale(DVec3::splat(2.0));
+        let transformed = ray.transformed(&matrix);
+        assert_eq!(transformed.get_direction(), DVec3::new(2.0, 0.0, 0.0));
+        assert_eq!(transformed.at(1.0), DVec3::new(2.0, 0.0, 0.0));
+    }
+}
@@ -1,34 +1,190 @@
-use glam::Vec3;
+use glam::{DMat4, Vec3};
 use glow::HasContext;
 
 use crate::{resources::{container::{Container, ContainerRef}, object::Object}, util::error::Error};
 use std::vec::Vec;
+use crate::resources::line_renderer::{box_lines, LineRenderer};
+use crate::resources::mesh::{Mesh, MeshData};
+use crate::resources::mesh_loader::{LoadedMesh, MeshLoader};
+use crate::resources::mesh_subdivide::SubdivisionOptions;
+use crate::resources::primitives::Primitive;
+use crate::resources::resource_file::mesh_dir;
+use crate::resources::shader_gen;
+use crate::util::log::{self, Subsystem};
 
+use super::bounds::{Aabb, BoundingSphere};
 use super::camera::Camera;
+use super::fractal::Fractal;
+use super::history::Command;
+use super::light::DirectionalLight;
+use super::ray::Ray;
+use super::scene_bvh::InstanceBvh;
+use super::transform::Transform;
+
+const PLACEHOLDER_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const BOUNDS_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+
+/* Which objects get their world space bounding box drawn */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BoundsOverlay {
+    Off,
+    Selected,
+    All,
+}
 
 pub struct Scene {
     main_camera: Camera,
     _current_rotation_dir: Vec3,
     container: Container,
     objects: Vec<ContainerRef<Object>>,
+    fractal: Option<Fractal>,
+    light: DirectionalLight,
+    /* Removed objects whose gl resources still need to be freed */
+    removed_objects: Vec<ContainerRef<Object>>,
+    /* Asset files that changed on disk, handled on the next render when gl is available */
+    changed_assets: Vec<String>,
+    mesh_loader: MeshLoader,
+    /* Objects waiting for their mesh, a box is drawn where they will appear */
+    pending_objects: Vec<PendingObject>,
+    /* Loads of mesh files that changed on disk, with the asset file, the old mesh is drawn until they finish */
+    pending_reloads: Vec<(u64, String)>,
+    line_renderer: Option<LineRenderer>,
+    line_renderer_failed: bool,
+    bounds_overlay: BoundsOverlay,
+    /* Subdivisions running on the mesh loader, with the object a copy is added of */
+    pending_subdivisions: Vec<(u64, ContainerRef<Object>)>,
+    /* Edits the scene made on its own, see take_commands */
+    commands: Vec<Command>,
+    /* Bumped when objects are added or removed or a mesh is replaced, see get_pick_key */
+    revision: u64,
+    /* The last bvh pick used and the key it was built at, rebuilt once the key changes */
+    instance_bvh: Option<((u64, u64), InstanceBvh)>,
+    /* Generated meshes to upload and add objects for on the next render, with the object names */
+    pending_primitives: Vec<(String, MeshData)>,
+}
+
+struct PendingObject {
+    load_id: u64,
+    name: String,
+    file_name: String,
+    transform: Transform,
 }
 
 impl Scene {
-    pub fn default(gl: &glow::Context) -> Result<Self, Error> {
-        let container = match Container::default(gl) {
-            Ok(value) => value,
-            Err(err) => return Err(err),
-        };
-        let mut objects = Vec::new();
-        objects.push(container.get_object("gargoyle").unwrap());
-        objects.push(container.get_object("monkey").unwrap());
-        //objects.push(container.get_object("cube").unwrap());
-        Ok(Self {
+    /* An empty scene over container */
+    pub fn new(container: Container) -> Self {
+        Self {
             container,
-            objects,
+            objects: Vec::new(),
             main_camera: Camera::new(),
             _current_rotation_dir: Vec3::new(0.0, 0.0, 1.0),
-        })
+            fractal: Some(Fractal::default_mandelbulb()),
+            light: DirectionalLight::default(),
+            removed_objects: Vec::new(),
+            changed_assets: Vec::new(),
+            mesh_loader: MeshLoader::new(),
+            pending_objects: Vec::new(),
+            pending_reloads: Vec::new(),
+            line_renderer: None,
+            line_renderer_failed: false,
+            bounds_overlay: BoundsOverlay::Off,
+            pending_subdivisions: Vec::new(),
+            pending_primitives: Vec::new(),
+            commands: Vec::new(),
+            revision: 0,
+            instance_bvh: None,
+        }
+    }
+
+    /* The meshes are loaded in the background, the built-in icosphere stands in if none of the files exist */
+    pub fn default(gl: &glow::Context) -> Result<Self, Error> {
+        let mut result = Self::new(Container::default(gl)?);
+        let mut found = false;
+        for name in ["gargoyle", "monkey"] {
+            let file_name = format!("{}.ply", name);
+            match mesh_dir(&file_name) {
+                Ok(_) => {
+                    result.load_object(&file_name, name, Transform::new());
+                    found = true;
+                }
+                // The sample meshes aren't part of a fresh checkout
+                Err(err) if err.is_asset_not_found() => log::info(Subsystem::Resource, format!("Skipping {}: {}", name, err)),
+                Err(err) => log::error(&format!("Could not load {}", name), &err),
+            }
+        }
+        if !found {
+            result.load_object("icosphere.ply", "icosphere", Transform::new());
+        }
+        Ok(result)
+    }
+
+    /*
+     * Loads file_name from the meshes in the asset roots on a worker thread, and adds an object
+     * named object_name using it once that is done. Returns the id of the load, see cancel_load.
+     */
+    pub fn load_object(&mut self, file_name: &str, object_name: &str, transform: Transform) -> u64 {
+        let load_id = self.mesh_loader.load(file_name);
+        self.pending_objects.push(PendingObject {
+            load_id,
+            name: String::from(object_name),
+            file_name: String::from(file_name),
+            transform,
+        });
+        load_id
+    }
+
+    /* Adds an object with a newly generated primitive at the origin, returns its name. It appears on the next render */
+    pub fn add_primitive(&mut self, primitive: &Primitive) -> String {
+        let base = primitive.get_name();
+        let is_used = |candidate: &str| {
+            self.container.get_object(candidate).is_some()
+                || self.pending_primitives.iter().any(|(name, _)| name == candidate)
+        };
+        let name = unused_name(base, is_used);
+        self.pending_primitives.push((name.clone(), primitive.generate()));
+        name
+    }
+
+    /*
+     * Subdivides the mesh of object on the mesh loader, then adds a copy of object using it in its
+     * place and hides object. Returns the id of the load, see cancel_load.
+     */
+    pub fn subdivide_object(&mut self, object: ContainerRef<Object>, options: SubdivisionOptions) -> Result<u64, Error> {
+        let mesh_name = String::from(object.lock().unwrap().get_mesh().lock().unwrap().get_name());
+        let source = self.container.get_polygon_source(&mesh_name)?;
+        let load_id = self.mesh_loader.subdivide(&format!("{} (subdividing)", mesh_name), source, options);
+        self.pending_subdivisions.push((load_id, object));
+        Ok(load_id)
+    }
+
+    /* Edits the scene made on its own, like finished subdivisions, for the History to record */
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.commands)
+    }
+
+    pub fn cancel_load(&mut self, load_id: u64) {
+        self.mesh_loader.cancel(load_id);
+        if let Some(index) = self.pending_objects.iter().position(|pending| pending.load_id == load_id) {
+            let pending = self.pending_objects.remove(index);
+            log::info(Subsystem::Resource, format!("Cancelled loading {}", pending.file_name));
+        }
+        if let Some(index) = self.pending_reloads.iter().position(|(id, _)| *id == load_id) {
+            let (_, asset) = self.pending_reloads.remove(index);
+            log::info(Subsystem::Resource, format!("Cancelled reloading {}, keeping the old mesh", asset));
+        }
+        if let Some(index) = self.pending_subdivisions.iter().position(|(id, _)| *id == load_id) {
+            let (_, object) = self.pending_subdivisions.remove(index);
+            log::info(Subsystem::Resource, format!("Cancelled subdividing {}", object.lock().unwrap().get_name()));
+        }
+    }
+
+    /* (load id, file name, fraction done) of the loads still running */
+    pub fn get_loads(&self) -> Vec<(u64, String, f32)> {
+        self.mesh_loader
+            .get_jobs()
+            .iter()
+            .map(|job| (job.get_id(), String::from(job.get_file_name()), job.get_progress()))
+            .collect()
     }
 
     pub fn get_camera<'a>(&'a self) -> &'a Camera {
@@ -39,19 +195,588 @@ impl Scene {
         &mut self.main_camera
     }
 
+    pub fn get_fractal(&self) -> Option<&Fractal> {
+        self.fractal.as_ref()
+    }
+
+    pub fn get_mut_fractal(&mut self) -> Option<&mut Fractal> {
+        self.fractal.as_mut()
+    }
+
+    pub fn set_fractal(&mut self, fractal: Option<Fractal>) {
+        self.fractal = fractal;
+    }
+
+    pub fn get_light(&self) -> &DirectionalLight {
+        &self.light
+    }
+
+    pub fn get_mut_light(&mut self) -> &mut DirectionalLight {
+        &mut self.light
+    }
+
+    /* Distance estimate from the camera to the active fractal */
+    pub fn get_camera_distance(&self) -> Option<f64> {
+        self.fractal.as_ref().map(|fractal| fractal.distance_estimate(self.main_camera.get_position()))
+    }
+
     pub fn get_container(&self) -> &Container {
         &self.container
     }
 
-    pub fn render(&mut self, gl: &glow::Context) {
+    pub fn get_objects(&self) -> &Vec<ContainerRef<Object>> {
+        &self.objects
+    }
+
+    pub fn get_object(&self, name: &str) -> Option<ContainerRef<Object>> {
+        self.container.get_object(name)
+    }
+
+    /* Objects whose parent is parent_name (or root objects if it is None) */
+    pub fn get_children(&self, parent_name: Option<&str>) -> Vec<ContainerRef<Object>> {
+        self.objects
+            .iter()
+            .filter(|object| object.lock().unwrap().get_parent() == parent_name)
+            .cloned()
+            .collect()
+    }
+
+    /* The transform of the object combined with all of its parents */
+    pub fn get_world_matrix(&self, object: &Object) -> DMat4 {
+        let mut result = object.get_transform().get_matrix();
+        let mut parent_name = object.get_parent().map(String::from);
+        while let Some(name) = parent_name {
+            match self.get_object(&name) {
+                Some(parent) => {
+                    let parent = parent.lock().unwrap();
+                    result = parent.get_transform().get_matrix() * result;
+                    parent_name = parent.get_parent().map(String::from);
+                }
+                None => break,
+            }
+        }
+        result
+    }
+
+    /* Bounds of the object's mesh in world space */
+    pub fn get_object_bounds(&self, object: &Object) -> Aabb {
+        object.get_world_bounds(&self.get_world_matrix(object))
+    }
+    pub fn get_object_bounding_sphere(&self, object: &Object) -> BoundingSphere {
+        object.get_world_bounding_sphere(&self.get_world_matrix(object))
+    }
+
+    /* Bounds of all visible objects, empty if there are none */
+    pub fn get_bounds(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::empty(), |bounds, object| {
+            let object = object.lock().unwrap();
+            match self.is_visible(&object) {
+                true => bounds.union(&self.get_object_bounds(&object)),
+                false => bounds,
+            }
+        })
+    }
+
+    /* Moves the camera back until object fills the view */
+    pub fn frame_object(&mut self, object: &ContainerRef<Object>) {
+        let sphere = self.get_object_bounding_sphere(&object.lock().unwrap());
+        self.main_camera.frame(&sphere);
+    }
+
+    /* Frames every visible object, does nothing in an empty scene */
+    pub fn frame_all(&mut self) {
+        let bounds = self.get_bounds();
+        if !bounds.is_empty() {
+            self.main_camera.frame(&BoundingSphere::from_aabb(&bounds));
+        }
+    }
+
+    pub fn get_bounds_overlay(&self) -> BoundsOverlay {
+        self.bounds_overlay
+    }
+    pub fn get_mut_bounds_overlay(&mut self) -> &mut BoundsOverlay {
+        &mut self.bounds_overlay
+    }
+    pub fn set_bounds_overlay(&mut self, value: BoundsOverlay) {
+        self.bounds_overlay = value;
+    }
+
+    /* Spheres around everything drawn, including the boxes of objects still loading */
+    fn get_drawn_spheres(&self) -> Vec<BoundingSphere> {
+        let mut result: Vec<BoundingSphere> = self.objects
+            .iter()
+            .filter_map(|object| {
+                let object = object.lock().unwrap();
+                self.is_visible(&object).then(|| self.get_object_bounding_sphere(&object))
+            })
+            .collect();
+        let placeholder = BoundingSphere::new(glam::DVec3::ZERO, 3.0_f64.sqrt());
+        result.extend(self.pending_objects.iter().map(|pending| placeholder.transformed(&pending.transform.get_matrix())));
+        result
+    }
+
+    /* Objects are hidden if they or any of their parents are hidden */
+    pub fn is_visible(&self, object: &Object) -> bool {
+        if !object.is_visible() {
+            return false;
+        }
+        let mut parent_name = object.get_parent().map(String::from);
+        while let Some(name) = parent_name {
+            match self.get_object(&name) {
+                Some(parent) => {
+                    let parent = parent.lock().unwrap();
+                    if !parent.is_visible() {
+                        return false;
+                    }
+                    parent_name = parent.get_parent().map(String::from);
+                }
+                None => break,
+            }
+        }
+        true
+    }
+
+    /* Whether ancestor_name is object_name or one of its parents */
+    pub fn is_ancestor(&self, ancestor_name: &str, object_name: &str) -> bool {
+        let mut current = Some(String::from(object_name));
+        while let Some(name) = current {
+            if name == ancestor_name {
+                return true;
+            }
+            current = match self.get_object(&name) {
+                Some(object) => object.lock().unwrap().get_parent().map(String::from),
+                None => None,
+            };
+        }
+        false
+    }
+
+    pub fn rename_object(&mut self, old_name: &str, new_name: &str) -> Result<(), Error> {
+        self.container.rename_object(old_name, new_name)?;
+        for object in &self.objects {
+            let mut object = object.lock().unwrap();
+            if object.get_parent() == Some(old_name) {
+                object.set_parent(Some(new_name));
+            }
+        }
+        Ok(())
+    }
+
+    /* Moves object under parent_name while keeping where it is in world space */
+    pub fn set_parent(&mut self, object_name: &str, parent_name: Option<&str>) -> Result<(), Error> {
+        let object = match self.get_object(object_name) {
+            Some(object) => object,
+            None => return Err(Error::object_does_not_exist(object_name)),
+        };
+        let parent_matrix = match parent_name {
+            Some(parent_name) => {
+                if self.is_ancestor(object_name, parent_name) {
+                    return Err(Error::invalid_parent(object_name, parent_name));
+                }
+                match self.get_object(parent_name) {
+                    Some(parent) => self.get_world_matrix(&parent.lock().unwrap()),
+                    None => return Err(Error::object_does_not_exist(parent_name)),
+                }
+            }
+            None => DMat4::IDENTITY,
+        };
+        let world_matrix = self.get_world_matrix(&object.lock().unwrap());
+        let mut object = object.lock().unwrap();
+        object.set_transform(Transform::from_matrix(&(parent_matrix.inverse() * world_matrix)));
+        object.set_parent(parent_name);
+        Ok(())
+    }
+
+    /*
+     * Removes the object from the scene. Its children are moved up to its parent.
+     * The object keeps its gl resources (so it can be added back) until it is passed to destroy_object.
+     * Returns the object and where it was in the scene.
+     */
+    pub fn remove_object(&mut self, name: &str) -> Result<(ContainerRef<Object>, usize), Error> {
+        let parent_name = match self.get_object(name) {
+            Some(object) => object.lock().unwrap().get_parent().map(String::from),
+            None => return Err(Error::object_does_not_exist(name)),
+        };
+        for child in self.get_children(Some(name)) {
+            let child_name = String::from(child.lock().unwrap().get_name());
+            self.set_parent(&child_name, parent_name.as_deref())?;
+        }
+        let object = self.container.remove_object(name)?;
+        let index = self.objects.iter().position(|other| ContainerRef::ptr_eq(other, &object)).unwrap_or(self.objects.len());
+        self.objects.retain(|other| !ContainerRef::ptr_eq(other, &object));
+        self.revision += 1;
+        Ok((object, index))
+    }
+
+    /* Adds an object back to the scene at index */
+    pub fn insert_object(&mut self, object: ContainerRef<Object>, index: usize) -> Result<(), Error> {
+        self.container.add_object(object.clone())?;
+        let index = index.min(self.objects.len());
+        self.objects.insert(index, object);
+        self.revision += 1;
+        Ok(())
+    }
+
+    /* Frees the gl resources of a removed object on the next render */
+    pub fn destroy_object(&mut self, object: ContainerRef<Object>) {
+        self.removed_objects.push(object);
+    }
+
+    /* The visible objects where they are now, for ray queries against the whole scene */
+    pub fn build_instance_bvh(&self) -> InstanceBvh {
+        let instances = self.objects
+            .iter()
+            .filter_map(|object_ref| {
+                let object = object_ref.lock().unwrap();
+                self.is_visible(&object).then(|| (object_ref.clone(), self.get_world_matrix(&object)))
+            })
+            .collect();
+        InstanceBvh::new(instances)
+    }
+
+    /*
+     * Changes whenever what build_instance_bvh returns could.
+     * Object revisions only go up, so while the objects stay the same their sum changes when any of them does.
+     */
+    fn get_pick_key(&self) -> (u64, u64) {
+        let object_revisions = self.objects.iter().map(|object| object.lock().unwrap().get_revision()).sum();
+        (self.revision, object_revisions)
+    }
+
+    /* The closest visible object hit by ray and the distance to it */
+    pub fn pick(&mut self, ray: &Ray) -> Option<(ContainerRef<Object>, f64)> {
+        let key = self.get_pick_key();
+        if self.instance_bvh.as_ref().is_none_or(|(built_at, _)| *built_at != key) {
+            self.instance_bvh = Some((key, self.build_instance_bvh()));
+        }
+        self.instance_bvh.as_ref().and_then(|(_, bvh)| bvh.closest_hit(ray))
+    }
+
+    /* selected is drawn highlighted */
+    pub fn render(&mut self, gl: &glow::Context, selected: Option<&ContainerRef<Object>>) {
         //self.current_rotation_dir = Quat::from_axis_angle(Vec3::new(1.0, 1.0, 0.0), 0.01).normalize() * self.current_rotation_dir;
         //self.main_camera.rotate_camera(Quat::from_axis_angle(self.current_rotation_dir, 0.02).normalize());
+        for object in self.removed_objects.drain(..) {
+            object.lock().unwrap().destroy(gl);
+        }
+        self.reload_changed_assets();
+        self.finish_loads(gl);
+        self.finish_primitives(gl);
+        if self.main_camera.is_auto_clip() {
+            let spheres = self.get_drawn_spheres();
+            self.main_camera.fit_clip_planes(&spheres);
+        }
         unsafe {
             gl.enable(glow::DEPTH_TEST);
             gl.clear(glow::DEPTH_BUFFER_BIT);
         }
-        for object in &self.objects {
-            object.lock().unwrap().render(gl, self.get_camera());
+        for object_ref in &self.objects {
+            let mut object = object_ref.lock().unwrap();
+            if object.needs_reload() {
+                if let Err(err) = object.reload_program(&self.container, gl) {
+                    log::error(&format!("Could not regenerate {}", object.get_name()), &err);
+                }
+            }
+            if !self.is_visible(&object) || object.is_render_failed() {
+                continue;
+            }
+            let world_matrix = self.get_world_matrix(&object);
+            let highlighted = selected.is_some_and(|selected| ContainerRef::ptr_eq(selected, object_ref));
+            if let Err(err) = object.render(gl, self.get_camera(), &self.light, &world_matrix, highlighted) {
+                log::error(&format!("Could not render {}, skipping it until its shaders are rebuilt", object.get_name()), &err);
+                object.set_render_error(&err);
+            }
+        }
+        self.render_pending_objects(gl);
+        self.render_bounds(gl, selected);
+    }
+
+    fn render_bounds(&mut self, gl: &glow::Context, selected: Option<&ContainerRef<Object>>) {
+        let objects: Vec<ContainerRef<Object>> = match self.bounds_overlay {
+            BoundsOverlay::Off => return,
+            BoundsOverlay::Selected => selected.into_iter().cloned().collect(),
+            BoundsOverlay::All => self.objects.clone(),
+        };
+        let mut boxes = Vec::new();
+        for object in &objects {
+            let object = object.lock().unwrap();
+            if self.is_visible(&object) {
+                boxes.push(self.get_object_bounds(&object));
+            }
+        }
+        if boxes.is_empty() {
+            return;
+        }
+        self.create_line_renderer(gl);
+        if let Some(line_renderer) = &self.line_renderer {
+            for bounds in boxes {
+                // Drawn relative to its min corner so the box goes through the floating origin like the objects
+                let camera_matrix = self.main_camera.get_object_matrix(&DMat4::from_translation(bounds.get_min()));
+                let lines = box_lines(Vec3::ZERO, bounds.get_size().as_vec3());
+                line_renderer.render(gl, &camera_matrix, &lines, BOUNDS_COLOR);
+            }
+        }
+    }
+
+    /* Uploads the meshes the loader is done with and adds their objects or swaps them in for the old ones */
+    fn finish_loads(&mut self, gl: &glow::Context) {
+        for (job, result) in self.mesh_loader.poll() {
+            if let Some(index) = self.pending_reloads.iter().position(|(id, _)| *id == job.get_id()) {
+                let (_, asset) = self.pending_reloads.remove(index);
+                self.finish_reload(&asset, result, gl);
+                continue;
+            }
+            if let Some(index) = self.pending_subdivisions.iter().position(|(id, _)| *id == job.get_id()) {
+                let (_, object) = self.pending_subdivisions.remove(index);
+                self.finish_subdivision(&object, result, gl);
+                continue;
+            }
+            let pending = match self.pending_objects.iter().position(|pending| pending.load_id == job.get_id()) {
+                Some(index) => self.pending_objects.remove(index),
+                None => continue,
+            };
+            let mesh_name = format!("{}_mesh", pending.name);
+            let added = result
+                .and_then(|loaded| Mesh::from_data_with_lods(loaded.data, loaded.lods, gl, &mesh_name))
+                .and_then(|mesh| self.container.add_mesh_from_file(mesh, &pending.file_name))
+                .and_then(|_| self.container.create_object(&pending.name, "default_material", &mesh_name, gl));
+            if let Err(err) = added {
+                log::error(&format!("Could not load {}", pending.file_name), &err);
+                continue;
+            }
+            if let Some(object) = self.container.get_object(&pending.name) {
+                object.lock().unwrap().set_transform(pending.transform);
+                self.objects.push(object);
+                self.revision += 1;
+                log::info(Subsystem::Resource, format!("Loaded {}", pending.file_name));
+            }
+        }
+    }
+
+    fn finish_primitives(&mut self, gl: &glow::Context) {
+        for (name, data) in std::mem::take(&mut self.pending_primitives) {
+            let mesh_name = unused_name(&format!("{}_mesh", name), |candidate| self.container.get_mesh(candidate).is_some());
+            let added = Mesh::from_data(data, gl, &mesh_name)
+                .and_then(|mesh| self.container.add_mesh(mesh))
+                .and_then(|_| self.container.create_object(&name, "default_material", &mesh_name, gl));
+            match (added, self.container.get_object(&name)) {
+                (Ok(_), Some(object)) => {
+                    self.objects.push(object);
+                    self.revision += 1;
+                    log::info(Subsystem::Scene, format!("Added {}", name));
+                }
+                (Err(err), _) => log::error(&format!("Could not add {}", name), &err),
+                (Ok(_), None) => (),
+            }
+        }
+    }
+
+    fn finish_subdivision(&mut self, object: &ContainerRef<Object>, result: Result<LoadedMesh, Error>, gl: &glow::Context) {
+        let name = String::from(object.lock().unwrap().get_name());
+        match result.and_then(|loaded| self.add_subdivided(object, loaded, gl)) {
+            Ok(new_name) => log::info(Subsystem::Resource, format!("Subdivided {} into {}", name, new_name)),
+            Err(err) => log::error(&format!("Could not subdivide {}", name), &err),
+        }
+    }
+
+    /* Adding the copy and hiding object are recorded as one command */
+    fn add_subdivided(&mut self, object_ref: &ContainerRef<Object>, loaded: LoadedMesh, gl: &glow::Context) -> Result<String, Error> {
+        let (name, mesh_name, material_name, transform, parent, visible) = {
+            let object = object_ref.lock().unwrap();
+            let mesh_name = String::from(object.get_mesh().lock().unwrap().get_name());
+            let material_name = String::from(object.get_material().lock().unwrap().get_name());
+            (String::from(object.get_name()), mesh_name, material_name, *object.get_transform(), object.get_parent().map(String::from), object.is_visible())
+        };
+        // Deleted while it was being subdivided
+        if !self.objects.iter().any(|object| ContainerRef::ptr_eq(object, object_ref)) {
+            return Err(Error::object_does_not_exist(&name));
+        }
+        let new_mesh_name = unused_name(&format!("{}_subdivided", mesh_name), |candidate| self.container.get_mesh(candidate).is_some());
+        self.container.add_mesh(Mesh::from_data_with_lods(loaded.data, loaded.lods, gl, &new_mesh_name)?)?;
+        let new_name = unused_name(&format!("{}_subdivided", name), |candidate| self.container.get_object(candidate).is_some());
+        self.container.create_object(&new_name, &material_name, &new_mesh_name, gl)?;
+        let new_object = match self.container.get_object(&new_name) {
+            Some(new_object) => new_object,
+            None => return Err(Error::object_does_not_exist(&new_name)),
+        };
+        {
+            let mut new_object = new_object.lock().unwrap();
+            new_object.set_transform(transform);
+            new_object.set_parent(parent.as_deref());
         }
+        self.objects.push(new_object.clone());
+        self.revision += 1;
+        object_ref.lock().unwrap().set_visible(false);
+        self.commands.push(Command::Group {
+            description: format!("Subdivide {}", name),
+            commands: vec![
+                Command::Add { object: new_object, index: self.objects.len() - 1 },
+                Command::SetVisible { object: object_ref.clone(), old: visible, new: false },
+            ],
+        });
+        Ok(new_name)
+    }
+
+    /* Boxes where the objects still loading will appear */
+    fn render_pending_objects(&mut self, gl: &glow::Context) {
+        if self.pending_objects.is_empty() {
+            return;
+        }
+        self.create_line_renderer(gl);
+        if let Some(line_renderer) = &self.line_renderer {
+            let lines = box_lines(Vec3::splat(-1.0), Vec3::splat(1.0));
+            for pending in &self.pending_objects {
+                let camera_matrix = self.main_camera.get_object_matrix(&pending.transform.get_matrix());
+                line_renderer.render(gl, &camera_matrix, &lines, PLACEHOLDER_COLOR);
+            }
+        }
+    }
+
+    /* The line renderer is created on first use, and only tried once */
+    fn create_line_renderer(&mut self, gl: &glow::Context) {
+        if self.line_renderer.is_none() && !self.line_renderer_failed {
+            match LineRenderer::create(gl) {
+                Ok(line_renderer) => self.line_renderer = Some(line_renderer),
+                Err(err) => {
+                    log::error("Could not create the line renderer, overlays won't be drawn", &err);
+                    self.line_renderer_failed = true;
+                }
+            }
+        }
+    }
+
+    /* Frees the gl resources of everything the scene and its container hold */
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        for object in self.container.get_objects().chain(self.removed_objects.iter()) {
+            object.lock().unwrap().destroy(gl);
+        }
+        self.removed_objects.clear();
+        for mesh in self.container.get_meshes() {
+            mesh.lock().unwrap().destroy(gl);
+        }
+        if let Some(line_renderer) = self.line_renderer.take() {
+            line_renderer.destroy(gl);
+        }
+    }
+
+    /* Files the scene depends on, as paths in the asset roots. See AssetWatcher */
+    pub fn get_asset_files(&self) -> Vec<String> {
+        let mut result = self.container.get_asset_files();
+        result.extend(shader_gen::TEMPLATE_FILES.iter().map(|name| format!("shaders/{}", name)));
+        result
+    }
+
+    pub fn queue_asset_changes(&mut self, changed: Vec<String>) {
+        self.changed_assets.extend(changed);
+    }
+
+    /*
+     * Objects keep their old program or mesh when the changed file doesn't work.
+     * Meshes are loaded again on the mesh loader's threads like any other, see finish_reload.
+     */
+    fn reload_changed_assets(&mut self) {
+        for asset in std::mem::take(&mut self.changed_assets) {
+            if asset.starts_with("shaders/") {
+                log::info(Subsystem::Shader, format!("{} changed, rebuilding the programs", asset));
+                for object in self.container.get_objects() {
+                    object.lock().unwrap().request_reload();
+                }
+                continue;
+            }
+            if !self.container.has_mesh_file(&asset) {
+                continue;
+            }
+            // A reload still running would read the file from before this change
+            if let Some(index) = self.pending_reloads.iter().position(|(_, other)| *other == asset) {
+                let (load_id, _) = self.pending_reloads.remove(index);
+                self.mesh_loader.cancel(load_id);
+            }
+            let load_id = self.mesh_loader.load(asset.trim_start_matches("meshes/"));
+            self.pending_reloads.push((load_id, asset));
+        }
+    }
+
+    fn finish_reload(&mut self, asset: &str, result: Result<LoadedMesh, Error>, gl: &glow::Context) {
+        match result.and_then(|loaded| self.container.replace_mesh_file(asset, loaded, gl)) {
+            Ok(Some(mesh)) => {
+                log::info(Subsystem::Resource, format!("Reloaded {}", asset));
+                self.revision += 1;
+                for object in self.container.get_objects() {
+                    let mut object = object.lock().unwrap();
+                    if ContainerRef::ptr_eq(&object.get_mesh(), &mesh) {
+                        object.request_reload();
+                    }
+                }
+            }
+            Ok(None) => (),
+            Err(err) => log::error(&format!("Could not reload {}, keeping the old mesh", asset), &err),
+        }
+    }
+}
+
+/* base, or base with the first number after it that is_used says is free */
+fn unused_name(base: &str, is_used: impl Fn(&str) -> bool) -> String {
+    if !is_used(base) {
+        return String::from(base);
+    }
+    (2..).map(|number| format!("{}{}", base, number)).find(|name| !is_used(name)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::DVec3;
+
+    use super::*;
+    use crate::util::test_util::object;
+
+    /* Straight down the z axis at x */
+    fn ray_at(x: f64) -> Ray {
+        Ray::new(DVec3::new(x + 0.1, 0.2, 5.0), DVec3::NEG_Z)
+    }
+
+    fn picked(scene: &mut Scene, x: f64) -> Option<String> {
+        scene.pick(&ray_at(x)).map(|(object, _)| String::from(object.lock().unwrap().get_name()))
+    }
+
+    fn get_built_at(scene: &Scene) -> Option<(u64, u64)> {
+        scene.instance_bvh.as_ref().map(|(built_at, _)| *built_at)
+    }
+
+    #[test]
+    fn pick_rebuilds_only_after_changes() {
+        let mut scene = Scene::new(Container::new());
+        let (first, second) = (object("first"), object("second"));
+        scene.insert_object(first.clone(), 0).unwrap();
+        assert_eq!(picked(&mut scene, 0.0).as_deref(), Some("first"));
+        let built_at = get_built_at(&scene);
+        assert_eq!(picked(&mut scene, 0.0).as_deref(), Some("first"));
+        assert_eq!(picked(&mut scene, 10.0), None);
+        assert_eq!(get_built_at(&scene), built_at);
+
+        first.lock().unwrap().set_transform(Transform::from_position(DVec3::new(10.0, 0.0, 0.0)));
+        assert_eq!(picked(&mut scene, 0.0), None);
+        assert_eq!(picked(&mut scene, 10.0).as_deref(), Some("first"));
+        let built_at = get_built_at(&scene);
+
+        // Moving the parent moves the child
+        scene.insert_object(second.clone(), 1).unwrap();
+        scene.set_parent("second", Some("first")).unwrap();
+        assert_ne!(Some(scene.get_pick_key()), built_at);
+        assert_eq!(picked(&mut scene, 0.0).as_deref(), Some("second"));
+        first.lock().unwrap().set_transform(Transform::new());
+        assert_eq!(picked(&mut scene, 0.0).as_deref(), Some("first"));
+        assert_eq!(picked(&mut scene, -10.0).as_deref(), Some("second"));
+
+        // Hiding the parent hides the child
+        first.lock().unwrap().set_visible(false);
+        assert_eq!(picked(&mut scene, 0.0), None);
+        assert_eq!(picked(&mut scene, -10.0), None);
+        first.lock().unwrap().set_visible(true);
+        assert_eq!(picked(&mut scene, 0.0).as_deref(), Some("first"));
+
+        scene.remove_object("first").unwrap();
+        assert_eq!(picked(&mut scene, 0.0), None);
+        let instance_count = scene.instance_bvh.as_ref().map(|(_, bvh)| bvh.get_instance_count());
+        assert_eq!(instance_count, Some(1));
     }
 }
@@ -0,0 +1,62 @@
+use glam::DMat4;
+
+use crate::resources::container::ContainerRef;
+use crate::resources::object::Object;
+
+use super::bounds::Aabb;
+use super::bvh::Bvh;
+use super::ray::Ray;
+
+struct Instance {
+    object: ContainerRef<Object>,
+    world_matrix: DMat4,
+}
+
+/*
+ * @brief
+ * The top level over the objects of a scene, each placed with its world matrix.
+ * Rays that get past an object's world bounds go on to the bvh of its mesh.
+ * It is a snapshot, build a new one after objects moved.
+ */
+pub struct InstanceBvh {
+    bvh: Bvh,
+    instances: Vec<Instance>,
+}
+
+impl InstanceBvh {
+    pub fn new(instances: Vec<(ContainerRef<Object>, DMat4)>) -> Self {
+        let bounds: Vec<Aabb> = instances
+            .iter()
+            .map(|(object, world_matrix)| object.lock().unwrap().get_world_bounds(world_matrix))
+            .collect();
+        Self {
+            bvh: Bvh::build(&bounds),
+            instances: instances.into_iter().map(|(object, world_matrix)| Instance { object, world_matrix }).collect(),
+        }
+    }
+
+    pub fn get_instance_count(&self) -> usize {
+        self.instances.len()
+    }
+
+    /* The closest object hit by ray and the distance to it */
+    pub fn closest_hit(&self, ray: &Ray) -> Option<(ContainerRef<Object>, f64)> {
+        self.bvh
+            .closest_hit(ray, f64::INFINITY, |index| {
+                let instance = &self.instances[index];
+                instance.object.lock().unwrap().intersect_ray(ray, &instance.world_matrix)
+            })
+            .map(|(index, distance)| (self.instances[index].object.clone(), distance))
+    }
+
+    /* Whether any object is hit before max_distance, e.g. to see if a point is visible */
+    pub fn any_hit(&self, ray: &Ray, max_distance: f64) -> bool {
+        self.bvh
+            .any_hit(ray, max_distance, |index| {
+                let instance = &self.instances[index];
+                let hit = instance.object.lock().unwrap().any_hit(ray, &instance.world_matrix, max_distance);
+                hit.then_some(0.0)
+            })
+            .is_some()
+    }
+}
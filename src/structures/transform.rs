@@ -0,0 +1,80 @@
+use glam::*;
+
+/*
+ * @brief
+ * Position, rotation and scale of something placed in the scene.
+ * Kept in double precision so that deep zooms do not jitter. It is only
+ * converted to f32 after being rebased relative to the camera.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    position: DVec3,
+    rotation: DQuat,
+    scale: DVec3,
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Self {
+            position: DVec3::ZERO,
+            rotation: DQuat::IDENTITY,
+            scale: DVec3::ONE,
+        }
+    }
+
+    pub fn from_position(position: DVec3) -> Self {
+        Self {
+            position,
+            ..Self::new()
+        }
+    }
+
+    pub fn get_position(&self) -> DVec3 {
+        self.position
+    }
+    pub fn get_mut_position(&mut self) -> &mut DVec3 {
+        &mut self.position
+    }
+    pub fn set_position(&mut self, value: DVec3) {
+        self.position = value;
+    }
+
+    pub fn get_rotation(&self) -> DQuat {
+        self.rotation
+    }
+    pub fn get_mut_rotation(&mut self) -> &mut DQuat {
+        &mut self.rotation
+    }
+    pub fn set_rotation(&mut self, value: DQuat) {
+        self.rotation = value;
+    }
+
+    pub fn get_scale(&self) -> DVec3 {
+        self.scale
+    }
+    pub fn get_mut_scale(&mut self) -> &mut DVec3 {
+        &mut self.scale
+    }
+    pub fn set_scale(&mut self, value: DVec3) {
+        self.scale = value;
+    }
+
+    pub fn get_matrix(&self) -> DMat4 {
+        DMat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+    }
+
+    pub fn from_matrix(matrix: &DMat4) -> Self {
+        let (scale, rotation, position) = matrix.to_scale_rotation_translation();
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
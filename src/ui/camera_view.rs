@@ -1,28 +1,41 @@
 use crate::egui;
-use crate::structures::camera::Camera;
+use eframe::egui::DragValue;
+use crate::structures::camera::{Camera, MovementMode};
 use crate::{Arc, Mutex};
+use crate::structures::history::Command;
 use crate::structures::scene::Scene;
+use std::rc::Rc;
 
 use super::common_views::{Vec3View, QuatView};
+use super::shared_state::SharedState;
 use super::ui_traits::{UiElement, UiElementParent};
 
 
 
 pub struct CameraView {
     scene: Arc<Mutex<Scene>>,
+    shared_state: Rc<SharedState>,
 }
 
 impl CameraView {
-    pub fn new(scene: Arc<Mutex<Scene>>) -> Self {
+    pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<SharedState>) -> Self {
         Self {
             scene,
+            shared_state,
         }
     }
 }
 
 impl UiElement for CameraView {
     fn render(&mut self, ui: &mut egui::Ui) {
-        Self::real_render(ui, self.scene.lock().get_camera_mut());
+        let mut scene = self.scene.lock();
+        let camera = scene.get_camera_mut();
+        let old = (camera.get_position(), camera.get_rotation_quat());
+        Self::real_render(ui, camera);
+        let new = (camera.get_position(), camera.get_rotation_quat());
+        if old != new {
+            self.shared_state.get_history().push(Command::SetCamera { old, new });
+        }
     }
 }
 
@@ -31,5 +44,58 @@ impl CameraView {
         ui.show_element(Vec3View::new(camera.get_mut_position()));
         ui.separator();
         ui.show_element(QuatView::new(camera.get_mut_rotation_quat()));
+        ui.separator();
+        Self::movement_render(ui, camera);
+        ui.separator();
+        Self::clip_render(ui, camera);
+    }
+
+    fn clip_render(ui: &mut egui::Ui, camera: &mut Camera) {
+        ui.checkbox(camera.get_mut_auto_clip(), "fit near and far to the scene");
+        let auto_clip = camera.is_auto_clip();
+        let (mut near, mut far) = (camera.get_near(), camera.get_far());
+        ui.add_enabled_ui(!auto_clip, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("near: ");
+                ui.add(DragValue::new(&mut near).speed(0.01).clamp_range(0.0..=f32::MAX));
+            });
+            ui.horizontal(|ui| {
+                ui.label("far: ");
+                ui.add(DragValue::new(&mut far).speed(1.0).clamp_range(0.0..=f32::MAX));
+            });
+        });
+        if !auto_clip && (near, far) != (camera.get_near(), camera.get_far()) {
+            camera.set_clip_planes(near, far);
+        }
+    }
+
+    fn movement_render(ui: &mut egui::Ui, camera: &mut Camera) {
+        ui.label("movement: ");
+        ui.horizontal(|ui| {
+            ui.radio_value(camera.get_mut_movement_mode(), MovementMode::Fixed, "fixed");
+            ui.radio_value(camera.get_mut_movement_mode(), MovementMode::Adaptive, "adaptive");
+        });
+        match camera.get_movement_mode() {
+            MovementMode::Fixed => {
+                ui.horizontal(|ui| {
+                    ui.label("speed: ");
+                    ui.add(DragValue::new(camera.get_mut_speed()).speed(0.001).clamp_range(0.0..=f64::MAX));
+                });
+            }
+            MovementMode::Adaptive => {
+                ui.horizontal(|ui| {
+                    ui.label("min speed: ");
+                    ui.add(DragValue::new(camera.get_mut_min_speed()).speed(0.000001).clamp_range(0.0..=f64::MAX));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("max speed: ");
+                    ui.add(DragValue::new(camera.get_mut_max_speed()).speed(0.001).clamp_range(0.0..=f64::MAX));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("factor: ");
+                    ui.add(DragValue::new(camera.get_mut_adaptive_factor()).speed(0.001).clamp_range(0.0..=1.0));
+                });
+            }
+        }
     }
 }
@@ -1,15 +1,15 @@
 use std::f64::consts::PI;
 
 use eframe::egui::DragValue;
-use glam::Vec3;
-use glam::Quat;
+use glam::DVec3;
+use glam::DQuat;
 use crate::{egui, util::util::GetSetValue};
 
 use super::ui_traits::UiElement;
 use super::ui_traits::UiElementParent;
 
 pub struct Vec3View<'a> {
-    value: GetSetValue<'a, Vec3>,
+    value: GetSetValue<'a, DVec3>,
     speed: f32,
     label: String,
 }
@@ -23,10 +23,10 @@ impl<'a> UiElement for Vec3View<'a> {
 
             let x_get_set = |v: Option<f64>| {
                 if let Some(v) = v {
-                    old_vec.x = v as f32;
+                    old_vec.x = v;
                     (self.value)(Some(old_vec));
                 }
-                (self.value)(None).x as f64
+                (self.value)(None).x
             };
             ui.horizontal(|ui| {
                 ui.label("x: ");
@@ -35,10 +35,10 @@ impl<'a> UiElement for Vec3View<'a> {
 
             let y_get_set = |v: Option<f64>| {
                 if let Some(v) = v {
-                    old_vec.y = v as f32;
+                    old_vec.y = v;
                     (self.value)(Some(old_vec));
                 }
-                (self.value)(None).y as f64
+                (self.value)(None).y
             };
             ui.horizontal(|ui| {
                 ui.label("y: ");
@@ -47,10 +47,10 @@ impl<'a> UiElement for Vec3View<'a> {
             
             let z_get_set = |v: Option<f64>| {
                 if let Some(v) = v {
-                    old_vec.z = v as f32;
+                    old_vec.z = v;
                     (self.value)(Some(old_vec));
                 }
-                (self.value)(None).z as f64
+                (self.value)(None).z
             };
             ui.horizontal(|ui| {
                 ui.label("z: ");
@@ -61,8 +61,8 @@ impl<'a> UiElement for Vec3View<'a> {
 }
 
 impl<'a> Vec3View<'a> {
-    pub fn new(value: &'a mut Vec3) -> Self {
-        let get_set: GetSetValue<Vec3> = Box::new(|v: Option<Vec3>| {
+    pub fn new(value: &'a mut DVec3) -> Self {
+        let get_set: GetSetValue<DVec3> = Box::new(|v: Option<DVec3>| {
             if let Some(v) = v {
                 *value = v;
             }
@@ -71,7 +71,7 @@ impl<'a> Vec3View<'a> {
         Self::from_get_set(get_set)
     }
 
-    pub fn from_get_set(value: GetSetValue<'a, Vec3>) -> Self {
+    pub fn from_get_set(value: GetSetValue<'a, DVec3>) -> Self {
         Self {
             value,
             speed: 0.1,
@@ -90,7 +90,7 @@ impl<'a> Vec3View<'a> {
 }
 
 pub struct QuatView<'a> {
-    value: GetSetValue<'a, Quat>,
+    value: GetSetValue<'a, DQuat>,
     rotation_speed: f32,
     axis_speed: f32,
     label: String,
@@ -101,9 +101,9 @@ impl<'a> UiElement for QuatView<'a> {
         let (axis, angle) = (self.value)(None).to_axis_angle();
 
         ui.vertical(|ui| {
-            let axis_get_set = Box::new(|v: Option<Vec3>| {
+            let axis_get_set = Box::new(|v: Option<DVec3>| {
                 if let Some(v) = v {
-                    (self.value)(Some(Quat::from_axis_angle(v, angle)));
+                    (self.value)(Some(DQuat::from_axis_angle(v, angle)));
                     v
                 } else {
                     axis
@@ -122,10 +122,10 @@ impl<'a> UiElement for QuatView<'a> {
                     if v < 0.0 {
                         v += PI * 2.0;
                     }
-                    (self.value)(Some(Quat::from_axis_angle(axis, v as f32)));
+                    (self.value)(Some(DQuat::from_axis_angle(axis, v)));
                     v
                 } else {
-                    angle as f64
+                    angle
                 }
             });
             ui.horizontal(|ui| {
@@ -137,8 +137,8 @@ impl<'a> UiElement for QuatView<'a> {
 }
 
 impl<'a> QuatView<'a> {
-    pub fn new(value: &'a mut Quat) -> Self {
-        let get_set: GetSetValue<Quat> = Box::new(|v: Option<Quat>| {
+    pub fn new(value: &'a mut DQuat) -> Self {
+        let get_set: GetSetValue<DQuat> = Box::new(|v: Option<DQuat>| {
             if let Some(v) = v {
                 *value = v;
             }
@@ -147,7 +147,7 @@ impl<'a> QuatView<'a> {
         Self::from_get_set(get_set)
     }
 
-    pub fn from_get_set(value: GetSetValue<'a, Quat>) -> Self {
+    pub fn from_get_set(value: GetSetValue<'a, DQuat>) -> Self {
         Self {
             value,
             label: String::from("rotation: "),
@@ -0,0 +1,168 @@
+use eframe::egui::{Color32, ComboBox, RichText, ScrollArea, TextEdit};
+
+use crate::egui;
+use crate::util::log::{self, LogEntry, LogLevel, Subsystem};
+
+use super::ui_traits::UiElement;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConsoleDock {
+    Hidden,
+    Bottom,
+    Floating,
+}
+
+/*
+ * @brief
+ * Shows the log. It can be docked at the bottom of the window or float on its own,
+ * and filtered by level, subsystem and text.
+ */
+pub struct ConsoleView {
+    dock: ConsoleDock,
+    min_level: LogLevel,
+    subsystems: Vec<Subsystem>,
+    search: String,
+    /* entries passing the filters and the log revision they were read at */
+    entries: Vec<LogEntry>,
+    entries_revision: Option<u64>,
+}
+
+impl ConsoleView {
+    pub fn new() -> Self {
+        Self {
+            dock: ConsoleDock::Hidden,
+            min_level: LogLevel::Info,
+            subsystems: Subsystem::ALL.to_vec(),
+            search: String::new(),
+            entries: Vec::new(),
+            entries_revision: None,
+        }
+    }
+
+    pub fn get_dock(&self) -> ConsoleDock {
+        self.dock
+    }
+
+    /* Hides the console, or docks it at the bottom if it was hidden */
+    pub fn toggle(&mut self) {
+        self.dock = match self.dock {
+            ConsoleDock::Hidden => ConsoleDock::Bottom,
+            _ => ConsoleDock::Hidden,
+        };
+    }
+
+    /* Number of errors in the log, so they can be pointed out while the console is hidden */
+    pub fn get_error_count(&self) -> usize {
+        log::get_count(LogLevel::Error)
+    }
+
+    /* Has to be called before the central panel so the bottom panel takes its space */
+    pub fn show(&mut self, ctx: &egui::Context) {
+        match self.dock {
+            ConsoleDock::Hidden => (),
+            ConsoleDock::Bottom => {
+                egui::TopBottomPanel::bottom("console_panel")
+                    .resizable(true)
+                    .default_height(160.0)
+                    .show(ctx, |ui| self.render(ui));
+            }
+            ConsoleDock::Floating => {
+                let mut open = true;
+                egui::Window::new("Console")
+                    .open(&mut open)
+                    .default_size(egui::vec2(500.0, 240.0))
+                    .show(ctx, |ui| self.render(ui));
+                if !open {
+                    self.dock = ConsoleDock::Hidden;
+                }
+            }
+        }
+    }
+
+    fn refresh(&mut self) {
+        let revision = log::get_revision();
+        if self.entries_revision == Some(revision) {
+            return;
+        }
+        let search = self.search.to_lowercase();
+        self.entries = log::get_entries(|entry| {
+            entry.level >= self.min_level
+                && self.subsystems.contains(&entry.subsystem)
+                && (search.is_empty() || entry.message.to_lowercase().contains(&search))
+        });
+        self.entries_revision = Some(revision);
+    }
+
+    fn level_color(ui: &egui::Ui, level: LogLevel) -> Color32 {
+        match level {
+            LogLevel::Debug => ui.style().visuals.weak_text_color(),
+            LogLevel::Info => ui.style().visuals.text_color(),
+            LogLevel::Warning => ui.style().visuals.warn_fg_color,
+            LogLevel::Error => ui.style().visuals.error_fg_color,
+        }
+    }
+
+    fn toolbar_render(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut filters_changed = false;
+        ui.horizontal_wrapped(|ui| {
+            ComboBox::from_id_source("console_level")
+                .selected_text(self.min_level.to_string())
+                .show_ui(ui, |ui| {
+                    for level in LogLevel::ALL {
+                        filters_changed |= ui.selectable_value(&mut self.min_level, level, level.to_string()).changed();
+                    }
+                });
+            for subsystem in Subsystem::ALL {
+                let mut enabled = self.subsystems.contains(&subsystem);
+                if ui.checkbox(&mut enabled, subsystem.to_string()).changed() {
+                    self.subsystems.retain(|other| *other != subsystem);
+                    if enabled {
+                        self.subsystems.push(subsystem);
+                    }
+                    filters_changed = true;
+                }
+            }
+            filters_changed |= ui.add(TextEdit::singleline(&mut self.search).hint_text("search").desired_width(120.0)).changed();
+            if ui.button("Clear").clicked() {
+                log::clear();
+            }
+            let (text, dock) = match self.dock {
+                ConsoleDock::Floating => ("Dock", ConsoleDock::Bottom),
+                _ => ("Undock", ConsoleDock::Floating),
+            };
+            if ui.button(text).clicked() {
+                self.dock = dock;
+            }
+        });
+        filters_changed
+    }
+}
+
+impl Default for ConsoleView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UiElement for ConsoleView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        if self.toolbar_render(ui) {
+            self.entries_revision = None;
+        }
+        self.refresh();
+        ui.separator();
+
+        ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for entry in &self.entries {
+                    let text = format!("{:>8.2} [{}] [{}] {}", entry.time, entry.level, entry.subsystem, entry.message);
+                    let response = ui.label(RichText::new(text).monospace().color(Self::level_color(ui, entry.level)));
+                    if let Some(details) = &entry.details {
+                        response.on_hover_text(details);
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,68 @@
+use std::rc::Rc;
+
+use eframe::egui::DragValue;
+
+use crate::egui;
+use crate::{Arc, Mutex};
+use crate::structures::fractal::{Fractal, MandelbulbFractal};
+use crate::structures::history::Command;
+use crate::structures::scene::Scene;
+
+use super::shared_state::SharedState;
+use super::ui_traits::UiElement;
+
+/*
+ * @brief
+ * Shows the parameters of the fractal in the scene.
+ */
+pub struct FractalView {
+    scene: Arc<Mutex<Scene>>,
+    shared_state: Rc<SharedState>,
+}
+
+impl FractalView {
+    pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<SharedState>) -> Self {
+        Self {
+            scene,
+            shared_state,
+        }
+    }
+}
+
+impl UiElement for FractalView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        let mut scene = self.scene.lock();
+        let old = scene.get_fractal().copied();
+        let mut enabled = old.is_some();
+        if ui.checkbox(&mut enabled, "enabled").changed() {
+            scene.set_fractal(if enabled { Some(Fractal::default_mandelbulb()) } else { None });
+        }
+        if let Some(fractal) = scene.get_mut_fractal() {
+            ui.label(fractal.get_name());
+            match fractal {
+                Fractal::Mandelbulb(mandelbulb) => Self::mandelbulb_render(ui, mandelbulb),
+            }
+        }
+        let new = scene.get_fractal().copied();
+        if old != new {
+            self.shared_state.get_history().push(Command::SetFractal { old, new });
+        }
+    }
+}
+
+impl FractalView {
+    fn mandelbulb_render(ui: &mut egui::Ui, mandelbulb: &mut MandelbulbFractal) {
+        ui.horizontal(|ui| {
+            ui.label("power: ");
+            ui.add(DragValue::new(mandelbulb.get_mut_power()).speed(0.01).clamp_range(1.0..=32.0));
+        });
+        ui.horizontal(|ui| {
+            ui.label("iterations: ");
+            ui.add(DragValue::new(mandelbulb.get_mut_iterations()).clamp_range(1..=256));
+        });
+        ui.horizontal(|ui| {
+            ui.label("bailout: ");
+            ui.add(DragValue::new(mandelbulb.get_mut_bailout()).speed(0.01).clamp_range(1.0..=f64::MAX));
+        });
+    }
+}
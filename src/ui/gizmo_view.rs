@@ -0,0 +1,498 @@
+use std::f64::consts::PI;
+
+use eframe::egui::{self, Color32, DragValue, Pos2, Rect, Shape, Stroke};
+use glam::{DMat4, DQuat, DVec3, Vec2};
+
+use crate::resources::container::ContainerRef;
+use crate::resources::object::Object;
+use crate::structures::camera::Camera;
+use crate::structures::ray::Ray;
+use crate::structures::scene::Scene;
+use crate::structures::transform::Transform;
+
+use super::key_bindings::{Action, KeyBindings};
+
+/* How close (in pixels) the pointer needs to be to grab a handle */
+const HANDLE_RADIUS: f32 = 6.0;
+/* Size of the gizmo as a fraction of its distance from the camera */
+const GIZMO_SCALE: f64 = 0.2;
+/* Smallest size a scale drag leaves on an axis, 0 would make the world matrix singular */
+const MIN_SCALE: f64 = 0.001;
+const RING_SEGMENTS: usize = 48;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GizmoSpace {
+    World,
+    Local,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum GizmoHandle {
+    Axis(usize),
+    /* plane perpendicular to the axis */
+    Plane(usize),
+    Uniform,
+}
+
+struct GizmoDrag {
+    handle: GizmoHandle,
+    start_pointer: Pos2,
+    start_transform: Transform,
+    start_world: DMat4,
+    parent_matrix: DMat4,
+    /* point on the axis or plane that was grabbed */
+    start_hit: DVec3,
+}
+
+/* Everything needed to draw and hit test the gizmo for one frame */
+struct GizmoFrame {
+    origin: DVec3,
+    axes: [DVec3; 3],
+    size: f64,
+    rect: Rect,
+}
+
+/*
+ * @brief
+ * Translate/rotate/scale manipulator drawn over the viewport for the selected object.
+ */
+pub struct GizmoView {
+    mode: GizmoMode,
+    space: GizmoSpace,
+    snapping: bool,
+    translate_snap: f64,
+    rotate_snap: f64,
+    scale_snap: f64,
+    drag: Option<GizmoDrag>,
+}
+
+impl GizmoView {
+    pub fn new() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::World,
+            snapping: false,
+            translate_snap: 0.1,
+            rotate_snap: 15.0,
+            scale_snap: 0.1,
+            drag: None,
+        }
+    }
+
+    /* Settings shown in the side panel */
+    pub fn render_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.mode, GizmoMode::Translate, "move (G)");
+            ui.selectable_value(&mut self.mode, GizmoMode::Rotate, "rotate (R)");
+            ui.selectable_value(&mut self.mode, GizmoMode::Scale, "scale (S)");
+        });
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.space, GizmoSpace::World, "world");
+            ui.radio_value(&mut self.space, GizmoSpace::Local, "local");
+        });
+        ui.checkbox(&mut self.snapping, "snapping");
+        if self.snapping {
+            ui.horizontal(|ui| {
+                ui.label("move: ");
+                ui.add(DragValue::new(&mut self.translate_snap).speed(0.01).clamp_range(0.001..=f64::MAX));
+            });
+            ui.horizontal(|ui| {
+                ui.label("rotate (°): ");
+                ui.add(DragValue::new(&mut self.rotate_snap).speed(0.5).clamp_range(0.1..=180.0));
+            });
+            ui.horizontal(|ui| {
+                ui.label("scale: ");
+                ui.add(DragValue::new(&mut self.scale_snap).speed(0.01).clamp_range(0.001..=f64::MAX));
+            });
+        }
+    }
+
+    /*
+     * Draws the gizmo for object and handles dragging it.
+     * Returns true if the pointer is being used by the gizmo so the viewport should ignore it.
+     */
+    pub fn update(&mut self, ui: &mut egui::Ui, rect: Rect, response: &egui::Response, scene: &mut Scene, object: &ContainerRef<Object>, key_bindings: &KeyBindings) -> bool {
+        if response.hovered() && !ui.ctx().wants_keyboard_input() && !ui.input().pointer.secondary_down() {
+            let input = ui.input();
+            if key_bindings.is_pressed(&input, Action::GizmoTranslate) {
+                self.mode = GizmoMode::Translate;
+            }
+            if key_bindings.is_pressed(&input, Action::GizmoRotate) {
+                self.mode = GizmoMode::Rotate;
+            }
+            if key_bindings.is_pressed(&input, Action::GizmoScale) {
+                self.mode = GizmoMode::Scale;
+            }
+        }
+
+        let (world_matrix, transform) = {
+            let object = object.lock().unwrap();
+            (scene.get_world_matrix(&object), *object.get_transform())
+        };
+        let frame = self.get_frame(scene.get_camera(), &world_matrix, rect);
+        let pointer = ui.input().pointer.interact_pos();
+        let hovered_handle = match (&self.drag, pointer) {
+            (Some(drag), _) => Some(drag.handle),
+            (None, Some(pointer)) if response.hovered() => self.hit_test(scene.get_camera(), &frame, pointer),
+            _ => None,
+        };
+
+        if response.drag_started() {
+            if let (Some(handle), Some(pointer)) = (hovered_handle, pointer) {
+                let parent_matrix = world_matrix * transform.get_matrix().inverse();
+                let start_hit = Self::grab_point(scene.get_camera(), &frame, handle, pointer).unwrap_or(frame.origin);
+                self.drag = Some(GizmoDrag {
+                    handle,
+                    start_pointer: pointer,
+                    start_transform: transform,
+                    start_world: world_matrix,
+                    parent_matrix,
+                    start_hit,
+                });
+            }
+        }
+
+        if let (Some(drag), Some(pointer)) = (&self.drag, pointer) {
+            if response.dragged() {
+                // The handles are measured where they were when the drag started
+                let start_frame = self.get_frame(scene.get_camera(), &drag.start_world, rect);
+                let new_transform = self.drag_transform(scene.get_camera(), &start_frame, drag, pointer);
+                object.lock().unwrap().set_transform(new_transform);
+            }
+        }
+
+        self.paint(ui, scene.get_camera(), &frame, hovered_handle);
+
+        let used = self.drag.is_some() || hovered_handle.is_some();
+        if response.drag_released() || !ui.input().pointer.any_down() {
+            self.drag = None;
+        }
+        used
+    }
+
+    fn get_frame(&self, camera: &Camera, world_matrix: &DMat4, rect: Rect) -> GizmoFrame {
+        let (_, rotation, origin) = world_matrix.to_scale_rotation_translation();
+        let axes = match self.space {
+            GizmoSpace::World => [DVec3::X, DVec3::Y, DVec3::Z],
+            GizmoSpace::Local => [rotation * DVec3::X, rotation * DVec3::Y, rotation * DVec3::Z],
+        };
+        // Scale always works along the object's own axes
+        let axes = match self.mode {
+            GizmoMode::Scale => [rotation * DVec3::X, rotation * DVec3::Y, rotation * DVec3::Z],
+            _ => axes,
+        };
+        let size = (origin - camera.get_position()).length() * GIZMO_SCALE;
+        GizmoFrame {
+            origin,
+            axes,
+            size,
+            rect,
+        }
+    }
+
+    fn to_screen(camera: &Camera, frame: &GizmoFrame, point: DVec3) -> Option<Pos2> {
+        camera.world_to_screen(point).map(|point| frame.rect.min + egui::vec2(point.x, point.y))
+    }
+
+    fn pointer_ray(camera: &Camera, frame: &GizmoFrame, pointer: Pos2) -> Ray {
+        let position = pointer - frame.rect.min;
+        camera.get_ray(Vec2::new(position.x, position.y))
+    }
+
+    fn plane_corners(frame: &GizmoFrame, axis: usize) -> [DVec3; 4] {
+        let a = frame.axes[(axis + 1) % 3] * frame.size;
+        let b = frame.axes[(axis + 2) % 3] * frame.size;
+        [
+            frame.origin + a * 0.2 + b * 0.2,
+            frame.origin + a * 0.4 + b * 0.2,
+            frame.origin + a * 0.4 + b * 0.4,
+            frame.origin + a * 0.2 + b * 0.4,
+        ]
+    }
+
+    fn ring_points(frame: &GizmoFrame, axis: usize) -> Vec<DVec3> {
+        let a = frame.axes[(axis + 1) % 3] * frame.size;
+        let b = frame.axes[(axis + 2) % 3] * frame.size;
+        (0..RING_SEGMENTS)
+            .map(|i| {
+                let angle = i as f64 / RING_SEGMENTS as f64 * PI * 2.0;
+                frame.origin + a * angle.cos() + b * angle.sin()
+            })
+            .collect()
+    }
+
+    fn hit_test(&self, camera: &Camera, frame: &GizmoFrame, pointer: Pos2) -> Option<GizmoHandle> {
+        let center = Self::to_screen(camera, frame, frame.origin)?;
+        match self.mode {
+            GizmoMode::Translate => {
+                for axis in 0..3 {
+                    let corners: Vec<Pos2> = Self::plane_corners(frame, axis)
+                        .iter()
+                        .filter_map(|corner| Self::to_screen(camera, frame, *corner))
+                        .collect();
+                    if corners.len() == 4 && point_in_polygon(pointer, &corners) {
+                        return Some(GizmoHandle::Plane(axis));
+                    }
+                }
+                self.hit_test_axes(camera, frame, center, pointer)
+            }
+            GizmoMode::Rotate => {
+                for axis in 0..3 {
+                    let points: Vec<Pos2> = Self::ring_points(frame, axis)
+                        .iter()
+                        .filter_map(|point| Self::to_screen(camera, frame, *point))
+                        .collect();
+                    for i in 0..points.len() {
+                        let next = points[(i + 1) % points.len()];
+                        if distance_to_segment(pointer, points[i], next) < HANDLE_RADIUS {
+                            return Some(GizmoHandle::Axis(axis));
+                        }
+                    }
+                }
+                None
+            }
+            GizmoMode::Scale => {
+                if pointer.distance(center) < HANDLE_RADIUS * 1.5 {
+                    return Some(GizmoHandle::Uniform);
+                }
+                self.hit_test_axes(camera, frame, center, pointer)
+            }
+        }
+    }
+
+    fn hit_test_axes(&self, camera: &Camera, frame: &GizmoFrame, center: Pos2, pointer: Pos2) -> Option<GizmoHandle> {
+        for axis in 0..3 {
+            if let Some(end) = Self::to_screen(camera, frame, frame.origin + frame.axes[axis] * frame.size) {
+                if distance_to_segment(pointer, center, end) < HANDLE_RADIUS {
+                    return Some(GizmoHandle::Axis(axis));
+                }
+            }
+        }
+        None
+    }
+
+    /* Point on the handle's axis or plane under the pointer */
+    fn grab_point(camera: &Camera, frame: &GizmoFrame, handle: GizmoHandle, pointer: Pos2) -> Option<DVec3> {
+        let ray = Self::pointer_ray(camera, frame, pointer);
+        match handle {
+            GizmoHandle::Axis(axis) => {
+                let direction = frame.axes[axis];
+                let offset = frame.origin - ray.get_origin();
+                let b = direction.dot(ray.get_direction());
+                let c = ray.get_direction().length_squared();
+                let d = direction.dot(offset);
+                let e = ray.get_direction().dot(offset);
+                let denominator = c - b * b;
+                if denominator.abs() < f64::EPSILON {
+                    return None;
+                }
+                Some(frame.origin + direction * ((b * e - c * d) / denominator))
+            }
+            GizmoHandle::Plane(axis) => {
+                let normal = frame.axes[axis];
+                let denominator = ray.get_direction().dot(normal);
+                if denominator.abs() < f64::EPSILON {
+                    return None;
+                }
+                Some(ray.at((frame.origin - ray.get_origin()).dot(normal) / denominator))
+            }
+            GizmoHandle::Uniform => None,
+        }
+    }
+
+    fn snap(&self, value: f64, step: f64) -> f64 {
+        if self.snapping && step > 0.0 {
+            (value / step).round() * step
+        } else {
+            value
+        }
+    }
+
+    /*
+     * A scale component after a drag of factor. Dragging past the center or snapping down to 0
+     * stops at MIN_SCALE so the world matrix stays invertible, a mirrored start stays mirrored.
+     */
+    fn scaled(&self, start: f64, factor: f64) -> f64 {
+        let value = self.snap(start.abs() * factor.max(0.0), self.scale_snap);
+        value.max(MIN_SCALE).copysign(start)
+    }
+
+    fn drag_transform(&self, camera: &Camera, frame: &GizmoFrame, drag: &GizmoDrag, pointer: Pos2) -> Transform {
+        let mut result = drag.start_transform;
+        let (_, start_rotation, start_position) = drag.start_world.to_scale_rotation_translation();
+        match self.mode {
+            GizmoMode::Translate => {
+                let hit = match Self::grab_point(camera, frame, drag.handle, pointer) {
+                    Some(hit) => hit,
+                    None => return result,
+                };
+                let delta = hit - drag.start_hit;
+                let delta = match drag.handle {
+                    GizmoHandle::Axis(axis) => frame.axes[axis] * self.snap(delta.dot(frame.axes[axis]), self.translate_snap),
+                    GizmoHandle::Plane(axis) => {
+                        let a = frame.axes[(axis + 1) % 3];
+                        let b = frame.axes[(axis + 2) % 3];
+                        a * self.snap(delta.dot(a), self.translate_snap) + b * self.snap(delta.dot(b), self.translate_snap)
+                    }
+                    GizmoHandle::Uniform => DVec3::ZERO,
+                };
+                result.set_position(drag.parent_matrix.inverse().transform_point3(start_position + delta));
+            }
+            GizmoMode::Rotate => {
+                let axis = match drag.handle {
+                    GizmoHandle::Axis(axis) => frame.axes[axis],
+                    _ => return result,
+                };
+                let center = match Self::to_screen(camera, frame, frame.origin) {
+                    Some(center) => center,
+                    None => return result,
+                };
+                let start = drag.start_pointer - center;
+                let current = pointer - center;
+                let mut angle = (start.x * current.y - start.y * current.x).atan2(start.dot(current)) as f64;
+                // Screen space y points down, so a clockwise drag is a positive angle
+                if axis.dot(camera.get_position() - frame.origin) > 0.0 {
+                    angle = -angle;
+                }
+                let angle = self.snap(angle.to_degrees(), self.rotate_snap).to_radians();
+                let world_rotation = DQuat::from_axis_angle(axis, angle) * start_rotation;
+                let (_, parent_rotation, _) = drag.parent_matrix.to_scale_rotation_translation();
+                result.set_rotation((parent_rotation.inverse() * world_rotation).normalize());
+            }
+            GizmoMode::Scale => {
+                let center = match Self::to_screen(camera, frame, frame.origin) {
+                    Some(center) => center,
+                    None => return result,
+                };
+                let factor = match drag.handle {
+                    GizmoHandle::Axis(axis) => {
+                        let end = match Self::to_screen(camera, frame, frame.origin + frame.axes[axis] * frame.size) {
+                            Some(end) => end,
+                            None => return result,
+                        };
+                        let direction = end - center;
+                        let length = direction.length();
+                        if length < 1.0 {
+                            return result;
+                        }
+                        1.0 + ((pointer - drag.start_pointer).dot(direction / length) / length) as f64
+                    }
+                    _ => {
+                        let start = drag.start_pointer.distance(center);
+                        if start < 1.0 {
+                            return result;
+                        }
+                        (pointer.distance(center) / start) as f64
+                    }
+                };
+                let mut scale = drag.start_transform.get_scale();
+                match drag.handle {
+                    GizmoHandle::Axis(axis) => scale[axis] = self.scaled(scale[axis], factor),
+                    _ => scale = DVec3::from_array(scale.to_array().map(|value| self.scaled(value, factor))),
+                }
+                result.set_scale(scale);
+            }
+        }
+        result
+    }
+
+    fn paint(&self, ui: &mut egui::Ui, camera: &Camera, frame: &GizmoFrame, hovered: Option<GizmoHandle>) {
+        let painter = ui.painter().with_clip_rect(frame.rect);
+        let center = match Self::to_screen(camera, frame, frame.origin) {
+            Some(center) => center,
+            None => return,
+        };
+        let color = |handle: GizmoHandle, axis: usize| {
+            if Some(handle) == hovered {
+                Color32::YELLOW
+            } else {
+                AXIS_COLORS[axis]
+            }
+        };
+        match self.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                for axis in 0..3 {
+                    let end = match Self::to_screen(camera, frame, frame.origin + frame.axes[axis] * frame.size) {
+                        Some(end) => end,
+                        None => continue,
+                    };
+                    let color = color(GizmoHandle::Axis(axis), axis);
+                    painter.line_segment([center, end], Stroke::new(2.0, color));
+                    match self.mode {
+                        GizmoMode::Translate => painter.circle_filled(end, 4.0, color),
+                        _ => painter.rect_filled(Rect::from_center_size(end, egui::vec2(8.0, 8.0)), 0.0, color),
+                    }
+                }
+                if self.mode == GizmoMode::Translate {
+                    for axis in 0..3 {
+                        let corners: Vec<Pos2> = Self::plane_corners(frame, axis)
+                            .iter()
+                            .filter_map(|corner| Self::to_screen(camera, frame, *corner))
+                            .collect();
+                        if corners.len() == 4 {
+                            let color = color(GizmoHandle::Plane(axis), axis);
+                            painter.add(Shape::convex_polygon(corners, color.linear_multiply(0.4), Stroke::new(1.0, color)));
+                        }
+                    }
+                } else {
+                    let color = match hovered {
+                        Some(GizmoHandle::Uniform) => Color32::YELLOW,
+                        _ => Color32::WHITE,
+                    };
+                    painter.rect_filled(Rect::from_center_size(center, egui::vec2(10.0, 10.0)), 0.0, color);
+                }
+            }
+            GizmoMode::Rotate => {
+                for axis in 0..3 {
+                    let points: Vec<Pos2> = Self::ring_points(frame, axis)
+                        .iter()
+                        .filter_map(|point| Self::to_screen(camera, frame, *point))
+                        .collect();
+                    painter.add(Shape::closed_line(points, Stroke::new(2.0, color(GizmoHandle::Axis(axis), axis))));
+                }
+            }
+        }
+    }
+}
+
+impl Default for GizmoView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const AXIS_COLORS: [Color32; 3] = [
+    Color32::from_rgb(230, 60, 60),
+    Color32::from_rgb(60, 200, 60),
+    Color32::from_rgb(70, 110, 240),
+];
+
+fn distance_to_segment(point: Pos2, start: Pos2, end: Pos2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_sq();
+    if length_squared == 0.0 {
+        return point.distance(start);
+    }
+    let t = ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0);
+    point.distance(start + segment * t)
+}
+
+fn point_in_polygon(point: Pos2, polygon: &[Pos2]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > point.y) != (b.y > point.y) && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
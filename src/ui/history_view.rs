@@ -0,0 +1,117 @@
+use std::rc::Rc;
+
+use eframe::egui::ScrollArea;
+
+use crate::egui;
+use crate::resources::container::ContainerRef;
+use crate::{Arc, Mutex};
+use crate::structures::scene::Scene;
+use crate::util::log;
+
+use super::shared_state::SharedState;
+use super::ui_traits::UiElement;
+
+/*
+ * @brief
+ * List of the recorded edits. Clicking an entry undoes or redoes everything up to it.
+ */
+pub struct HistoryView {
+    scene: Arc<Mutex<Scene>>,
+    shared_state: Rc<SharedState>,
+}
+
+impl HistoryView {
+    pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<SharedState>) -> Self {
+        Self {
+            scene,
+            shared_state,
+        }
+    }
+
+    pub fn undo(&self, steps: usize) {
+        let mut scene = self.scene.lock();
+        for _ in 0..steps {
+            match self.shared_state.get_history().undo(&mut scene) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(err) => {
+                    log::error("Could not undo", &err);
+                    break;
+                }
+            }
+        }
+        self.fix_selection(&scene);
+    }
+
+    pub fn redo(&self, steps: usize) {
+        let mut scene = self.scene.lock();
+        for _ in 0..steps {
+            match self.shared_state.get_history().redo(&mut scene) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(err) => {
+                    log::error("Could not redo", &err);
+                    break;
+                }
+            }
+        }
+        self.fix_selection(&scene);
+    }
+
+    /* Undoing or redoing may remove the selected object from the scene */
+    fn fix_selection(&self, scene: &Scene) {
+        if let Some(selected) = self.shared_state.get_selected_object() {
+            if !scene.get_objects().iter().any(|object| ContainerRef::ptr_eq(object, &selected)) {
+                self.shared_state.set_selected_object(None);
+            }
+        }
+    }
+}
+
+impl UiElement for HistoryView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        let (undo_names, redo_names): (Vec<String>, Vec<String>) = {
+            let history = self.shared_state.get_history();
+            (
+                history.get_undo_stack().iter().map(|command| command.get_description()).collect(),
+                history.get_redo_stack().iter().rev().map(|command| command.get_description()).collect(),
+            )
+        };
+
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!undo_names.is_empty(), egui::Button::new("Undo")).clicked() {
+                self.undo(1);
+            }
+            if ui.add_enabled(!redo_names.is_empty(), egui::Button::new("Redo")).clicked() {
+                self.redo(1);
+            }
+        });
+
+        let mut undo_steps = 0;
+        let mut redo_steps = 0;
+        ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            if ui.selectable_label(undo_names.is_empty(), "Initial state").clicked() {
+                undo_steps = undo_names.len();
+            }
+            for (i, name) in undo_names.iter().enumerate() {
+                let current = i + 1 == undo_names.len();
+                if ui.selectable_label(current, name).clicked() {
+                    undo_steps = undo_names.len() - i - 1;
+                }
+            }
+            for (i, name) in redo_names.iter().enumerate() {
+                let text = egui::RichText::new(name).color(ui.style().visuals.weak_text_color());
+                if ui.selectable_label(false, text).clicked() {
+                    redo_steps = i + 1;
+                }
+            }
+        });
+
+        if undo_steps > 0 {
+            self.undo(undo_steps);
+        }
+        if redo_steps > 0 {
+            self.redo(redo_steps);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::rc::Rc;
+
+use crate::egui;
+use crate::{Arc, Mutex};
+use crate::structures::scene::Scene;
+
+use super::camera_view::CameraView;
+use super::fractal_view::FractalView;
+use super::light_view::LightView;
+use super::material_view::MaterialView;
+use super::object_view::ObjectView;
+use super::shared_state::{SharedState, Selection};
+use super::ui_traits::UiElement;
+
+/*
+ * @brief
+ * Left panel showing the properties of whatever is selected.
+ */
+pub struct InspectorView {
+    shared_state: Rc<SharedState>,
+    camera_view: CameraView,
+    object_view: ObjectView,
+    material_view: MaterialView,
+    light_view: LightView,
+    fractal_view: FractalView,
+}
+
+impl InspectorView {
+    pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<SharedState>) -> Self {
+        Self {
+            shared_state: shared_state.clone(),
+            camera_view: CameraView::new(scene.clone(), shared_state.clone()),
+            object_view: ObjectView::new(scene.clone(), shared_state.clone()),
+            material_view: MaterialView::new(shared_state.clone()),
+            light_view: LightView::new(scene.clone(), shared_state.clone()),
+            fractal_view: FractalView::new(scene, shared_state),
+        }
+    }
+}
+
+impl UiElement for InspectorView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        match self.shared_state.get_selection() {
+            Selection::Nothing => {
+                ui.heading("Inspector");
+                ui.label("Nothing selected");
+            }
+            Selection::Camera => {
+                ui.heading("Camera");
+                self.camera_view.render(ui);
+            }
+            Selection::Object(_) => {
+                ui.heading("Object");
+                self.object_view.render(ui);
+                ui.separator();
+                egui::CollapsingHeader::new("Material").default_open(true).show(ui, |ui| {
+                    self.material_view.render(ui);
+                });
+            }
+            Selection::Light => {
+                ui.heading("Light");
+                self.light_view.render(ui);
+            }
+            Selection::Fractal => {
+                ui.heading("Fractal");
+                self.fractal_view.render(ui);
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use std::fmt::Display;
+
+use eframe::egui::{self, Key, InputState};
+
+use crate::resources::parser::TextParser;
+use crate::resources::resource_file::{key_bindings_dir, load_file, save_file};
+use crate::util::error::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    GizmoTranslate,
+    GizmoRotate,
+    GizmoScale,
+    FrameSelected,
+    ToggleLeftPanel,
+    ToggleRightPanel,
+    ToggleConsole,
+    Undo,
+    Redo,
+    Screenshot,
+}
+
+/*
+ * Where an action is listened for.
+ * Fly actions only apply while the right mouse button is held and viewport actions only while it isn't,
+ * so the two can share keys.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BindingContext {
+    Fly,
+    Viewport,
+    Global,
+}
+
+impl BindingContext {
+    pub fn overlaps(&self, other: &BindingContext) -> bool {
+        self == other || *self == BindingContext::Global || *other == BindingContext::Global
+    }
+}
+
+impl Action {
+    pub const ALL: [Action; 16] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::GizmoTranslate,
+        Action::GizmoRotate,
+        Action::GizmoScale,
+        Action::FrameSelected,
+        Action::ToggleLeftPanel,
+        Action::ToggleRightPanel,
+        Action::ToggleConsole,
+        Action::Undo,
+        Action::Redo,
+        Action::Screenshot,
+    ];
+
+    /* Name used in the bindings file */
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::GizmoTranslate => "gizmo_translate",
+            Action::GizmoRotate => "gizmo_rotate",
+            Action::GizmoScale => "gizmo_scale",
+            Action::FrameSelected => "frame_selected",
+            Action::ToggleLeftPanel => "toggle_left_panel",
+            Action::ToggleRightPanel => "toggle_right_panel",
+            Action::ToggleConsole => "toggle_console",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Screenshot => "screenshot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().find(|action| action.get_name() == name).copied()
+    }
+
+    /* Name shown in the ui */
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBackward => "Move backward",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::GizmoTranslate => "Gizmo translate",
+            Action::GizmoRotate => "Gizmo rotate",
+            Action::GizmoScale => "Gizmo scale",
+            Action::FrameSelected => "Frame selected",
+            Action::ToggleLeftPanel => "Toggle left panel",
+            Action::ToggleRightPanel => "Toggle right panel",
+            Action::ToggleConsole => "Toggle console",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Screenshot => "Screenshot",
+        }
+    }
+
+    pub fn get_context(&self) -> BindingContext {
+        match self {
+            Action::MoveForward | Action::MoveBackward | Action::MoveLeft |
+            Action::MoveRight | Action::MoveUp | Action::MoveDown => BindingContext::Fly,
+            Action::GizmoTranslate | Action::GizmoRotate | Action::GizmoScale |
+            Action::FrameSelected => BindingContext::Viewport,
+            Action::ToggleLeftPanel | Action::ToggleRightPanel | Action::ToggleConsole |
+            Action::Undo | Action::Redo | Action::Screenshot => BindingContext::Global,
+        }
+    }
+
+    fn get_default_bindings(&self) -> Vec<KeyBinding> {
+        match self {
+            Action::MoveForward => vec![KeyBinding::new(Key::W)],
+            Action::MoveBackward => vec![KeyBinding::new(Key::S)],
+            Action::MoveLeft => vec![KeyBinding::new(Key::A)],
+            Action::MoveRight => vec![KeyBinding::new(Key::D)],
+            Action::MoveUp => vec![KeyBinding::new(Key::E)],
+            Action::MoveDown => vec![KeyBinding::new(Key::Q)],
+            Action::GizmoTranslate => vec![KeyBinding::new(Key::G)],
+            Action::GizmoRotate => vec![KeyBinding::new(Key::R)],
+            Action::GizmoScale => vec![KeyBinding::new(Key::S)],
+            Action::FrameSelected => vec![KeyBinding::new(Key::F)],
+            Action::ToggleLeftPanel => vec![KeyBinding::new(Key::Num1).ctrl()],
+            Action::ToggleRightPanel => vec![KeyBinding::new(Key::Num2).ctrl()],
+            Action::ToggleConsole => vec![KeyBinding::new(Key::Num3).ctrl()],
+            Action::Undo => vec![KeyBinding::new(Key::Z).ctrl()],
+            Action::Redo => vec![KeyBinding::new(Key::Z).ctrl().shift(), KeyBinding::new(Key::Y).ctrl()],
+            Action::Screenshot => vec![KeyBinding::new(Key::F12)],
+        }
+    }
+}
+
+const KEY_NAMES: [(Key, &str); 63] = [
+    (Key::ArrowDown, "Down"), (Key::ArrowLeft, "Left"), (Key::ArrowRight, "Right"), (Key::ArrowUp, "Up"),
+    (Key::Escape, "Escape"), (Key::Tab, "Tab"), (Key::Backspace, "Backspace"), (Key::Enter, "Enter"), (Key::Space, "Space"),
+    (Key::Insert, "Insert"), (Key::Delete, "Delete"), (Key::Home, "Home"), (Key::End, "End"),
+    (Key::PageUp, "PageUp"), (Key::PageDown, "PageDown"),
+    (Key::Num0, "0"), (Key::Num1, "1"), (Key::Num2, "2"), (Key::Num3, "3"), (Key::Num4, "4"),
+    (Key::Num5, "5"), (Key::Num6, "6"), (Key::Num7, "7"), (Key::Num8, "8"), (Key::Num9, "9"),
+    (Key::A, "A"), (Key::B, "B"), (Key::C, "C"), (Key::D, "D"), (Key::E, "E"), (Key::F, "F"),
+    (Key::G, "G"), (Key::H, "H"), (Key::I, "I"), (Key::J, "J"), (Key::K, "K"), (Key::L, "L"),
+    (Key::M, "M"), (Key::N, "N"), (Key::O, "O"), (Key::P, "P"), (Key::Q, "Q"), (Key::R, "R"),
+    (Key::S, "S"), (Key::T, "T"), (Key::U, "U"), (Key::V, "V"), (Key::W, "W"), (Key::X, "X"),
+    (Key::Y, "Y"), (Key::Z, "Z"),
+    (Key::F1, "F1"), (Key::F2, "F2"), (Key::F3, "F3"), (Key::F4, "F4"), (Key::F5, "F5"),
+    (Key::F6, "F6"), (Key::F7, "F7"), (Key::F8, "F8"), (Key::F9, "F9"), (Key::F10, "F10"),
+    (Key::F11, "F11"), (Key::F12, "F12"),
+];
+
+pub fn key_name(key: Key) -> &'static str {
+    KEY_NAMES.iter()
+        .find(|(other, _)| *other == key)
+        .map(|(_, name)| *name)
+        .unwrap_or("Unknown")
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    KEY_NAMES.iter()
+        .find(|(_, other)| other.eq_ignore_ascii_case(name))
+        .map(|(key, _)| *key)
+}
+
+/* A key together with the modifiers that have to be held. ctrl means cmd on mac. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyBinding {
+    key: Key,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl KeyBinding {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+    pub fn from_modifiers(key: Key, modifiers: &egui::Modifiers) -> Self {
+        Self {
+            key,
+            ctrl: modifiers.command,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+        }
+    }
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    pub fn get_key(&self) -> Key {
+        self.key
+    }
+
+    /* Parses bindings like "ctrl+shift+Z" */
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = text.split('+').collect();
+        let key = key_from_name(parts.pop()?)?;
+        let mut result = Self::new(key);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "cmd" => result.ctrl = true,
+                "shift" => result.shift = true,
+                "alt" => result.alt = true,
+                _ => return None,
+            }
+        }
+        Some(result)
+    }
+
+    /* Extra modifiers are allowed so holding shift doesn't stop the camera */
+    pub fn is_down(&self, input: &InputState) -> bool {
+        input.key_down(self.key)
+            && (!self.ctrl || input.modifiers.command)
+            && (!self.shift || input.modifiers.shift)
+            && (!self.alt || input.modifiers.alt)
+    }
+
+    /* The modifiers have to match exactly so ctrl+Z doesn't also fire ctrl+shift+Z */
+    pub fn is_pressed(&self, input: &InputState) -> bool {
+        input.events.iter().any(|event| match event {
+            egui::Event::Key { key, pressed: true, modifiers } => *self == Self::from_modifiers(*key, modifiers),
+            _ => false,
+        })
+    }
+}
+
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+        write!(f, "{}", key_name(self.key))
+    }
+}
+
+/*
+ * @brief
+ * Maps actions to keys, any of which triggers the action. Saved in the asset directory as lines of
+ * "action bindings" with the bindings separated by commas, for example "move_forward W" or
+ * "redo ctrl+shift+Z,ctrl+Y". "none" leaves an action unbound.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<KeyBinding>)>,
+    /* Disabled while a new key is being captured so it doesn't also trigger its action */
+    enabled: bool,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL.iter().map(|action| (*action, action.get_default_bindings())).collect(),
+            enabled: true,
+        }
+    }
+}
+
+impl KeyBindings {
+    /* Loads the bindings file, falling back to the defaults if there isn't one */
+    pub fn load() -> Result<Self, Error> {
+        let dir = key_bindings_dir()?;
+        if !dir.exists() {
+            return Ok(Self::default());
+        }
+        Self::parse(&load_file(dir)?)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        save_file(key_bindings_dir()?, &self.to_text())
+    }
+
+    /* Actions missing from text keep their default binding */
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut result = Self::default();
+        let mut parser = TextParser::create_from_str(text);
+        let mut line_number = 0;
+        while parser.get_remaining_bytes() > 0 {
+            line_number += 1;
+            let words = parser.next_words();
+            if words.is_empty() || words[0].starts_with('#') {
+                continue;
+            }
+            if words.len() != 2 {
+                return Err(Error::key_binding_parser(String::from("expected an action and a binding"), line_number));
+            }
+            let action = match Action::from_name(words[0]) {
+                Some(action) => action,
+                None => return Err(Error::key_binding_parser(format!("unknown action {}", words[0]), line_number)),
+            };
+            let mut bindings = Vec::new();
+            if words[1] != "none" {
+                for binding in words[1].split(',') {
+                    match KeyBinding::parse(binding) {
+                        Some(binding) => bindings.push(binding),
+                        None => return Err(Error::key_binding_parser(format!("unknown binding {}", binding), line_number)),
+                    }
+                }
+            }
+            result.set(action, bindings);
+        }
+        Ok(result)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut result = String::new();
+        for (action, bindings) in &self.bindings {
+            let bindings = match bindings.is_empty() {
+                true => String::from("none"),
+                false => bindings.iter().map(KeyBinding::to_string).collect::<Vec<String>>().join(","),
+            };
+            result.push_str(&format!("{} {}\n", action.get_name(), bindings));
+        }
+        result
+    }
+
+    pub fn get(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.iter().find(|(other, _)| *other == action).map_or(&[], |(_, bindings)| bindings)
+    }
+
+    pub fn set(&mut self, action: Action, bindings: Vec<KeyBinding>) {
+        for (other, other_bindings) in &mut self.bindings {
+            if *other == action {
+                *other_bindings = bindings.clone();
+            }
+        }
+    }
+
+    /* Adds another key for action, keeping the ones it has */
+    pub fn add(&mut self, action: Action, binding: KeyBinding) {
+        for (other, other_bindings) in &mut self.bindings {
+            if *other == action && !other_bindings.contains(&binding) {
+                other_bindings.push(binding);
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_down(&self, input: &InputState, action: Action) -> bool {
+        self.enabled && self.get(action).iter().any(|binding| binding.is_down(input))
+    }
+
+    pub fn is_pressed(&self, input: &InputState, action: Action) -> bool {
+        self.enabled && self.get(action).iter().any(|binding| binding.is_pressed(input))
+    }
+
+    /* Pairs of actions that share a binding and can be triggered at the same time */
+    pub fn get_conflicts(&self) -> Vec<(Action, Action)> {
+        let mut result = Vec::new();
+        for (i, (action, bindings)) in self.bindings.iter().enumerate() {
+            for (other_action, other_bindings) in &self.bindings[i + 1..] {
+                let shared = bindings.iter().any(|binding| other_bindings.contains(binding));
+                if shared && action.get_context().overlaps(&other_action.get_context()) {
+                    result.push((*action, *other_action));
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::egui::{self, InputState, Key, Modifiers};
+
+    use super::{Action, KeyBinding, KeyBindings};
+
+    fn pressed(key: Key, modifiers: Modifiers) -> InputState {
+        let mut input = InputState::default();
+        input.events.push(egui::Event::Key { key, pressed: true, modifiers });
+        input
+    }
+
+    #[test]
+    fn parse_binding() {
+        assert_eq!(KeyBinding::parse("W"), Some(KeyBinding::new(Key::W)));
+        assert_eq!(KeyBinding::parse("ctrl+shift+z"), Some(KeyBinding::new(Key::Z).ctrl().shift()));
+        assert_eq!(KeyBinding::parse("F12"), Some(KeyBinding::new(Key::F12)));
+        assert_eq!(KeyBinding::parse("hyper+W"), None);
+        assert_eq!(KeyBinding::parse("ctrl+"), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bindings = KeyBindings::default();
+        bindings.set(Action::MoveForward, vec![KeyBinding::new(Key::Z)]);
+        bindings.set(Action::Screenshot, Vec::new());
+        bindings.add(Action::Undo, KeyBinding::new(Key::Backspace).alt());
+        assert_eq!(KeyBindings::parse(&bindings.to_text()), Ok(bindings));
+    }
+
+    #[test]
+    fn parse_file() {
+        let text = "# azerty\nmove_forward Z\n\nmove_left Q\nmove_down none\nundo ctrl+Z,alt+Backspace\n";
+        let bindings = KeyBindings::parse(text).unwrap();
+        assert_eq!(bindings.get(Action::MoveForward), [KeyBinding::new(Key::Z)]);
+        assert_eq!(bindings.get(Action::MoveLeft), [KeyBinding::new(Key::Q)]);
+        assert_eq!(bindings.get(Action::MoveDown), []);
+        assert_eq!(bindings.get(Action::MoveRight), [KeyBinding::new(Key::D)]);
+        assert_eq!(bindings.get(Action::Undo), [KeyBinding::new(Key::Z).ctrl(), KeyBinding::new(Key::Backspace).alt()]);
+
+        assert!(KeyBindings::parse("fly_away W").is_err());
+        assert!(KeyBindings::parse("move_forward").is_err());
+        assert!(KeyBindings::parse("move_forward ctrl+Nope").is_err());
+        assert!(KeyBindings::parse("move_forward W,").is_err());
+    }
+
+    #[test]
+    fn redo_shortcuts() {
+        let bindings = KeyBindings::default();
+        let ctrl_shift = Modifiers { shift: true, ..Modifiers::COMMAND };
+        assert!(bindings.is_pressed(&pressed(Key::Z, ctrl_shift), Action::Redo));
+        assert!(bindings.is_pressed(&pressed(Key::Y, Modifiers::COMMAND), Action::Redo));
+        assert!(!bindings.is_pressed(&pressed(Key::Y, Modifiers::COMMAND), Action::Undo));
+        assert!(!bindings.is_pressed(&pressed(Key::Z, Modifiers::COMMAND), Action::Redo));
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.get_conflicts().is_empty());
+        bindings.set(Action::MoveLeft, vec![KeyBinding::new(Key::W)]);
+        assert_eq!(bindings.get_conflicts(), vec![(Action::MoveForward, Action::MoveLeft)]);
+        bindings.set(Action::MoveLeft, vec![KeyBinding::new(Key::A)]);
+        bindings.set(Action::Screenshot, vec![KeyBinding::new(Key::G)]);
+        assert_eq!(bindings.get_conflicts(), vec![(Action::GizmoTranslate, Action::Screenshot)]);
+        bindings.set(Action::Screenshot, vec![KeyBinding::new(Key::F12)]);
+        bindings.add(Action::Undo, KeyBinding::new(Key::Y).ctrl());
+        assert_eq!(bindings.get_conflicts(), vec![(Action::Undo, Action::Redo)]);
+    }
+}
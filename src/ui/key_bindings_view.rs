@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use crate::egui;
+use crate::util::log;
+
+use super::key_bindings::{Action, KeyBinding, KeyBindings};
+use super::shared_state::SharedState;
+use super::ui_traits::UiElement;
+
+/*
+ * @brief
+ * Lists every action with its keys. Clicking a key waits for the next key press to rebind it,
+ * "+" waits for one to add. Changes are saved to the bindings file straight away.
+ */
+pub struct KeyBindingsView {
+    shared_state: Rc<SharedState>,
+    /* The action and which of its bindings is replaced, None to add one */
+    capturing: Option<(Action, Option<usize>)>,
+}
+
+impl KeyBindingsView {
+    pub fn new(shared_state: Rc<SharedState>) -> Self {
+        Self {
+            shared_state,
+            capturing: None,
+        }
+    }
+}
+
+impl UiElement for KeyBindingsView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        let mut key_bindings = self.shared_state.get_mut_key_bindings();
+        // Stays disabled for the frame the key was captured in
+        key_bindings.set_enabled(self.capturing.is_none());
+
+        let mut changed = false;
+        if let Some((action, replaced)) = self.capturing {
+            if let Some(binding) = Self::capture_key(ui) {
+                if binding.get_key() != egui::Key::Escape {
+                    let mut bindings = key_bindings.get(action).to_vec();
+                    // A key the action already has is left where it is
+                    if !bindings.contains(&binding) {
+                        match replaced {
+                            Some(index) => bindings[index] = binding,
+                            None => bindings.push(binding),
+                        }
+                        key_bindings.set(action, bindings);
+                        changed = true;
+                    }
+                }
+                self.capturing = None;
+            }
+        }
+
+        let conflicts = key_bindings.get_conflicts();
+        egui::Grid::new("key_bindings").striped(true).show(ui, |ui| {
+            for action in Action::ALL {
+                let conflicting = conflicts.iter().any(|(first, second)| *first == action || *second == action);
+                let label = egui::RichText::new(action.get_label());
+                let label = if conflicting { label.color(ui.style().visuals.error_fg_color) } else { label };
+                ui.label(label);
+
+                ui.horizontal(|ui| {
+                    let bindings = key_bindings.get(action);
+                    if bindings.is_empty() {
+                        ui.label("none");
+                    }
+                    for (index, binding) in bindings.iter().enumerate() {
+                        let text = match self.capturing == Some((action, Some(index))) {
+                            true => String::from("press a key..."),
+                            false => binding.to_string(),
+                        };
+                        if ui.button(text).clicked() {
+                            self.capturing = Some((action, Some(index)));
+                        }
+                    }
+                    let text = match self.capturing == Some((action, None)) {
+                        true => "press a key...",
+                        false => "+",
+                    };
+                    if ui.small_button(text).on_hover_text("add a key").clicked() {
+                        self.capturing = Some((action, None));
+                    }
+                });
+                if ui.small_button("x").on_hover_text("unbind").clicked() {
+                    key_bindings.set(action, Vec::new());
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+
+        for (first, second) in &conflicts {
+            ui.colored_label(
+                ui.style().visuals.error_fg_color,
+                format!("{} and {} use the same key", first.get_label(), second.get_label()));
+        }
+
+        if ui.button("Reset to defaults").clicked() {
+            *key_bindings = KeyBindings::default();
+            changed = true;
+        }
+
+        if changed {
+            if let Err(err) = key_bindings.save() {
+                log::error("Could not save key bindings", &err);
+            }
+        }
+    }
+}
+
+impl KeyBindingsView {
+    fn capture_key(ui: &egui::Ui) -> Option<KeyBinding> {
+        ui.input().events.iter().find_map(|event| match event {
+            egui::Event::Key { key, pressed: true, modifiers } => Some(KeyBinding::from_modifiers(*key, modifiers)),
+            _ => None,
+        })
+    }
+}
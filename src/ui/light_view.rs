@@ -0,0 +1,63 @@
+use std::rc::Rc;
+
+use eframe::egui::Slider;
+use glam::Vec3;
+
+use crate::egui;
+use crate::{Arc, Mutex};
+use crate::structures::history::Command;
+use crate::structures::light::DirectionalLight;
+use crate::structures::scene::Scene;
+
+use super::common_views::Vec3View;
+use super::shared_state::SharedState;
+use super::ui_traits::{UiElement, UiElementParent};
+
+/*
+ * @brief
+ * Shows the direction, color and intensity of the scene light.
+ */
+pub struct LightView {
+    scene: Arc<Mutex<Scene>>,
+    shared_state: Rc<SharedState>,
+}
+
+impl LightView {
+    pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<SharedState>) -> Self {
+        Self {
+            scene,
+            shared_state,
+        }
+    }
+}
+
+impl UiElement for LightView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        let mut scene = self.scene.lock();
+        let light = scene.get_mut_light();
+        let old = *light;
+        Self::light_render(ui, light);
+        if old != *light {
+            self.shared_state.get_history().push(Command::SetLight { old, new: *light });
+        }
+    }
+}
+
+impl LightView {
+    fn light_render(ui: &mut egui::Ui, light: &mut DirectionalLight) {
+        let mut direction = light.get_direction();
+        ui.show_element(Vec3View::new(&mut direction).label(String::from("direction: ")));
+        if direction != light.get_direction() {
+            light.set_direction(direction);
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("color: ");
+            let mut color = light.get_color().to_array();
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                light.set_color(Vec3::from(color));
+            }
+        });
+        ui.add(Slider::new(light.get_mut_intensity(), 0.0..=4.0).text("intensity"));
+    }
+}
@@ -0,0 +1,89 @@
+use std::rc::Rc;
+
+use eframe::egui::Slider;
+use glam::Vec3;
+
+use crate::egui;
+use crate::resources::material::{Material, MaterialType, SolidColorMaterial};
+
+use crate::structures::history::Command;
+
+use super::shared_state::SharedState;
+use super::ui_traits::UiElement;
+
+/*
+ * @brief
+ * Inspector for the material of the selected object.
+ * Most parameters are uniforms and apply immediately; structural ones regenerate the shaders.
+ */
+pub struct MaterialView {
+    shared_state: Rc<SharedState>,
+}
+
+impl MaterialView {
+    pub fn new(shared_state: Rc<SharedState>) -> Self {
+        Self {
+            shared_state,
+        }
+    }
+}
+
+impl UiElement for MaterialView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        let material = match self.shared_state.get_selected_object() {
+            Some(object) => object.lock().unwrap().get_material(),
+            None => {
+                ui.label("No object selected");
+                return;
+            }
+        };
+        let (old, new) = {
+            let mut material = material.lock().unwrap();
+            let old = material.get_parameters();
+            Self::material_render(ui, &mut material);
+            (old, material.get_parameters())
+        };
+        if old != new {
+            self.shared_state.get_history().push(Command::SetMaterial { material, old, new });
+        }
+    }
+}
+
+impl MaterialView {
+    fn material_render(ui: &mut egui::Ui, material: &mut Material) {
+        ui.label(material.get_name());
+        let structure_changed = match material.get_mut_material_type() {
+            MaterialType::SolidColor(solid_material) => Self::solid_color_render(ui, solid_material),
+        };
+        if structure_changed {
+            material.invalidate_shader();
+        }
+    }
+
+    /* Returns true if the shader needs to be regenerated */
+    fn solid_color_render(ui: &mut egui::Ui, material: &mut SolidColorMaterial) -> bool {
+        ui.horizontal(|ui| {
+            ui.label("color: ");
+            let mut color = material.get_color().to_array();
+            if ui.color_edit_button_rgb(&mut color).changed() {
+                material.set_color(Vec3::from(color));
+            }
+        });
+
+        let mut shaded = material.is_shaded();
+        let structure_changed = ui.checkbox(&mut shaded, "shaded").changed();
+        material.set_shaded(shaded);
+
+        ui.add_enabled_ui(shaded, |ui| {
+            let mut ambient = material.get_ambient();
+            if ui.add(Slider::new(&mut ambient, 0.0..=1.0).text("ambient")).changed() {
+                material.set_ambient(ambient);
+            }
+            let mut diffuse = material.get_diffuse();
+            if ui.add(Slider::new(&mut diffuse, 0.0..=1.0).text("diffuse")).changed() {
+                material.set_diffuse(diffuse);
+            }
+        });
+        structure_changed
+    }
+}
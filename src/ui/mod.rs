@@ -4,3 +4,14 @@ pub mod ui_traits;
 pub mod camera_view;
 pub mod common_views;
 pub mod scene_panel_view;
+pub mod object_view;
+pub mod gizmo_view;
+pub mod material_view;
+pub mod history_view;
+pub mod key_bindings;
+pub mod key_bindings_view;
+pub mod light_view;
+pub mod fractal_view;
+pub mod inspector_view;
+pub mod console_view;
+pub mod shader_editor_view;
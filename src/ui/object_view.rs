@@ -0,0 +1,90 @@
+use std::rc::Rc;
+
+use crate::egui;
+use crate::{Arc, Mutex};
+use crate::resources::mesh_subdivide::{SubdivisionOptions, SubdivisionScheme};
+use crate::structures::history::Command;
+use crate::structures::scene::Scene;
+use crate::util::log;
+
+use super::common_views::{Vec3View, QuatView};
+use super::shared_state::SharedState;
+use super::ui_traits::{UiElement, UiElementParent};
+
+/*
+ * @brief
+ * Shows the transform of the selected object, and mesh operations on it.
+ */
+pub struct ObjectView {
+    scene: Arc<Mutex<Scene>>,
+    shared_state: Rc<SharedState>,
+    subdivision: SubdivisionOptions,
+}
+
+impl ObjectView {
+    pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<SharedState>) -> Self {
+        Self {
+            scene,
+            shared_state,
+            subdivision: SubdivisionOptions::default(),
+        }
+    }
+}
+
+impl UiElement for ObjectView {
+    fn render(&mut self, ui: &mut egui::Ui) {
+        let object = match self.shared_state.get_selected_object() {
+            Some(object) => object,
+            None => {
+                ui.label("No object selected");
+                return;
+            }
+        };
+        let (old, new) = {
+            let mut object = object.lock().unwrap();
+            ui.label(object.get_name());
+            if let Some(error) = object.get_error() {
+                let text = match object.is_render_failed() {
+                    true => "Could not be drawn, skipped until its shaders are rebuilt",
+                    false => "Shaders failed to build, using the error material",
+                };
+                ui.colored_label(ui.style().visuals.error_fg_color, text).on_hover_text(error);
+            }
+            let old = *object.get_transform();
+            // Edited on a copy so an object that isn't changed doesn't count as moved
+            let mut transform = old;
+            ui.show_element(Vec3View::new(transform.get_mut_position()));
+            ui.separator();
+            ui.show_element(QuatView::new(transform.get_mut_rotation()));
+            ui.separator();
+            ui.show_element(Vec3View::new(transform.get_mut_scale()).label(String::from("scale: ")));
+            if transform != old {
+                object.set_transform(transform);
+            }
+            (old, transform)
+        };
+        if old != new {
+            self.shared_state.get_history().push(Command::SetTransform { object: object.clone(), old, new });
+        }
+        ui.separator();
+        ui.collapsing("Subdivide", |ui| {
+            let options = &mut self.subdivision;
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut options.scheme, SubdivisionScheme::Auto, "auto");
+                ui.radio_value(&mut options.scheme, SubdivisionScheme::Loop, "loop");
+                ui.radio_value(&mut options.scheme, SubdivisionScheme::CatmullClark, "catmull-clark");
+            });
+            ui.add(egui::Slider::new(&mut options.levels, 1..=4).text("levels"));
+            ui.horizontal(|ui| {
+                ui.drag_angle(&mut options.crease_angle);
+                ui.label("crease angle");
+            });
+            options.crease_angle = options.crease_angle.clamp(0.0, std::f32::consts::PI);
+            if ui.button("Subdivide").on_hover_text("Adds a subdivided copy and hides the original").clicked() {
+                if let Err(err) = self.scene.lock().subdivide_object(object, *options) {
+                    log::error("Could not subdivide", &err);
+                }
+            }
+        });
+    }
+}
@@ -1,15 +1,42 @@
 use std::{sync::{Arc}, rc::Rc};
 
+use eframe::egui::{self, Rect, Sense, TextEdit};
 use eframe::epaint::mutex::Mutex;
 
+use crate::resources::container::ContainerRef;
+use crate::resources::object::Object;
+use crate::resources::primitives::Primitive;
+use crate::structures::history::Command;
 use crate::structures::scene::Scene;
+use crate::util::error::Error;
+use crate::util::log;
 
-use super::{shared_state::SharedState, ui_traits::UiElement};
-
+use super::{shared_state::{SharedState, Selection}, ui_traits::UiElement};
 
+/*
+ * @brief
+ * Outliner listing the objects in the scene as a tree.
+ * Objects can be selected, hidden, renamed, deleted and dragged onto each other to reparent them.
+ */
 pub struct ScenePanelView {
     scene: Arc<Mutex<Scene>>,
     shared_state: Rc<SharedState>,
+    /* name of the object being renamed and the text typed so far */
+    renaming: Option<(String, String)>,
+    dragging: Option<String>,
+    /* rects of the rows drawn this frame, used to find drop targets */
+    row_rects: Vec<(String, Rect)>,
+    /* detail of the primitives added from the menu */
+    primitive_segments: u32,
+    icosphere_subdivisions: u32,
+}
+
+enum OutlinerAction {
+    Select(ContainerRef<Object>),
+    SetVisible(String, bool),
+    Rename(String, String),
+    Delete(String),
+    Reparent(String, Option<String>),
 }
 
 impl ScenePanelView {
@@ -17,18 +44,249 @@ impl ScenePanelView {
         Self {
             scene: scene.clone(),
             shared_state: shared_state.clone(),
+            renaming: None,
+            dragging: None,
+            row_rects: Vec::new(),
+            primitive_segments: 32,
+            icosphere_subdivisions: 3,
         }
     }
 }
 
 impl UiElement for ScenePanelView {
     fn render(&mut self, ui: &mut eframe::egui::Ui) {
-        let _scene = self.scene.clone();
-        ui.label("Scene");
-        let list = ["one", "two", "threww", "four"];
-        let mut values = [false, false, false, false];
-        for (i, item) in list.iter().enumerate() {
-            ui.toggle_value(&mut values[i], String::from(*item));
+        ui.horizontal(|ui| {
+            ui.label("Scene");
+            ui.menu_button("Add primitive", |ui| self.render_primitive_menu(ui));
+        });
+        self.render_scene_items(ui);
+        ui.separator();
+        let mut actions = Vec::new();
+        self.row_rects.clear();
+        {
+            let scene = self.scene.clone();
+            let scene = scene.lock();
+            self.render_children(ui, &scene, None, &mut actions);
+        }
+
+        let root_rect = match self.dragging {
+            Some(_) => {
+                let response = ui.allocate_response(egui::vec2(ui.available_width(), 24.0), Sense::hover());
+                ui.painter().text(
+                    response.rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    "drop here to unparent",
+                    egui::FontId::default(),
+                    ui.style().visuals.weak_text_color());
+                Some(response.rect)
+            }
+            None => None,
+        };
+        self.handle_drop(ui, root_rect, &mut actions);
+        self.apply_actions(actions);
+    }
+}
+
+impl ScenePanelView {
+    fn render_primitive_menu(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.primitive_segments, 3..=128).text("segments"));
+        ui.add(egui::Slider::new(&mut self.icosphere_subdivisions, 0..=6).text("icosphere subdivisions"));
+        ui.separator();
+        let segments = self.primitive_segments;
+        let rings = (segments / 2).max(2);
+        let primitives = [
+            ("UV sphere", Primitive::UvSphere { segments, rings }),
+            ("Icosphere", Primitive::Icosphere { subdivisions: self.icosphere_subdivisions }),
+            ("Cylinder", Primitive::Cylinder { segments }),
+            ("Cone", Primitive::Cone { segments }),
+            ("Torus", Primitive::Torus { segments, tube_segments: rings, tube_radius: 0.25 }),
+            ("Plane", Primitive::Plane { segments: (segments / 4).max(1) }),
+            ("Capsule", Primitive::Capsule { segments, rings: (rings / 2).max(1), length: 1.0 }),
+        ];
+        for (label, primitive) in primitives {
+            if ui.button(label).clicked() {
+                self.scene.lock().add_primitive(&primitive);
+                ui.close_menu();
+            }
+        }
+    }
+
+    /* Rows for the parts of the scene that aren't objects */
+    fn render_scene_items(&mut self, ui: &mut egui::Ui) {
+        let selection = self.shared_state.get_selection();
+        let items = [
+            (Selection::Camera, "Camera", matches!(selection, Selection::Camera)),
+            (Selection::Light, "Light", matches!(selection, Selection::Light)),
+            (Selection::Fractal, "Fractal", matches!(selection, Selection::Fractal)),
+        ];
+        for (item, name, selected) in items {
+            if ui.selectable_label(selected, name).clicked() {
+                self.shared_state.set_selection(item);
+            }
+        }
+    }
+
+    fn render_children(&mut self, ui: &mut egui::Ui, scene: &Scene, parent_name: Option<&str>, actions: &mut Vec<OutlinerAction>) {
+        for object in scene.get_children(parent_name) {
+            let (name, mut visible) = {
+                let object = object.lock().unwrap();
+                (String::from(object.get_name()), object.is_visible())
+            };
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut visible, "").on_hover_text("visible").changed() {
+                    actions.push(OutlinerAction::SetVisible(name.clone(), visible));
+                }
+                self.render_row(ui, &object, &name, actions);
+            });
+            ui.push_id(&name, |ui| {
+                ui.indent("children", |ui| {
+                    self.render_children(ui, scene, Some(&name), actions);
+                });
+            });
+        }
+    }
+
+    fn render_row(&mut self, ui: &mut egui::Ui, object: &ContainerRef<Object>, name: &str, actions: &mut Vec<OutlinerAction>) {
+        if let Some((renamed, buffer)) = &mut self.renaming {
+            if renamed == name {
+                let response = ui.add(TextEdit::singleline(buffer));
+                if response.lost_focus() {
+                    if ui.input().key_pressed(egui::Key::Enter) && buffer != name && !buffer.is_empty() {
+                        actions.push(OutlinerAction::Rename(String::from(name), buffer.clone()));
+                    }
+                    self.renaming = None;
+                } else if !response.has_focus() {
+                    response.request_focus();
+                }
+                return;
+            }
+        }
+
+        let selected = self.shared_state.is_selected(object);
+        let response = ui.selectable_label(selected, name).interact(Sense::drag());
+        if response.clicked() {
+            actions.push(OutlinerAction::Select(object.clone()));
+        }
+        if response.double_clicked() {
+            self.renaming = Some((String::from(name), String::from(name)));
+        }
+        if response.drag_started() {
+            self.dragging = Some(String::from(name));
+        }
+        if let Some(dragging) = &self.dragging {
+            if dragging != name && ui.rect_contains_pointer(response.rect) {
+                ui.painter().rect_stroke(response.rect, 2.0, ui.style().visuals.selection.stroke);
+            }
+        }
+        self.row_rects.push((String::from(name), response.rect));
+        response.context_menu(|ui| {
+            if ui.button("Rename").clicked() {
+                self.renaming = Some((String::from(name), String::from(name)));
+                ui.close_menu();
+            }
+            if ui.button("Unparent").clicked() {
+                actions.push(OutlinerAction::Reparent(String::from(name), None));
+                ui.close_menu();
+            }
+            if ui.button("Delete").clicked() {
+                actions.push(OutlinerAction::Delete(String::from(name)));
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn handle_drop(&mut self, ui: &mut egui::Ui, root_rect: Option<Rect>, actions: &mut Vec<OutlinerAction>) {
+        if self.dragging.is_none() || !ui.input().pointer.any_released() {
+            return;
+        }
+        let dragging = self.dragging.take().unwrap();
+        let pointer = match ui.input().pointer.interact_pos() {
+            Some(pointer) => pointer,
+            None => return,
+        };
+        if let Some(root_rect) = root_rect {
+            if root_rect.contains(pointer) {
+                actions.push(OutlinerAction::Reparent(dragging, None));
+                return;
+            }
         }
+        for (name, rect) in &self.row_rects {
+            if rect.contains(pointer) && name != &dragging {
+                actions.push(OutlinerAction::Reparent(dragging, Some(name.clone())));
+                return;
+            }
+        }
+    }
+
+    fn apply_actions(&mut self, actions: Vec<OutlinerAction>) {
+        let mut scene = self.scene.lock();
+        for action in actions {
+            let result = match action {
+                OutlinerAction::Select(object) => {
+                    self.shared_state.set_selected_object(Some(object));
+                    Ok(None)
+                }
+                OutlinerAction::SetVisible(name, visible) => Self::set_visible(&scene, &name, visible),
+                OutlinerAction::Rename(old_name, new_name) => scene.rename_object(&old_name, &new_name)
+                    .map(|_| Some(Command::Rename { old_name, new_name })),
+                OutlinerAction::Delete(name) => {
+                    if let Some(object) = scene.get_object(&name) {
+                        if self.shared_state.is_selected(&object) {
+                            self.shared_state.set_selected_object(None);
+                        }
+                    }
+                    Self::delete(&mut scene, &name)
+                }
+                OutlinerAction::Reparent(name, parent_name) => Self::reparent(&mut scene, &name, parent_name.as_deref()),
+            };
+            match result {
+                Ok(Some(command)) => {
+                    let mut history = self.shared_state.get_history();
+                    history.seal();
+                    history.push(command);
+                }
+                Ok(None) => (),
+                Err(err) => log::error("Could not edit the scene", &err),
+            }
+        }
+    }
+
+    /* The functions below make the edit and return the command recording it */
+
+    fn set_visible(scene: &Scene, name: &str, visible: bool) -> Result<Option<Command>, Error> {
+        let object = match scene.get_object(name) {
+            Some(object) => object,
+            None => return Err(Error::object_does_not_exist(name)),
+        };
+        let old = object.lock().unwrap().is_visible();
+        object.lock().unwrap().set_visible(visible);
+        Ok(Some(Command::SetVisible { object, old, new: visible }))
+    }
+
+    fn delete(scene: &mut Scene, name: &str) -> Result<Option<Command>, Error> {
+        let children = scene.get_children(Some(name))
+            .into_iter()
+            .map(|child| {
+                let transform = *child.lock().unwrap().get_transform();
+                (child, transform)
+            })
+            .collect();
+        let (object, index) = scene.remove_object(name)?;
+        Ok(Some(Command::Delete { object, index, children }))
+    }
+
+    fn reparent(scene: &mut Scene, name: &str, parent_name: Option<&str>) -> Result<Option<Command>, Error> {
+        let object = match scene.get_object(name) {
+            Some(object) => object,
+            None => return Err(Error::object_does_not_exist(name)),
+        };
+        let (old_parent, old_transform) = {
+            let object = object.lock().unwrap();
+            (object.get_parent().and_then(|parent| scene.get_object(parent)), *object.get_transform())
+        };
+        scene.set_parent(name, parent_name)?;
+        let new_parent = parent_name.and_then(|parent| scene.get_object(parent));
+        let new_transform = *object.lock().unwrap().get_transform();
+        Ok(Some(Command::Reparent { object, old_parent, new_parent, old_transform, new_transform }))
     }
 }
@@ -1,40 +1,78 @@
 use eframe::egui;
-use egui::Key;
 use egui::mutex::Mutex;
 use std::{sync::Arc, rc::Rc};
-use crate::structures::scene::Scene;
+use crate::structures::scene::{BoundsOverlay, Scene};
 
-use super::{ui_traits::*, camera_view::CameraView, scene_panel_view::ScenePanelView};
+use super::{ui_traits::*, scene_panel_view::ScenePanelView, inspector_view::InspectorView};
+use super::{gizmo_view::GizmoView, history_view::HistoryView};
+use super::{key_bindings::{Action, KeyBindings}, key_bindings_view::KeyBindingsView};
+use crate::structures::history::Command;
+use crate::resources::resource_file::screenshot_dir;
+use crate::util::error::Error;
+use crate::util::log::{self, Subsystem};
+use glow::HasContext;
 
 pub struct SceneView {
+    showing_left_panel: bool,
     showing_right_panel: bool,
     scene: Arc<Mutex<Scene>>,
-    _shared_state: Rc<crate::ui::shared_state::SharedState>,
-    camera_view: CameraView,
+    shared_state: Rc<crate::ui::shared_state::SharedState>,
+    inspector_view: InspectorView,
     scene_panel_view: ScenePanelView,
+    gizmo_view: GizmoView,
+    history_view: HistoryView,
+    key_bindings_view: KeyBindingsView,
+    screenshot_requested: bool,
 }
 
 impl SceneView {
     pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<crate::ui::shared_state::SharedState>) -> Self {
         Self {
+            showing_left_panel: false,
             showing_right_panel: false,
             scene: scene.clone(),
-            _shared_state: shared_state.clone(),
-            camera_view: CameraView::new(scene.clone()),
-            scene_panel_view: ScenePanelView::new(scene.clone(), shared_state),
+            shared_state: shared_state.clone(),
+            inspector_view: InspectorView::new(scene.clone(), shared_state.clone()),
+            scene_panel_view: ScenePanelView::new(scene.clone(), shared_state.clone()),
+            history_view: HistoryView::new(scene.clone(), shared_state.clone()),
+            key_bindings_view: KeyBindingsView::new(shared_state),
+            screenshot_requested: false,
+            gizmo_view: GizmoView::new(),
         }
     }
 }
 
 impl UiElement for SceneView {
     fn render(&mut self, ui: &mut egui::Ui) {
+        self.shared_state.get_mut_key_bindings().set_enabled(true);
 
+        if self.showing_left_panel {
+            egui::SidePanel::left("component_left_panel").show_inside(ui, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    self.inspector_view.render(ui);
+                });
+            });
+        }
         if self.showing_right_panel {
             egui::SidePanel::right("component_right_panel").show_inside(ui, |ui| {
-                //self.camera_view.render(ui);
                 self.scene_panel_view.render(ui);
+                ui.separator();
+                ui.collapsing("Gizmo", |ui| {
+                    self.gizmo_view.render_settings(ui);
+                });
+                ui.collapsing("Overlays", |ui| {
+                    Self::overlay_settings(ui, &mut self.scene.lock());
+                });
+                ui.collapsing("History", |ui| {
+                    self.history_view.render(ui);
+                });
+                ui.collapsing("Key bindings", |ui| {
+                    self.key_bindings_view.render(ui);
+                });
             });
         }
+        // After the panels so a key being captured for rebinding doesn't trigger anything
+        self.shortcuts(ui);
         self.scene_viewport(ui);
     }
 }
@@ -46,56 +84,245 @@ impl UiScene for SceneView {
     fn right_panel_state(&mut self) -> Option<&mut bool> {
         Some(&mut self.showing_right_panel)
     }
+
+    fn has_left_panel(&self) -> bool {
+        true
+    }
+    fn left_panel_state(&mut self) -> Option<&mut bool> {
+        Some(&mut self.showing_left_panel)
+    }
 }
 
 impl SceneView {
     fn scene_viewport(&mut self, ui: &mut egui::Ui) {
         let (rect, response) =
-            ui.allocate_exact_size(ui.available_size(), egui::Sense::drag());
+            ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
 
         let scene = self.scene.clone();
-        let drag_delta = response.drag_delta();
-        let drag_delta = glam::Vec2::new(drag_delta.x * 0.004, drag_delta.y * 0.004);
-
-        let move_delta = Self::get_move_delta(ui, 0.01);
+        // The scene has to be drawn before the gizmo but the selection is only known after it
+        let callback_index = ui.painter().add(egui::Shape::Noop);
 
         let resolution = glam::Vec2::new(rect.width(), rect.height());
         scene.lock().get_camera_mut().set_resolution(resolution);
 
-        scene.lock().get_camera_mut().pan_camera(drag_delta);
-        scene.lock().get_camera_mut().change_relative_position(move_delta);
+        let gizmo_used = match self.shared_state.get_selected_object() {
+            Some(object) => {
+                let old = *object.lock().unwrap().get_transform();
+                let key_bindings = self.shared_state.get_key_bindings();
+                let gizmo_used = self.gizmo_view.update(ui, rect, &response, &mut scene.lock(), &object, &key_bindings);
+                drop(key_bindings);
+                let new = *object.lock().unwrap().get_transform();
+                if old != new {
+                    self.shared_state.get_history().push(Command::SetTransform { object, old, new });
+                }
+                gizmo_used
+            }
+            None => false,
+        };
+
+        if !gizmo_used {
+            let drag_delta = response.drag_delta();
+            let drag_delta = glam::DVec2::new(drag_delta.x as f64 * 0.004, drag_delta.y as f64 * 0.004);
+            scene.lock().get_camera_mut().pan_camera(drag_delta);
+        }
+
+        // Keys only fly the camera while the right mouse button is held so they don't clash with the gizmo shortcuts
+        if ui.input().pointer.secondary_down() {
+            let speed = {
+                let scene = scene.lock();
+                scene.get_camera().get_speed(scene.get_camera_distance())
+            };
+            let move_delta = Self::get_move_delta(ui, speed, &self.shared_state.get_key_bindings());
+            scene.lock().get_camera_mut().change_relative_position(move_delta);
+        }
+
+        let frame = response.hovered() && !ui.ctx().wants_keyboard_input() && {
+            let input = ui.input();
+            !input.pointer.secondary_down() && self.shared_state.get_key_bindings().is_pressed(&input, Action::FrameSelected)
+        };
+        if frame {
+            self.frame_selected();
+        }
+
+        if response.clicked() && !gizmo_used {
+            if let Some(position) = response.interact_pointer_pos() {
+                let position = position - rect.min;
+                let mut scene = scene.lock();
+                let ray = scene.get_camera().get_ray(glam::Vec2::new(position.x, position.y));
+                let picked = scene.pick(&ray).map(|(object, _)| object);
+                self.shared_state.set_selected_object(picked);
+            }
+        }
+
+        for command in scene.lock().take_commands() {
+            let mut history = self.shared_state.get_history();
+            history.seal();
+            history.push(command);
+        }
+        for object in self.shared_state.get_history().take_discarded_objects() {
+            scene.lock().destroy_object(object);
+        }
+
+        let selected = self.shared_state.get_selected_object();
+        let take_screenshot = std::mem::take(&mut self.screenshot_requested);
         let callback = egui::PaintCallback {
             rect,
-            callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |_info, painter| {
-                scene.lock().render(painter.gl());
+            callback: std::sync::Arc::new(egui_glow::CallbackFn::new(move |info, painter| {
+                scene.lock().render(painter.gl(), selected.as_ref());
+                if take_screenshot {
+                    if let Err(err) = Self::save_screenshot(painter.gl(), &info) {
+                        log::error("Could not save screenshot", &err);
+                    }
+                }
             })),
         };
-        ui.painter().add(callback);
+        ui.painter().set(callback_index, callback);
+        self.loading_overlay(ui, rect);
     }
 
-    fn get_move_delta(ui: &mut egui::Ui, speed: f32) -> glam::Vec3 {
-        let input = ui.input_mut();
-        let mut move_delta = glam::Vec3::new(0.0, 0.0, 0.0);
-        if input.key_down(Key::W) {
-            move_delta.z += speed;
+    fn overlay_settings(ui: &mut egui::Ui, scene: &mut Scene) {
+        ui.label("bounding boxes: ");
+        ui.horizontal(|ui| {
+            let overlay = scene.get_mut_bounds_overlay();
+            ui.radio_value(overlay, BoundsOverlay::Off, "off");
+            ui.radio_value(overlay, BoundsOverlay::Selected, "selected");
+            ui.radio_value(overlay, BoundsOverlay::All, "all");
+        });
+    }
+
+    /* Frames the selected object, or the whole scene when nothing is selected */
+    fn frame_selected(&mut self) {
+        let mut scene = self.scene.lock();
+        let camera = scene.get_camera();
+        let old = (camera.get_position(), camera.get_rotation_quat());
+        match self.shared_state.get_selected_object() {
+            Some(object) => scene.frame_object(&object),
+            None => scene.frame_all(),
         }
-        if input.key_down(Key::S) {
-            move_delta.z -= speed;
+        let camera = scene.get_camera();
+        let new = (camera.get_position(), camera.get_rotation_quat());
+        if old != new {
+            self.shared_state.get_history().push(Command::SetCamera { old, new });
         }
+    }
 
-        if input.key_down(Key::D) {
-            move_delta.x -= speed;
+    /* Progress of the meshes loading in the background, in the top left corner of the viewport */
+    fn loading_overlay(&mut self, ui: &mut egui::Ui, rect: egui::Rect) {
+        let loads = self.scene.lock().get_loads();
+        if loads.is_empty() {
+            return;
         }
-        if input.key_down(Key::A) {
-            move_delta.x += speed;
+        let mut cancelled = Vec::new();
+        egui::Area::new("mesh_loading")
+            .fixed_pos(rect.left_top() + egui::vec2(8.0, 8.0))
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for (load_id, file_name, progress) in &loads {
+                        ui.horizontal(|ui| {
+                            ui.label(file_name);
+                            ui.add(egui::ProgressBar::new(*progress).desired_width(120.0).show_percentage());
+                            if ui.small_button("Cancel").clicked() {
+                                cancelled.push(*load_id);
+                            }
+                        });
+                    }
+                });
+            });
+        let mut scene = self.scene.lock();
+        for load_id in cancelled {
+            scene.cancel_load(load_id);
         }
+    }
 
-        if input.key_down(Key::E) {
-            move_delta.y -= speed;
+    /*
+     * Undo, redo and screenshot. Edits made while a pointer button is held
+     * or a text field is focused are merged into one undo step.
+     */
+    fn shortcuts(&mut self, ui: &mut egui::Ui) {
+        if ui.ctx().wants_keyboard_input() {
+            return;
+        }
+        let (undo, redo, screenshot, pointer_down) = {
+            let input = ui.input();
+            let key_bindings = self.shared_state.get_key_bindings();
+            (
+                key_bindings.is_pressed(&input, Action::Undo),
+                key_bindings.is_pressed(&input, Action::Redo),
+                key_bindings.is_pressed(&input, Action::Screenshot),
+                input.pointer.any_down(),
+            )
+        };
+        if !pointer_down {
+            self.shared_state.get_history().seal();
+        }
+        if undo {
+            self.history_view.undo(1);
+        }
+        if redo {
+            self.history_view.redo(1);
         }
-        if input.key_down(Key::Q) {
+        if screenshot {
+            self.screenshot_requested = true;
+        }
+    }
+
+    /* Saves what was drawn in viewport to a png in the screenshot directory */
+    fn save_screenshot(gl: &glow::Context, info: &egui::PaintCallbackInfo) -> Result<(), Error> {
+        let viewport = info.viewport_in_pixels();
+        let width = viewport.width_px.round() as u32;
+        let height = viewport.height_px.round() as u32;
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            gl.read_pixels(
+                viewport.left_px.round() as i32,
+                viewport.from_bottom_px.round() as i32,
+                width as i32,
+                height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels));
+        }
+        let image = match image::RgbaImage::from_raw(width, height, pixels) {
+            Some(image) => image::imageops::flip_vertical(&image),
+            None => return Err(Error::unknown(String::from("screenshot buffer has the wrong size"))),
+        };
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        let mut dir = screenshot_dir()?;
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            return Err(Error::unknown_io(err));
+        }
+        dir.push(format!("screenshot_{}.png", time));
+        image.save(&dir)?;
+        log::info(Subsystem::Ui, format!("Saved screenshot to {}", dir.display()));
+        Ok(())
+    }
+
+    fn get_move_delta(ui: &mut egui::Ui, speed: f64, key_bindings: &KeyBindings) -> glam::DVec3 {
+        let input = ui.input();
+        let mut move_delta = glam::DVec3::new(0.0, 0.0, 0.0);
+        if key_bindings.is_down(&input, Action::MoveForward) {
+            move_delta.z -= speed;
+        }
+        if key_bindings.is_down(&input, Action::MoveBackward) {
+            move_delta.z += speed;
+        }
+
+        if key_bindings.is_down(&input, Action::MoveRight) {
+            move_delta.x += speed;
+        }
+        if key_bindings.is_down(&input, Action::MoveLeft) {
+            move_delta.x -= speed;
+        }
+
+        if key_bindings.is_down(&input, Action::MoveUp) {
             move_delta.y += speed;
         }
+        if key_bindings.is_down(&input, Action::MoveDown) {
+            move_delta.y -= speed;
+        }
         
         move_delta
     }
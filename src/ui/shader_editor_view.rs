@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use eframe::egui::text::LayoutJob;
+use eframe::egui::{Color32, ComboBox, RichText, ScrollArea, TextEdit, TextFormat, TextStyle};
+
+use crate::egui;
+use crate::resources::container::ContainerRef;
+use crate::resources::object::Object;
+use crate::resources::resource_file::{load_shader, save_shader};
+use crate::resources::shader_log::{map_generated_lines, ShaderMessage, ShaderStage};
+use crate::structures::scene::Scene;
+use crate::util::log::{self, Subsystem};
+use crate::{Arc, Mutex};
+
+use super::shared_state::SharedState;
+
+const TEMPLATES: [(&str, ShaderStage); 2] = [
+    ("template_vertex.glsl", ShaderStage::Vertex),
+    ("template_fragment.glsl", ShaderStage::Fragment),
+];
+
+#[derive(Clone)]
+enum EditorTarget {
+    Object(ContainerRef<Object>),
+    Template(&'static str, ShaderStage),
+}
+
+impl EditorTarget {
+    fn get_label(&self) -> String {
+        match self {
+            Self::Object(object) => String::from(object.lock().unwrap().get_name()),
+            Self::Template(name, _) => String::from(*name),
+        }
+    }
+
+    fn is(&self, other: &EditorTarget) -> bool {
+        match (self, other) {
+            (Self::Object(object), Self::Object(other)) => ContainerRef::ptr_eq(object, other),
+            (Self::Template(name, _), Self::Template(other, _)) => name == other,
+            _ => false,
+        }
+    }
+}
+
+/*
+ * @brief
+ * Floating window to edit the shaders of an object or the templates they are generated from.
+ * Compiling an object replaces its generated sources until it is regenerated, saving a template
+ * writes it to the asset directory and rebuilds every object using it.
+ * Lines the compiler complained about are highlighted.
+ */
+pub struct ShaderEditorView {
+    scene: Arc<Mutex<Scene>>,
+    shared_state: Rc<SharedState>,
+    open: bool,
+    target: Option<EditorTarget>,
+    stage: ShaderStage,
+    vertex_text: String,
+    fragment_text: String,
+    template_text: String,
+    /* The template as it was last saved or loaded, the generated sources come from it */
+    saved_template: String,
+    /* While the text wasn't edited it follows the sources of the object */
+    modified: bool,
+}
+
+impl ShaderEditorView {
+    pub fn new(scene: Arc<Mutex<Scene>>, shared_state: Rc<SharedState>) -> Self {
+        Self {
+            scene,
+            shared_state,
+            open: false,
+            target: None,
+            stage: ShaderStage::Fragment,
+            vertex_text: String::new(),
+            fragment_text: String::new(),
+            template_text: String::new(),
+            saved_template: String::new(),
+            modified: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.open {
+            return;
+        }
+        let mut open = true;
+        egui::Window::new("Shader editor")
+            .open(&mut open)
+            .default_size(egui::vec2(600.0, 500.0))
+            .show(ctx, |ui| self.window_render(ui));
+        self.open = open;
+    }
+
+    fn set_target(&mut self, target: EditorTarget) {
+        if let EditorTarget::Template(name, stage) = &target {
+            self.stage = *stage;
+            self.saved_template = match load_shader(name) {
+                Ok(source) => source,
+                Err(err) => {
+                    log::error(&format!("Could not load {}", name), &err);
+                    String::new()
+                }
+            };
+            self.template_text = self.saved_template.clone();
+        }
+        self.target = Some(target);
+        self.modified = false;
+        self.sync_object_sources();
+    }
+
+    fn sync_object_sources(&mut self) {
+        if let Some(EditorTarget::Object(object)) = &self.target {
+            let object = object.lock().unwrap();
+            self.vertex_text = String::from(object.get_vertex_source());
+            self.fragment_text = String::from(object.get_fragment_source());
+        }
+    }
+
+    fn target_render(&mut self, ui: &mut egui::Ui) {
+        let mut targets: Vec<EditorTarget> = self.scene.lock().get_objects().iter().cloned().map(EditorTarget::Object).collect();
+        targets.extend(TEMPLATES.iter().map(|(name, stage)| EditorTarget::Template(name, *stage)));
+
+        let selected_text = match &self.target {
+            Some(target) => target.get_label(),
+            None => String::from("Nothing"),
+        };
+        let mut new_target = None;
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("shader_editor_target")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for target in targets {
+                        let current = self.target.as_ref().is_some_and(|current| current.is(&target));
+                        if ui.selectable_label(current, target.get_label()).clicked() {
+                            new_target = Some(target);
+                        }
+                    }
+                });
+            if let Some(object) = self.shared_state.get_selected_object() {
+                if ui.button("Use selection").clicked() {
+                    new_target = Some(EditorTarget::Object(object));
+                }
+            }
+        });
+        if let Some(target) = new_target {
+            self.set_target(target);
+        }
+    }
+
+    fn window_render(&mut self, ui: &mut egui::Ui) {
+        self.target_render(ui);
+        let target = match &self.target {
+            Some(target) => target.clone(),
+            None => {
+                ui.label("Pick an object or a template to edit");
+                return;
+            }
+        };
+        if !self.modified {
+            self.sync_object_sources();
+        }
+
+        match &target {
+            EditorTarget::Object(object) => self.object_toolbar_render(ui, object),
+            EditorTarget::Template(name, _) => self.template_toolbar_render(ui, name),
+        }
+        ui.separator();
+
+        let messages = self.get_messages(&target);
+        let error_lines: HashSet<usize> = messages.iter().filter_map(|(_, line, _)| *line).collect();
+        let error_background = ui.style().visuals.error_fg_color.linear_multiply(0.25);
+        let text_color = ui.style().visuals.text_color();
+        let font_id = TextStyle::Monospace.resolve(ui.style());
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            let mut job = LayoutJob::default();
+            for (i, line) in text.split_inclusive('\n').enumerate() {
+                let background = match error_lines.contains(&(i + 1)) {
+                    true => error_background,
+                    false => Color32::TRANSPARENT,
+                };
+                job.append(line, 0.0, TextFormat { font_id: font_id.clone(), color: text_color, background, ..Default::default() });
+            }
+            job.wrap.max_width = wrap_width;
+            ui.fonts().layout_job(job)
+        };
+
+        let text = match (&target, self.stage) {
+            (EditorTarget::Template(..), _) => &mut self.template_text,
+            (EditorTarget::Object(_), ShaderStage::Vertex) => &mut self.vertex_text,
+            (EditorTarget::Object(_), ShaderStage::Fragment) => &mut self.fragment_text,
+        };
+        ScrollArea::vertical().max_height(ui.available_height() - 80.0).show(ui, |ui| {
+            let response = ui.add(TextEdit::multiline(text)
+                .code_editor()
+                .desired_rows(24)
+                .desired_width(f32::INFINITY)
+                .layouter(&mut layouter));
+            self.modified |= response.changed();
+        });
+
+        ui.separator();
+        ScrollArea::vertical().id_source("shader_editor_messages").max_height(80.0).show(ui, |ui| {
+            if messages.is_empty() {
+                ui.label("No errors");
+            }
+            for (source, line, message) in &messages {
+                let text = match line {
+                    Some(line) => format!("{}line {}: {}", source, line, message.message),
+                    None => format!("{}{}", source, message.message),
+                };
+                ui.label(RichText::new(text).monospace().color(ui.style().visuals.error_fg_color));
+            }
+        });
+    }
+
+    fn object_toolbar_render(&mut self, ui: &mut egui::Ui, object: &ContainerRef<Object>) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.stage, ShaderStage::Vertex, "Vertex");
+            ui.selectable_value(&mut self.stage, ShaderStage::Fragment, "Fragment");
+            ui.separator();
+            if ui.button("Compile").clicked() {
+                let sources = (self.vertex_text.clone(), self.fragment_text.clone());
+                object.lock().unwrap().set_custom_sources(Some(sources));
+                self.modified = false;
+            }
+            if ui.add_enabled(self.modified, egui::Button::new("Revert")).clicked() {
+                self.modified = false;
+            }
+            let custom = object.lock().unwrap().has_custom_sources();
+            if ui.add_enabled(custom, egui::Button::new("Regenerate"))
+                .on_hover_text("Drop the edits and generate the sources from the material again")
+                .clicked() {
+                object.lock().unwrap().set_custom_sources(None);
+                self.modified = false;
+            }
+            if custom {
+                ui.label(RichText::new("custom sources").color(ui.style().visuals.warn_fg_color));
+            }
+        });
+    }
+
+    fn template_toolbar_render(&mut self, ui: &mut egui::Ui, name: &str) {
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                match save_shader(name, &self.template_text) {
+                    Ok(()) => {
+                        log::info(Subsystem::Shader, format!("Saved {}", name));
+                        self.saved_template = self.template_text.clone();
+                        self.modified = false;
+                        for object in self.scene.lock().get_objects() {
+                            object.lock().unwrap().request_reload();
+                        }
+                    }
+                    Err(err) => log::error(&format!("Could not save {}", name), &err),
+                }
+            }
+            if ui.add_enabled(self.modified, egui::Button::new("Revert")).clicked() {
+                self.template_text = self.saved_template.clone();
+                self.modified = false;
+            }
+        });
+    }
+
+    /*
+     * The messages to show as (where they come from, line in the edited text, message).
+     * Errors of objects built from a template are mapped back to the lines of the template.
+     */
+    fn get_messages(&self, target: &EditorTarget) -> Vec<(String, Option<usize>, ShaderMessage)> {
+        match target {
+            EditorTarget::Object(object) => object
+                .lock()
+                .unwrap()
+                .get_shader_messages()
+                .iter()
+                .filter(|message| message.stage == self.stage)
+                .map(|message| (String::new(), message.line, message.clone()))
+                .collect(),
+            EditorTarget::Template(_, stage) => {
+                let mut result = Vec::new();
+                for object in self.scene.lock().get_objects() {
+                    let object = object.lock().unwrap();
+                    if object.has_custom_sources() {
+                        continue;
+                    }
+                    let source = match stage {
+                        ShaderStage::Vertex => object.get_vertex_source(),
+                        ShaderStage::Fragment => object.get_fragment_source(),
+                    };
+                    let line_map = map_generated_lines(&self.saved_template, source);
+                    for message in object.get_shader_messages().iter().filter(|message| message.stage == *stage) {
+                        let line = message.line.and_then(|line| line.checked_sub(1)).and_then(|i| line_map.get(i).copied());
+                        result.push((format!("{}: ", object.get_name()), line, message.clone()));
+                    }
+                }
+                result
+            }
+        }
+    }
+}
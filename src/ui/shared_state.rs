@@ -1,16 +1,36 @@
+use std::cell::{Ref, RefCell, RefMut};
+
 use crate::resources::container::{Container, ContainerRef};
 use crate::resources::object::Object;
+use crate::structures::history::History;
+use crate::util::log;
+
+use super::key_bindings::KeyBindings;
+
+/* What the inspector shows. Only objects can be picked in the viewport. */
+#[derive(Clone)]
+pub enum Selection {
+    Nothing,
+    Camera,
+    Object(ContainerRef<Object>),
+    Light,
+    Fractal,
+}
 
 pub struct SharedState {
     container: Container,
-    selected_object: Option<ContainerRef<Object>>,
+    selection: RefCell<Selection>,
+    history: RefCell<History>,
+    key_bindings: RefCell<KeyBindings>,
 }
 
 impl SharedState {
     pub fn new(gl: &eframe::egui::Context) -> Self {
         Self {
-            container: Container::system_default(gl).unwrap(),
-            selected_object: None,
+            container: Container::system_default(gl),
+            selection: RefCell::new(Selection::Nothing),
+            history: RefCell::new(History::new()),
+            key_bindings: RefCell::new(Self::load_key_bindings()),
         }
     }
 
@@ -18,17 +38,51 @@ impl SharedState {
         &self.container
     }
 
+    pub fn get_selection(&self) -> Selection {
+        self.selection.borrow().clone()
+    }
+    pub fn set_selection(&self, selection: Selection) {
+        *self.selection.borrow_mut() = selection;
+    }
+
     pub fn get_selected_object(&self) -> Option<ContainerRef<Object>> {
-        if let Some(object) = self.selected_object.clone() {
-            return Some(object);
+        match &*self.selection.borrow() {
+            Selection::Object(object) => Some(object.clone()),
+            _ => None,
+        }
+    }
+    pub fn set_selected_object(&self, object: Option<ContainerRef<Object>>) {
+        let selection = match object {
+            Some(object) => Selection::Object(object),
+            None => Selection::Nothing,
+        };
+        self.set_selection(selection);
+    }
+    pub fn is_selected(&self, object: &ContainerRef<Object>) -> bool {
+        match &*self.selection.borrow() {
+            Selection::Object(selected) => ContainerRef::ptr_eq(selected, object),
+            _ => false,
         }
-        return None;
     }
-    pub fn set_selected_object(&mut self, name: Option<&str>) {
-        if let Some(name) = name {
-            self.selected_object = self.container.get_object(name);
-        } else {
-            self.selected_object = None;
+
+    pub fn get_history(&self) -> RefMut<'_, History> {
+        self.history.borrow_mut()
+    }
+
+    pub fn get_key_bindings(&self) -> Ref<'_, KeyBindings> {
+        self.key_bindings.borrow()
+    }
+    pub fn get_mut_key_bindings(&self) -> RefMut<'_, KeyBindings> {
+        self.key_bindings.borrow_mut()
+    }
+
+    fn load_key_bindings() -> KeyBindings {
+        match KeyBindings::load() {
+            Ok(key_bindings) => key_bindings,
+            Err(err) => {
+                log::error("Could not load key bindings, using the defaults", &err);
+                KeyBindings::default()
+            }
         }
     }
 }
@@ -2,6 +2,9 @@
 
 use std::fmt::{Display, write};
 
+use crate::resources::shader_log::{parse_info_log, ShaderMessage, ShaderStage};
+use crate::util::log::Subsystem;
+
 pub type OptionsType = u8;
 
 //Acts as a bitset
@@ -106,9 +109,22 @@ pub enum ErrorKind {
     LoadingFile(std::io::Error, String),
     LoadingImage(image::ImageError),
     InvalidShaderSource(String),
+    ShaderCompile(ShaderStage, String),
     InvalidBuffer(String),
     InvalidGLProgram(String),
     InvalidHomeDir,
+    InvalidParent(String, String),
+    MissingAttributeIndex(String),
+    /* The asset and every place it was looked for */
+    AssetNotFound(String, Vec<String>),
+    LoadCancelled,
+    /* The file and what the loading thread panicked with */
+    LoadPanicked(String, String),
+    InvalidMeshCache(String),
+    /* The mesh and what is wrong with it */
+    InvalidMesh(String, String),
+    /* How many triangles subdividing would make and how many are allowed */
+    TooManyTriangles(usize, usize),
     /* Ref dict errors */
     ValueAlreadyExists,
     ValueDoesNotExist,
@@ -120,6 +136,8 @@ pub enum ErrorKind {
     ParserEndOfFile(),
     ParserInvalidAsciiInt(ParserInvalidAsciiIntContent),
     PlyParser(String),
+    KeyBindingParser(String, usize),
+    ObjParser(String, usize),
 }
 impl std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -139,9 +157,18 @@ impl std::fmt::Display for ErrorKind {
             Self::LoadingFile(error, file_name) => write!(f, "Could not find file {} ({})", file_name, error),
             Self::LoadingImage(error) => write!(f, "Problem loading image {}", error),
             Self::InvalidShaderSource(error) => write!(f, "Problem compiling shader ({})", error),
+            Self::ShaderCompile(stage, info_log) => write!(f, "Problem compiling {} shader ({})", stage, info_log.trim()),
             Self::InvalidBuffer(error) => write!(f, "Problem creating buffer ({})", error),
             Self::InvalidGLProgram(error) => write!(f, "Problem loading OpenGL program ({})", error),
             Self::InvalidHomeDir => write!(f, "Could not find home dir"),
+            Self::InvalidParent(object_name, parent_name) => write!(f, "Object \"{}\" cannot be a child of \"{}\"", object_name, parent_name),
+            Self::MissingAttributeIndex(attribute_name) => write!(f, "Vertex attribute \"{}\" has no location", attribute_name),
+            Self::LoadCancelled => write!(f, "Loading was cancelled"),
+            Self::LoadPanicked(file_name, message) => write!(f, "Loading {} crashed ({})", file_name, message),
+            Self::InvalidMeshCache(message) => write!(f, "Invalid mesh cache entry ({})", message),
+            Self::InvalidMesh(mesh_name, problems) => write!(f, "Invalid mesh {} ({})", mesh_name, problems),
+            Self::TooManyTriangles(count, budget) => write!(f, "Subdividing would make {} triangles, more than the {} allowed", count, budget),
+            Self::AssetNotFound(asset_name, searched) => write!(f, "Could not find asset {} (looked in {})", asset_name, searched.join(", ")),
 
             Self::ValueAlreadyExists => write!(f, "Value already exists"),
             Self::ValueDoesNotExist => write!(f, "Value does not exist"),
@@ -153,6 +180,8 @@ impl std::fmt::Display for ErrorKind {
             Self::ParserEndOfFile() => write!(f, "Parser error: Reached end of line."),
             Self::ParserInvalidAsciiInt(word) => write!(f, "Parser error: {} is not an int", word),
             Self::PlyParser(message) => write!(f, "Problem loading ply parser: {}", message),
+            Self::KeyBindingParser(message, line_number) => write!(f, "Problem loading key bindings: {} at line {}", message, line_number),
+            Self::ObjParser(message, line_number) => write!(f, "Problem loading obj: {} at line {}", message, line_number),
         }
     }
 }
@@ -225,15 +254,69 @@ impl Error {
     pub fn invalid_shader_source(shader_error: String) -> Self {
         Self { kind: ErrorKind::InvalidShaderSource(shader_error), options: options::RESOURCE_ERROR }
     }
+    /* info_log is what the gl compiler had to say, see get_shader_messages */
+    pub fn shader_compile(stage: ShaderStage, info_log: String) -> Self {
+        Self { kind: ErrorKind::ShaderCompile(stage, info_log), options: options::RESOURCE_ERROR }
+    }
+    /* The compiler messages if this is a compile error, empty otherwise */
+    pub fn get_shader_messages(&self) -> Vec<ShaderMessage> {
+        match &self.kind {
+            ErrorKind::ShaderCompile(stage, info_log) => parse_info_log(*stage, info_log),
+            _ => Vec::new(),
+        }
+    }
     pub fn invalid_buffer(buffer_error: String) -> Self {
         Self { kind: ErrorKind::InvalidBuffer(buffer_error), options: options::RESOURCE_ERROR }
     }
     pub fn invalid_gl_program(program_error: String) -> Self {
         Self { kind: ErrorKind::InvalidGLProgram(program_error), options: options::RESOURCE_ERROR }
     }
+    /* Where errors of this kind are shown in the log */
+    pub fn get_subsystem(&self) -> Subsystem {
+        match self.kind {
+            ErrorKind::InvalidShaderSource(_) | ErrorKind::ShaderCompile(..) | ErrorKind::InvalidGLProgram(_) => Subsystem::Shader,
+            _ if self.options & options::PARSER_ERROR != 0 => Subsystem::Parser,
+            _ if self.options & options::RESOURCE_ERROR != 0 => Subsystem::Resource,
+            _ => Subsystem::General,
+        }
+    }
+
     pub fn invalid_home_dir() -> Self {
         Self { kind: ErrorKind::InvalidHomeDir, options: options::RESOURCE_ERROR }
     }
+    pub fn invalid_parent(object_name: &str, parent_name: &str) -> Self {
+        Self { kind: ErrorKind::InvalidParent(String::from(object_name), String::from(parent_name)), options: options::RESOURCE_ERROR }
+    }
+
+    pub fn missing_attribute_index(attribute_name: &str) -> Self {
+        Self { kind: ErrorKind::MissingAttributeIndex(String::from(attribute_name)), options: options::RESOURCE_ERROR }
+    }
+
+    pub fn asset_not_found(asset_name: &str, searched: Vec<String>) -> Self {
+        Self { kind: ErrorKind::AssetNotFound(String::from(asset_name), searched), options: options::RESOURCE_ERROR }
+    }
+    pub fn is_asset_not_found(&self) -> bool {
+        matches!(self.kind, ErrorKind::AssetNotFound(..))
+    }
+
+    pub fn load_cancelled() -> Self {
+        Self { kind: ErrorKind::LoadCancelled, options: options::RESOURCE_ERROR }
+    }
+    pub fn is_load_cancelled(&self) -> bool {
+        matches!(self.kind, ErrorKind::LoadCancelled)
+    }
+    pub fn load_panicked(file_name: &str, message: String) -> Self {
+        Self { kind: ErrorKind::LoadPanicked(file_name.to_string(), message), options: options::RESOURCE_ERROR }
+    }
+    pub fn invalid_mesh_cache(message: String) -> Self {
+        Self { kind: ErrorKind::InvalidMeshCache(message), options: options::RESOURCE_ERROR }
+    }
+    pub fn invalid_mesh(mesh_name: &str, problems: String) -> Self {
+        Self { kind: ErrorKind::InvalidMesh(mesh_name.to_string(), problems), options: options::RESOURCE_ERROR }
+    }
+    pub fn too_many_triangles(count: usize, budget: usize) -> Self {
+        Self { kind: ErrorKind::TooManyTriangles(count, budget), options: options::RESOURCE_ERROR }
+    }
 
     pub fn value_already_exists() -> Self {
         Self { kind: ErrorKind::ValueDoesNotExist, options: options::REF_DICT_ERROR }
@@ -263,6 +346,12 @@ impl Error {
     pub fn ply_parser(message: String) -> Self {
         Self { kind: ErrorKind::PlyParser(message), options: options::PARSER_ERROR }
     }
+    pub fn key_binding_parser(message: String, line_number: usize) -> Self {
+        Self { kind: ErrorKind::KeyBindingParser(message, line_number), options: options::PARSER_ERROR }
+    }
+    pub fn obj_parser(message: String, line_number: usize) -> Self {
+        Self { kind: ErrorKind::ObjParser(message, line_number), options: options::PARSER_ERROR }
+    }
 }
 
 impl PartialEq for Error {
@@ -0,0 +1,230 @@
+/* a global log shown in the console panel instead of printing to stdout */
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Instant;
+
+use crate::util::error::Error;
+
+/* Oldest entries are dropped past this */
+const MAX_ENTRIES: usize = 5000;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [LogLevel::Debug, LogLevel::Info, LogLevel::Warning, LogLevel::Error];
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Debug => write!(f, "debug"),
+            Self::Info => write!(f, "info"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Subsystem {
+    General,
+    Resource,
+    Shader,
+    Parser,
+    Scene,
+    Ui,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 6] = [
+        Subsystem::General,
+        Subsystem::Resource,
+        Subsystem::Shader,
+        Subsystem::Parser,
+        Subsystem::Scene,
+        Subsystem::Ui,
+    ];
+}
+
+impl Display for Subsystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::General => write!(f, "general"),
+            Self::Resource => write!(f, "resource"),
+            Self::Shader => write!(f, "shader"),
+            Self::Parser => write!(f, "parser"),
+            Self::Scene => write!(f, "scene"),
+            Self::Ui => write!(f, "ui"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub subsystem: Subsystem,
+    pub message: String,
+    /* Extra information such as the ErrorKind of an error, shown when the entry is expanded */
+    pub details: Option<String>,
+    /* Seconds since the log was started */
+    pub time: f64,
+}
+
+struct Log {
+    entries: VecDeque<LogEntry>,
+    /* Entries of every level still in the log, indexed by LogLevel */
+    counts: [usize; 4],
+    start: Instant,
+    /* Bumped on every change so views can tell when to refresh */
+    revision: u64,
+}
+
+impl Log {
+    fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            counts: [0; 4],
+            start: Instant::now(),
+            revision: 0,
+        }
+    }
+
+    fn push(&mut self, level: LogLevel, subsystem: Subsystem, message: String, details: Option<String>) {
+        let time = self.start.elapsed().as_secs_f64();
+        self.entries.push_back(LogEntry { level, subsystem, message, details, time });
+        self.counts[level as usize] += 1;
+        if self.entries.len() > MAX_ENTRIES {
+            if let Some(oldest) = self.entries.pop_front() {
+                self.counts[oldest.level as usize] -= 1;
+            }
+        }
+        self.revision += 1;
+    }
+
+    fn get_entries(&self, filter: impl Fn(&LogEntry) -> bool) -> Vec<LogEntry> {
+        self.entries.iter().filter(|entry| filter(entry)).cloned().collect()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.counts = [0; 4];
+        self.revision += 1;
+    }
+}
+
+fn global_log() -> MutexGuard<'static, Log> {
+    static LOG: OnceLock<Mutex<Log>> = OnceLock::new();
+    match LOG.get_or_init(|| Mutex::new(Log::new())).lock() {
+        Ok(log) => log,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+pub fn log_with_details(level: LogLevel, subsystem: Subsystem, message: String, details: Option<String>) {
+    global_log().push(level, subsystem, message, details);
+}
+
+pub fn log(level: LogLevel, subsystem: Subsystem, message: impl Into<String>) {
+    log_with_details(level, subsystem, message.into(), None);
+}
+
+pub fn debug(subsystem: Subsystem, message: impl Into<String>) {
+    log(LogLevel::Debug, subsystem, message);
+}
+pub fn info(subsystem: Subsystem, message: impl Into<String>) {
+    log(LogLevel::Info, subsystem, message);
+}
+pub fn warning(subsystem: Subsystem, message: impl Into<String>) {
+    log(LogLevel::Warning, subsystem, message);
+}
+
+/* Logs error under the subsystem it came from, context says what was being done */
+pub fn error(context: &str, error: &Error) {
+    log_with_details(
+        LogLevel::Error,
+        error.get_subsystem(),
+        format!("{}: {}", context, error),
+        Some(format!("{:?}", error)));
+}
+
+/* Copies of the entries that pass filter, oldest first */
+pub fn get_entries(filter: impl Fn(&LogEntry) -> bool) -> Vec<LogEntry> {
+    global_log().get_entries(filter)
+}
+
+/* Number of entries of level in the log, without copying them */
+pub fn get_count(level: LogLevel) -> usize {
+    global_log().counts[level as usize]
+}
+
+pub fn get_revision() -> u64 {
+    global_log().revision
+}
+
+pub fn clear() {
+    global_log().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* A log of its own, the global one is shared with every test running in parallel */
+    fn filled() -> Log {
+        let mut log = Log::new();
+        for level in LogLevel::ALL {
+            log.push(level, Subsystem::General, level.to_string(), None);
+        }
+        log.push(LogLevel::Error, Subsystem::Parser, String::from("second error"), Some(String::from("details")));
+        log
+    }
+
+    #[test]
+    fn filters_by_level() {
+        let log = filled();
+        let messages = |min_level: LogLevel| -> Vec<String> {
+            log.get_entries(|entry| entry.level >= min_level).into_iter().map(|entry| entry.message).collect()
+        };
+        assert_eq!(messages(LogLevel::Debug), ["debug", "info", "warning", "error", "second error"]);
+        assert_eq!(messages(LogLevel::Warning), ["warning", "error", "second error"]);
+        assert_eq!(messages(LogLevel::Error), ["error", "second error"]);
+        let parser = log.get_entries(|entry| entry.subsystem == Subsystem::Parser);
+        assert_eq!(parser.len(), 1);
+        assert_eq!(parser[0].details.as_deref(), Some("details"));
+    }
+
+    #[test]
+    fn counts_errors_and_warnings() {
+        let mut log = filled();
+        assert_eq!(log.counts[LogLevel::Error as usize], 2);
+        assert_eq!(log.counts[LogLevel::Warning as usize], 1);
+        let revision = log.revision;
+        log.clear();
+        assert_eq!(log.counts, [0; 4]);
+        assert!(log.entries.is_empty());
+        assert!(log.revision > revision);
+    }
+
+    #[test]
+    fn drops_the_oldest_at_capacity() {
+        let mut log = filled();
+        for index in 0..MAX_ENTRIES - 2 {
+            log.push(LogLevel::Info, Subsystem::General, index.to_string(), None);
+        }
+        // The debug, info and warning entries were the oldest
+        assert_eq!(log.entries.len(), MAX_ENTRIES);
+        assert_eq!(log.entries[0].message, "error");
+        assert_eq!(log.entries[MAX_ENTRIES - 1].message, (MAX_ENTRIES - 3).to_string());
+        assert_eq!(log.counts, [0, MAX_ENTRIES - 2, 0, 2]);
+        let times: Vec<f64> = log.entries.iter().map(|entry| entry.time).collect();
+        assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
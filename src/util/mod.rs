@@ -1,3 +1,6 @@
 pub mod ref_dict;
 pub mod util;
 pub mod error;
+pub mod log;
+#[cfg(test)]
+pub mod test_util;
@@ -0,0 +1,62 @@
+/* fixtures shared by the tests of every module */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use glam::Vec3;
+
+use crate::resources::container::{new_container_ref, ContainerRef};
+use crate::resources::embedded::get_embedded;
+use crate::resources::material::Material;
+use crate::resources::mesh::{Mesh, MeshData};
+use crate::resources::mesh_loader::LoadProgress;
+use crate::resources::mesh_normals::position_key;
+use crate::resources::object::Object;
+use crate::resources::parser::{parse_mesh_data, parse_polygon_mesh};
+use crate::resources::polygon_mesh::PolygonMesh;
+
+/* The built-in ply, 162 vertexes with normals and 320 triangles on the unit sphere */
+pub fn icosphere_ply() -> Vec<u8> {
+    get_embedded("meshes/icosphere.ply").unwrap().to_vec()
+}
+
+pub fn icosphere() -> MeshData {
+    parse_mesh_data(icosphere_ply(), &LoadProgress::new()).unwrap()
+}
+
+pub fn icosphere_polygons() -> PolygonMesh {
+    parse_polygon_mesh(icosphere_ply(), &LoadProgress::new()).unwrap()
+}
+
+/* An icosphere object that is never drawn, so scenes can be tested without gl */
+pub fn object(name: &str) -> ContainerRef<Object> {
+    let material = new_container_ref(Material::create_solid_color(Vec3::ONE, name));
+    let mesh = new_container_ref(Mesh::from_data_without_gl(icosphere(), name));
+    new_container_ref(Object::without_gl(material, mesh, name))
+}
+
+/* Corner i of the cube from -1 to 1, bit 0 picks x, bit 1 y and bit 2 z */
+pub fn cube_corner(i: usize) -> Vec3 {
+    Vec3::new((i & 1) as f32 * 2.0 - 1.0, (i >> 1 & 1) as f32 * 2.0 - 1.0, (i >> 2 & 1) as f32 * 2.0 - 1.0)
+}
+
+/* Every edge, with vertexes at the same place merged, is shared by exactly two triangles */
+pub fn is_closed(data: &MeshData) -> bool {
+    let size = data.shader.get_vertex_size();
+    let key = |index: u32| position_key(Vec3::from_slice(&data.vertexes[index as usize * size..]));
+    let mut edges: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+    for triangle in data.indexes.as_ref().unwrap().chunks_exact(3) {
+        for corner in 0..3 {
+            let (a, b) = (key(triangle[corner]), key(triangle[(corner + 1) % 3]));
+            *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    edges.values().all(|count| *count == 2)
+}
+
+/* A directory of its own for a test, the process id keeps parallel runs apart */
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusty_fractal_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
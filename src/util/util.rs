@@ -1 +1,15 @@
 pub type GetSetValue<'a, T> = Box<dyn 'a + FnMut(Option<T>) -> T>;
+
+/* Splits a double into a high and low float so that high + low ≈ value. Used to pass f64 positions to shaders */
+pub fn split_f64(value: f64) -> (f32, f32) {
+    let high = value as f32;
+    let low = (value - high as f64) as f32;
+    (high, low)
+}
+
+pub fn split_dvec3(value: glam::DVec3) -> (glam::Vec3, glam::Vec3) {
+    let (x_high, x_low) = split_f64(value.x);
+    let (y_high, y_low) = split_f64(value.y);
+    let (z_high, z_low) = split_f64(value.z);
+    (glam::Vec3::new(x_high, y_high, z_high), glam::Vec3::new(x_low, y_low, z_low))
+}